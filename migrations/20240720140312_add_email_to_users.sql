@@ -0,0 +1,3 @@
+-- Users need an email address to receive password reset links
+ALTER TABLE users
+ADD COLUMN email TEXT NULL UNIQUE;
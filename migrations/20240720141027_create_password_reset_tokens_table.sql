@@ -0,0 +1,9 @@
+-- Only the SHA-256 hash of the token is stored, never the token itself
+CREATE TABLE password_reset_tokens (
+    token_hash TEXT NOT NULL,
+    user_id UUID NOT NULL REFERENCES users (user_id),
+    created_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ NULL,
+    PRIMARY KEY (token_hash)
+);
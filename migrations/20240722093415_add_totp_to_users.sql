@@ -0,0 +1,5 @@
+-- A secret that is set while `totp_enabled` is false is a pending enrolment
+ALTER TABLE users
+ADD COLUMN totp_secret TEXT NULL,
+ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+ADD COLUMN totp_last_used_step BIGINT NULL;
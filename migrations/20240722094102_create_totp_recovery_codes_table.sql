@@ -0,0 +1,6 @@
+CREATE TABLE totp_recovery_codes (
+    user_id UUID NOT NULL REFERENCES users (user_id),
+    code_hash TEXT NOT NULL,
+    used_at TIMESTAMPTZ NULL,
+    PRIMARY KEY (user_id, code_hash)
+);
@@ -0,0 +1,2 @@
+-- How many subscribers an issue was queued for, to report delivery progress.
+ALTER TABLE newsletter_issues ADD COLUMN n_recipients INTEGER NOT NULL DEFAULT 0;
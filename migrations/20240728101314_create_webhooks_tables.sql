@@ -0,0 +1,33 @@
+CREATE TABLE webhook_endpoints (
+    endpoint_id UUID PRIMARY KEY,
+    url TEXT NOT NULL,
+    -- Receivers need the same secret to verify signatures: it cannot be hashed.
+    secret TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE webhook_events (
+    event_id UUID PRIMARY KEY,
+    event_type TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+-- One row per event and endpoint, written in the same transaction as the
+-- change that triggered the event, and deleted once it has been delivered.
+CREATE TABLE webhook_outbox (
+    event_id UUID NOT NULL REFERENCES webhook_events (event_id) ON DELETE CASCADE,
+    endpoint_id UUID NOT NULL REFERENCES webhook_endpoints (endpoint_id) ON DELETE CASCADE,
+    n_attempts SMALLINT NOT NULL DEFAULT 0,
+    execute_after TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (event_id, endpoint_id)
+);
+
+-- Set once the last delivery task of the issue has been processed.
+ALTER TABLE newsletter_issues ADD COLUMN delivered_at TIMESTAMPTZ NULL;
+UPDATE newsletter_issues i
+SET delivered_at = now()
+WHERE NOT EXISTS (
+    SELECT 1 FROM issue_delivery_queue q
+    WHERE q.newsletter_issue_id = i.newsletter_issue_id
+);
@@ -0,0 +1,3 @@
+-- Subscribers the email provider can no longer deliver to are moved to the
+-- 'suppressed' status. Soft bounces are only counted.
+ALTER TABLE subscriptions ADD COLUMN n_soft_bounces INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,7 @@
+-- Addresses that must never be mailed, whatever their subscription status.
+-- They are stored lower-cased, so lookups are case-insensitive.
+CREATE TABLE suppressed_emails(
+    email TEXT NOT NULL PRIMARY KEY,
+    reason TEXT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,10 @@
+-- One row per recipient who opened an issue, as reported by the tracking pixel.
+CREATE TABLE newsletter_issue_opens(
+    newsletter_issue_id UUID NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    subscriber_id UUID NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    first_opened_at TIMESTAMPTZ NOT NULL,
+    n_opens INTEGER NOT NULL DEFAULT 1,
+    PRIMARY KEY (newsletter_issue_id, subscriber_id)
+);
@@ -0,0 +1,11 @@
+-- One row per recipient and link they clicked in an issue.
+CREATE TABLE newsletter_issue_clicks(
+    newsletter_issue_id UUID NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    subscriber_id UUID NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    first_clicked_at TIMESTAMPTZ NOT NULL,
+    n_clicks INTEGER NOT NULL DEFAULT 1,
+    PRIMARY KEY (newsletter_issue_id, subscriber_id, url)
+);
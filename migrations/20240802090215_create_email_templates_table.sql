@@ -0,0 +1,20 @@
+-- Layouts wrapping the body of emails: header, footer, legal address...
+CREATE TABLE email_templates (
+    template_id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    html_layout TEXT NOT NULL,
+    text_layout TEXT NOT NULL,
+    -- Wraps the emails sent by the application, e.g. confirmations,
+    -- and is preselected for new issues.
+    is_default BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+CREATE UNIQUE INDEX email_templates_single_default ON email_templates (is_default)
+WHERE is_default;
+
+-- Issues keep a copy of the layout they were published with, so that
+-- editing a template does not change issues that are being delivered.
+ALTER TABLE newsletter_issues
+    ADD COLUMN html_layout TEXT NULL,
+    ADD COLUMN text_layout TEXT NULL;
@@ -0,0 +1,3 @@
+-- The source of issues written in Markdown, kept for later editing.
+-- Their HTML and plain text contents are rendered from it when published.
+ALTER TABLE newsletter_issues ADD COLUMN markdown_content TEXT NULL;
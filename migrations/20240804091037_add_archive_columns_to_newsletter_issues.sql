@@ -0,0 +1,18 @@
+-- Issues are listed in the public archive unless they are excluded,
+-- under a slug derived from their title that never changes afterwards.
+-- Issues published so far were written for subscribers only: they stay
+-- out until they are added from their report. New issues always say.
+ALTER TABLE newsletter_issues
+    ADD COLUMN in_archive BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN slug TEXT NULL;
+ALTER TABLE newsletter_issues ALTER COLUMN in_archive DROP DEFAULT;
+
+-- Issues published so far get the id in their slug to keep it unique.
+UPDATE newsletter_issues
+SET slug = COALESCE(
+    NULLIF(TRIM(BOTH '-' FROM LOWER(REGEXP_REPLACE(title, '[^A-Za-z0-9]+', '-', 'g'))), ''),
+    'issue'
+) || '-' || LEFT(newsletter_issue_id::text, 8);
+
+ALTER TABLE newsletter_issues ALTER COLUMN slug SET NOT NULL;
+ALTER TABLE newsletter_issues ADD CONSTRAINT newsletter_issues_slug_key UNIQUE (slug);
@@ -0,0 +1,7 @@
+-- `published_at` was filled with `NOW()` cast to text, which Postgres
+-- parses back into the same instant.
+ALTER TABLE newsletter_issues
+    ALTER COLUMN published_at TYPE timestamptz USING published_at::timestamptz;
+
+-- Archive pages and feeds list the most recent issues first.
+CREATE INDEX newsletter_issues_published_at_idx ON newsletter_issues (published_at DESC);
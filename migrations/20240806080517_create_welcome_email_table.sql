@@ -0,0 +1,21 @@
+-- The email sent to subscribers once they confirm their subscription.
+-- There is at most one row, created the first time it is saved.
+CREATE TABLE welcome_email (
+    id BOOLEAN PRIMARY KEY DEFAULT true CHECK (id),
+    enabled BOOLEAN NOT NULL,
+    subject TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+-- Welcome emails go through the delivery queue too, without an issue.
+ALTER TABLE issue_delivery_queue DROP CONSTRAINT issue_delivery_queue_pkey;
+ALTER TABLE issue_delivery_queue
+    ADD COLUMN task_id UUID NOT NULL DEFAULT gen_random_uuid() PRIMARY KEY,
+    ALTER COLUMN newsletter_issue_id DROP NOT NULL;
+-- NULLs are distinct: a subscriber can have several welcome emails queued,
+-- but an issue is still only queued once for them.
+ALTER TABLE issue_delivery_queue
+    ADD CONSTRAINT issue_delivery_queue_issue_recipient_key
+    UNIQUE (newsletter_issue_id, subscriber_email);
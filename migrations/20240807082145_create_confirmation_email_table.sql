@@ -0,0 +1,16 @@
+-- The email asking new subscribers to confirm their subscription.
+-- There is exactly one row: the email cannot be turned off.
+CREATE TABLE confirmation_email (
+    id BOOLEAN PRIMARY KEY DEFAULT true CHECK (id),
+    subject TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+INSERT INTO confirmation_email (subject, html_content, text_content, updated_at)
+VALUES (
+    'Welcome!',
+    'Welcome to our newsletter!<br>Click <a href="{{ confirmation_link }}">here</a> to confirm your subscription.',
+    E'Welcome to our newsletter!\nVisit {{ confirmation_link }} to confirm your subscription.',
+    now()
+);
@@ -0,0 +1,4 @@
+-- Failed deliveries are postponed rather than dropped, like webhooks.
+ALTER TABLE issue_delivery_queue
+    ADD COLUMN n_attempts SMALLINT NOT NULL DEFAULT 0,
+    ADD COLUMN execute_after TIMESTAMPTZ NOT NULL DEFAULT now();
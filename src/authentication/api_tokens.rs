@@ -0,0 +1,251 @@
+use actix_web::http::Method;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Makes tokens easy to recognise, e.g. by secret scanners.
+const TOKEN_PREFIX: &str = "z2p_";
+
+/// What an API token is allowed to do.
+/// Tokens are only accepted by the endpoints that require one of these scopes:
+/// everything else keeps requiring a browser session.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ApiScope {
+    PublishNewsletters,
+    ReadNewsletters,
+    ReadSubscribers,
+    ManageSubscribers,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 4] = [
+        ApiScope::PublishNewsletters,
+        ApiScope::ReadNewsletters,
+        ApiScope::ReadSubscribers,
+        ApiScope::ManageSubscribers,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::PublishNewsletters => "newsletters:publish",
+            ApiScope::ReadNewsletters => "newsletters:read",
+            ApiScope::ReadSubscribers => "subscribers:read",
+            ApiScope::ManageSubscribers => "subscribers:write",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ApiScope::PublishNewsletters => "Publish newsletter issues",
+            ApiScope::ReadNewsletters => "Read newsletter issues and their delivery status",
+            ApiScope::ReadSubscribers => "Read subscribers",
+            ApiScope::ManageSubscribers => "Create, update and delete subscribers",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<ApiScope> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == s)
+    }
+
+    /// The scope a token needs to call an endpoint,
+    /// or `None` if the endpoint does not accept tokens.
+    pub fn required_for(method: &Method, path: &str) -> Option<ApiScope> {
+        match (method, path) {
+            (&Method::POST, "/admin/newsletters" | "/api/v1/newsletters") => {
+                Some(ApiScope::PublishNewsletters)
+            }
+            (&Method::GET, "/api/v1/newsletters") => Some(ApiScope::ReadNewsletters),
+            (&Method::GET, path) if path.starts_with("/api/v1/newsletters/") => {
+                Some(ApiScope::ReadNewsletters)
+            }
+            (method, path)
+                if path == "/api/v1/subscribers" || path.starts_with("/api/v1/subscribers/") =>
+            {
+                match *method {
+                    Method::GET => Some(ApiScope::ReadSubscribers),
+                    Method::POST | Method::PATCH | Method::DELETE => {
+                        Some(ApiScope::ManageSubscribers)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+pub struct ApiTokenSummary {
+    pub token_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// Generate a random token: it is only ever shown to the user once.
+fn generate_api_token() -> Secret<String> {
+    let mut rng = thread_rng();
+    let random: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(40)
+        .collect();
+    Secret::new(format!("{TOKEN_PREFIX}{random}"))
+}
+
+/// Tokens are stored hashed, like passwords, but they are long and random
+/// enough for a fast hash to be safe.
+fn hash_api_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[tracing::instrument(name = "Create API token", skip(pool))]
+pub async fn create_api_token(
+    user_id: Uuid,
+    name: &str,
+    scopes: &[ApiScope],
+    pool: &PgPool,
+) -> Result<Secret<String>, anyhow::Error> {
+    let token = generate_api_token();
+    let scopes: Vec<String> = scopes.iter().map(|s| s.as_str().to_owned()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (token_id, user_id, name, token_hash, scopes, created_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        name,
+        hash_api_token(token.expose_secret()),
+        &scopes
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the API token.")?;
+
+    Ok(token)
+}
+
+#[tracing::instrument(name = "List API tokens", skip(pool))]
+pub async fn list_api_tokens(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<Vec<ApiTokenSummary>, anyhow::Error> {
+    let tokens = sqlx::query_as!(
+        ApiTokenSummary,
+        r#"
+        SELECT token_id, name, scopes, created_at, last_used_at
+        FROM api_tokens
+        WHERE user_id = $1 AND revoked_at IS NULL
+        ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the API tokens of the user.")?;
+
+    Ok(tokens)
+}
+
+#[tracing::instrument(name = "Revoke API token", skip(pool))]
+pub async fn revoke_api_token(
+    user_id: Uuid,
+    token_id: Uuid,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE api_tokens
+        SET revoked_at = now()
+        WHERE token_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        token_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke the API token.")?;
+
+    Ok(())
+}
+
+/// Return the owner and the scopes of a valid token, recording its use.
+#[tracing::instrument(name = "Authenticate API token", skip(token, pool))]
+pub async fn authenticate_api_token(
+    token: &str,
+    pool: &PgPool,
+) -> Result<Option<(Uuid, Vec<ApiScope>)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE api_tokens
+        SET last_used_at = now()
+        WHERE token_hash = $1 AND revoked_at IS NULL
+        RETURNING user_id, scopes
+        "#,
+        hash_api_token(token)
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the API token.")?;
+
+    Ok(row.map(|row| {
+        let scopes = row
+            .scopes
+            .iter()
+            .filter_map(|s| ApiScope::parse(s))
+            .collect();
+        (row.user_id, scopes)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiScope;
+    use actix_web::http::Method;
+    use claims::{assert_none, assert_some_eq};
+
+    #[test]
+    fn scopes_round_trip_through_their_name() {
+        for scope in ApiScope::ALL {
+            assert_some_eq!(ApiScope::parse(scope.as_str()), scope);
+        }
+        assert_none!(ApiScope::parse("admin"));
+    }
+
+    #[test]
+    fn tokens_are_only_accepted_by_scoped_endpoints() {
+        assert_some_eq!(
+            ApiScope::required_for(&Method::POST, "/admin/newsletters"),
+            ApiScope::PublishNewsletters
+        );
+        assert_none!(ApiScope::required_for(&Method::POST, "/admin/password"));
+        assert_none!(ApiScope::required_for(&Method::GET, "/admin/api-tokens"));
+    }
+
+    #[test]
+    fn reading_and_publishing_newsletters_are_separate_scopes() {
+        assert_some_eq!(
+            ApiScope::required_for(&Method::POST, "/api/v1/newsletters"),
+            ApiScope::PublishNewsletters
+        );
+        assert_some_eq!(
+            ApiScope::required_for(&Method::GET, "/api/v1/newsletters"),
+            ApiScope::ReadNewsletters
+        );
+        assert_some_eq!(
+            ApiScope::required_for(
+                &Method::GET,
+                &format!("/api/v1/newsletters/{}", uuid::Uuid::new_v4())
+            ),
+            ApiScope::ReadNewsletters
+        );
+        assert_none!(ApiScope::required_for(
+            &Method::DELETE,
+            "/api/v1/newsletters"
+        ));
+    }
+}
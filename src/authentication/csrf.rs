@@ -0,0 +1,87 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    error::ErrorForbidden,
+    http::Method,
+    web, FromRequest, HttpMessage,
+};
+use actix_web_lab::middleware::Next;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+use crate::{
+    authentication::middleware::AuthenticatedWithApiToken, session_state::TypedSession, utils::e500,
+};
+
+/// The name of the hidden field carrying the token in every form.
+const CSRF_TOKEN_FIELD: &str = "csrf_token";
+
+/// The synchronizer token of the session, generated on first use.
+/// It must be embedded in every form that changes state, and only there:
+/// generating it stores a session for the visitor.
+pub fn csrf_token(session: &TypedSession) -> Result<String, anyhow::Error> {
+    if let Some(token) = session.get_csrf_token()? {
+        return Ok(token);
+    }
+    rotate_csrf_token(session)
+}
+
+/// Replace the token of the session with a fresh one, e.g. when the session
+/// is renewed at login: a token picked up before logging in must not be
+/// usable afterwards.
+pub fn rotate_csrf_token(session: &TypedSession) -> Result<String, anyhow::Error> {
+    let mut rng = thread_rng();
+    let token: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect();
+    session.insert_csrf_token(&token)?;
+    Ok(token)
+}
+
+/// Reject state-changing requests whose form does not carry
+/// the CSRF token of the session with a 403.
+pub async fn reject_invalid_csrf_tokens(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS)
+        || req.extensions().contains::<AuthenticatedWithApiToken>()
+    {
+        return next.call(req).await;
+    }
+
+    let (session, body) = {
+        let (http_request, payload) = req.parts_mut();
+        let session = TypedSession::from_request(http_request, payload).await?;
+        let body = web::Bytes::from_request(http_request, payload).await?;
+        (session, body)
+    };
+    // The handler still has to read the form.
+    req.set_payload(bytes_to_payload(body.clone()));
+
+    let submitted_token = form_urlencoded::parse(&body)
+        .find(|(key, _)| key == CSRF_TOKEN_FIELD)
+        .map(|(_, value)| value.into_owned());
+    let expected_token = session.get_csrf_token().map_err(e500)?;
+    match (submitted_token, expected_token) {
+        (Some(submitted), Some(expected)) if constant_time_eq(&submitted, &expected) => {
+            next.call(req).await
+        }
+        _ => Err(ErrorForbidden("Missing or invalid CSRF token.")),
+    }
+}
+
+fn bytes_to_payload(body: web::Bytes) -> Payload {
+    let (_, mut payload) = actix_http::h1::Payload::create(true);
+    payload.unread_data(body);
+    Payload::from(payload)
+}
+
+/// Compare without leaking how many leading characters match.
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
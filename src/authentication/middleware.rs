@@ -1,14 +1,20 @@
 use crate::{
+    authentication::api_tokens::{authenticate_api_token, ApiScope},
+    routes::ApiError,
+    session_registry::{SessionActivity, SessionRegistry},
     session_state::TypedSession,
-    utils::{e500, see_other},
+    utils::{client_ip, e500, see_other},
 };
 use actix_web::{
     body::MessageBody,
     dev::{ServiceRequest, ServiceResponse},
-    error::InternalError,
-    FromRequest, HttpMessage,
+    error::{ErrorForbidden, InternalError},
+    http::header::{HeaderMap, AUTHORIZATION, WWW_AUTHENTICATE},
+    web, FromRequest, HttpMessage, HttpResponse,
 };
+use actix_web_flash_messages::FlashMessage;
 use actix_web_lab::middleware::Next;
+use sqlx::PgPool;
 use std::ops::Deref;
 use uuid::Uuid;
 
@@ -29,24 +35,176 @@ impl Deref for UserId {
     }
 }
 
+/// The registry id of the session the request was made with.
+#[derive(Copy, Clone, Debug)]
+pub struct SessionId(Uuid);
+
+impl Deref for SessionId {
+    type Target = Uuid;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Marks requests authenticated with an API token rather than a session.
+/// They carry no cookies, hence they are not exposed to CSRF.
+#[derive(Copy, Clone, Debug)]
+pub struct AuthenticatedWithApiToken;
+
+/// Guard the JSON API: unlike the admin pages, it only accepts API tokens.
+pub async fn reject_requests_without_api_token(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let token = bearer_token(req.headers())
+        .ok_or_else(|| ApiError::Unauthorized("An API token is required.".into()))?;
+    let user_id = authenticate_with_api_token(&req, &token)
+        .await
+        .map_err(ApiError::from)?;
+    req.extensions_mut().insert(UserId(user_id));
+    req.extensions_mut().insert(AuthenticatedWithApiToken);
+    next.call(req).await
+}
+
 pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
-    next: Next<impl MessageBody>,
+    next: Next<impl MessageBody + 'static>,
 ) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if let Some(token) = bearer_token(req.headers()) {
+        let user_id = authenticate_with_api_token(&req, &token).await?;
+        req.extensions_mut().insert(UserId(user_id));
+        req.extensions_mut().insert(AuthenticatedWithApiToken);
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+
     let session = {
         let (http_request, payload) = req.parts_mut();
         TypedSession::from_request(http_request, payload).await
     }?;
 
-    match session.get_user_id().map_err(e500)? {
-        Some(user_id) => {
-            req.extensions_mut().insert(UserId(user_id));
-            next.call(req).await
-        }
+    let user_id = match session.get_user_id().map_err(e500)? {
+        Some(user_id) => user_id,
         None => {
             let response = see_other("/login");
             let e = anyhow::anyhow!("The user has not logged in.");
-            Err(InternalError::from_response(e, response).into())
+            return Err(InternalError::from_response(e, response).into());
+        }
+    };
+
+    // A session that is missing from the registry has been revoked,
+    // e.g. because the password of the user has been reset.
+    let registry = req
+        .app_data::<web::Data<SessionRegistry>>()
+        .expect("The session registry is not registered as application data.");
+    let session_id = session.get_session_id().map_err(e500)?;
+    let is_active = match session_id {
+        Some(session_id) => registry
+            .is_active(user_id, session_id)
+            .await
+            .map_err(e500)?,
+        None => false,
+    };
+    let session_id = match session_id {
+        Some(session_id) if is_active => session_id,
+        _ => {
+            session.log_out();
+            let response = see_other("/login");
+            let e = anyhow::anyhow!("The session has been revoked.");
+            return Err(InternalError::from_response(e, response).into());
+        }
+    };
+    match registry
+        .touch(session_id, &client_ip(req.request()))
+        .await
+        .map_err(e500)?
+    {
+        SessionActivity::Active => {}
+        SessionActivity::Expired => {
+            registry.revoke(user_id, session_id).await.map_err(e500)?;
+            session.log_out();
+            // Returned as a response rather than an error: errors bubble up
+            // past the flash messages middleware without their messages.
+            FlashMessage::error("Your session has expired. Please log in again.").send();
+            return Ok(req.into_response(see_other("/login")).map_into_right_body());
+        }
+    }
+
+    req.extensions_mut().insert(UserId(user_id));
+    req.extensions_mut().insert(SessionId(session_id));
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim().to_owned())
+}
+
+#[derive(thiserror::Error, Debug)]
+enum ApiTokenError {
+    #[error("Invalid API token.")]
+    InvalidToken,
+    #[error("The API token is missing the `{0}` scope.")]
+    MissingScope(&'static str),
+    #[error("This endpoint does not accept API tokens.")]
+    EndpointNotAllowed,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl From<ApiTokenError> for actix_web::Error {
+    fn from(e: ApiTokenError) -> Self {
+        match e {
+            ApiTokenError::InvalidToken => {
+                let response = HttpResponse::Unauthorized()
+                    .insert_header((WWW_AUTHENTICATE, "Bearer"))
+                    .finish();
+                InternalError::from_response(e, response).into()
+            }
+            ApiTokenError::MissingScope(_) | ApiTokenError::EndpointNotAllowed => {
+                ErrorForbidden(e.to_string())
+            }
+            ApiTokenError::UnexpectedError(e) => e500(e),
+        }
+    }
+}
+
+impl From<ApiTokenError> for ApiError {
+    fn from(e: ApiTokenError) -> Self {
+        match e {
+            ApiTokenError::InvalidToken => ApiError::Unauthorized(e.to_string()),
+            ApiTokenError::MissingScope(_) | ApiTokenError::EndpointNotAllowed => {
+                ApiError::Forbidden(e.to_string())
+            }
+            ApiTokenError::UnexpectedError(e) => ApiError::UnexpectedError(e),
         }
     }
 }
+
+/// Return the owner of the token, if it is valid and allowed to call the endpoint.
+async fn authenticate_with_api_token(
+    req: &ServiceRequest,
+    token: &str,
+) -> Result<Uuid, ApiTokenError> {
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .expect("The database pool is not registered as application data.");
+    let (user_id, scopes) = authenticate_api_token(token, pool)
+        .await?
+        .ok_or(ApiTokenError::InvalidToken)?;
+
+    match ApiScope::required_for(req.method(), req.path()) {
+        Some(scope) if scopes.contains(&scope) => Ok(user_id),
+        Some(scope) => Err(ApiTokenError::MissingScope(scope.as_str())),
+        None => Err(ApiTokenError::EndpointNotAllowed),
+    }
+}
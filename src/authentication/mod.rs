@@ -1,6 +1,30 @@
+mod api_tokens;
+mod csrf;
 mod middleware;
 mod password;
+mod password_policy;
+mod password_reset;
+mod throttle;
+mod totp;
 
-pub use middleware::reject_anonymous_users;
-pub use middleware::UserId;
-pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub use api_tokens::{
+    create_api_token, list_api_tokens, revoke_api_token, ApiScope, ApiTokenSummary,
+};
+pub(crate) use csrf::constant_time_eq;
+pub use csrf::{csrf_token, reject_invalid_csrf_tokens, rotate_csrf_token};
+pub use middleware::{reject_anonymous_users, reject_requests_without_api_token};
+pub use middleware::{SessionId, UserId};
+pub use password::{
+    change_password, validate_credentials, AuthError, Credentials, PasswordHashingPolicy,
+};
+pub use password_policy::{PasswordPolicy, PasswordPolicyViolation};
+pub use password_reset::{
+    consume_reset_token, generate_reset_token, get_user_by_email, store_reset_token,
+    validate_reset_token, RESET_TOKEN_TTL_MINUTES,
+};
+pub use throttle::{LoginThrottle, ThrottleDecision};
+pub use totp::{
+    disable_totp, enable_totp, generate_recovery_codes, generate_totp_secret, get_totp_settings,
+    provisioning_uri, store_pending_totp_secret, time_step, totp_code, use_recovery_code,
+    verify_totp, TotpSettings,
+};
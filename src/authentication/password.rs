@@ -4,9 +4,9 @@ use argon2::{
     PasswordVerifier, Version,
 };
 use secrecy::{ExposeSecret, Secret};
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 
-use crate::telemetry::spawn_blocking_with_tracing;
+use crate::{configuration::PasswordHashingSettings, telemetry::spawn_blocking_with_tracing};
 
 pub struct Credentials {
     pub username: String,
@@ -21,18 +21,58 @@ pub enum AuthError {
     UnexpectedError(#[from] anyhow::Error),
 }
 
-#[tracing::instrument(name = "Validate credentials", skip(credentials, pool))]
+/// The Argon2 parameters new password hashes are computed with.
+#[derive(Clone)]
+pub struct PasswordHashingPolicy {
+    params: Params,
+    /// Verified against when the username is unknown, so that the response
+    /// time does not reveal whether an account exists.
+    /// It must be computed with the current parameters to take as long
+    /// as a real verification.
+    dummy_hash: Secret<String>,
+}
+
+impl PasswordHashingPolicy {
+    pub fn new(settings: &PasswordHashingSettings) -> Result<Self, anyhow::Error> {
+        let params = Params::new(
+            settings.memory_kib,
+            settings.iterations,
+            settings.parallelism,
+            None,
+        )
+        .map_err(|e| anyhow::anyhow!("Invalid Argon2 parameters: {}", e))?;
+        let dummy_password = Secret::new(uuid::Uuid::new_v4().to_string());
+        let dummy_hash = compute_password_hash(dummy_password, params.clone())?;
+
+        Ok(Self { params, dummy_hash })
+    }
+
+    /// Whether the hash was computed with weaker parameters than the current ones.
+    fn needs_rehash(&self, password_hash: &PasswordHash) -> bool {
+        if password_hash.algorithm != Algorithm::Argon2id.ident()
+            || password_hash.version != Some(Version::V0x13.into())
+        {
+            return true;
+        }
+        match Params::try_from(password_hash) {
+            Ok(params) => {
+                params.m_cost() < self.params.m_cost()
+                    || params.t_cost() < self.params.t_cost()
+                    || params.p_cost() < self.params.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+#[tracing::instrument(name = "Validate credentials", skip(credentials, policy, pool))]
 pub async fn validate_credentials(
     credentials: Credentials,
+    policy: &PasswordHashingPolicy,
     pool: &PgPool,
 ) -> Result<uuid::Uuid, AuthError> {
     let mut user_id = None;
-    let mut expected_password_hash = Secret::new(
-        "$argon2id$v=19$m=15000,t=2,p=1$\
-        gZiV/M1gPc22ElAH/Jh1Hw$\
-        CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
-            .to_string(),
-    );
+    let mut expected_password_hash = policy.dummy_hash.clone();
 
     if let Some((stored_user_id, stored_password_hash)) =
         get_stored_credentials(&credentials.username, pool).await?
@@ -41,6 +81,8 @@ pub async fn validate_credentials(
         expected_password_hash = stored_password_hash;
     }
 
+    let password = credentials.password.clone();
+    let stored_password_hash = expected_password_hash.clone();
     spawn_blocking_with_tracing(move || {
         verify_password_hash(expected_password_hash, credentials.password)
     })
@@ -53,9 +95,65 @@ pub async fn validate_credentials(
     // with the provided password,
     // we never authenticate a non-existing user.
     // You can easily add a unit test for that precise scenario.
-    user_id
+    let user_id = user_id
         .ok_or_else(|| anyhow::anyhow!("Unknown username."))
-        .map_err(AuthError::InvalidCredentials)
+        .map_err(AuthError::InvalidCredentials)?;
+
+    let parsed_hash = PasswordHash::new(stored_password_hash.expose_secret())
+        .context("Failed to parse the stored password hash in PHC string format.")?;
+    if policy.needs_rehash(&parsed_hash) {
+        // The user is waiting on the login: upgrade the hash in the background.
+        let policy = policy.clone();
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            if let Err(e) =
+                upgrade_password_hash(user_id, password, stored_password_hash, &policy, &pool).await
+            {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to upgrade the password hash of a user."
+                );
+            }
+        });
+    }
+
+    Ok(user_id)
+}
+
+#[tracing::instrument(
+    name = "Upgrade password hash",
+    skip(password, stored_password_hash, policy, pool)
+)]
+async fn upgrade_password_hash(
+    user_id: uuid::Uuid,
+    password: Secret<String>,
+    stored_password_hash: Secret<String>,
+    policy: &PasswordHashingPolicy,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let params = policy.params.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, params))
+            .await?
+            .context("Failed to hash password")?;
+
+    // Do not overwrite a password that has been changed in the meantime.
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $1
+        WHERE user_id = $2 AND password_hash = $3
+        "#,
+        password_hash.expose_secret(),
+        user_id,
+        stored_password_hash.expose_secret()
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the upgraded password hash in the database")?;
+
+    Ok(())
 }
 
 #[tracing::instrument(
@@ -99,15 +197,21 @@ async fn get_stored_credentials(
     Ok(row)
 }
 
-#[tracing::instrument(name = "Change password", skip(password, pool))]
-pub async fn change_password(
+#[tracing::instrument(name = "Change password", skip(password, policy, executor))]
+pub async fn change_password<'c, E>(
     user_id: uuid::Uuid,
     password: Secret<String>,
-    pool: &PgPool,
-) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("Failed to hash password")?;
+    policy: &PasswordHashingPolicy,
+    executor: E,
+) -> Result<(), anyhow::Error>
+where
+    E: PgExecutor<'c>,
+{
+    let params = policy.params.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, params))
+            .await?
+            .context("Failed to hash password")?;
 
     sqlx::query!(
         r#"
@@ -118,22 +222,82 @@ pub async fn change_password(
         password_hash.expose_secret(),
         user_id
     )
-    .execute(pool)
+    .execute(executor)
     .await
     .context("Failed to change the user's password in the database")?;
 
     Ok(())
 }
 
-fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
+fn compute_password_hash(
+    password: Secret<String>,
+    params: Params,
+) -> Result<Secret<String>, anyhow::Error> {
     let salt = SaltString::generate(&mut rand::thread_rng());
-    let password_hash = Argon2::new(
-        Algorithm::Argon2id,
-        Version::V0x13,
-        Params::new(15000, 2, 1, None).unwrap(),
-    )
-    .hash_password(password.expose_secret().as_bytes(), &salt)?
-    .to_string();
+    let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.expose_secret().as_bytes(), &salt)?
+        .to_string();
 
     Ok(Secret::new(password_hash))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PasswordHashingPolicy;
+    use crate::configuration::PasswordHashingSettings;
+    use argon2::PasswordHash;
+
+    fn policy() -> PasswordHashingPolicy {
+        PasswordHashingPolicy::new(&PasswordHashingSettings {
+            memory_kib: 15000,
+            iterations: 2,
+            parallelism: 1,
+        })
+        .unwrap()
+    }
+
+    fn needs_rehash(hash: &str) -> bool {
+        policy().needs_rehash(&PasswordHash::new(hash).unwrap())
+    }
+
+    #[test]
+    fn a_hash_with_the_current_parameters_is_kept() {
+        assert!(!needs_rehash(
+            "$argon2id$v=19$m=15000,t=2,p=1$\
+            gZiV/M1gPc22ElAH/Jh1Hw$\
+            CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
+        ));
+    }
+
+    #[test]
+    fn a_hash_with_stronger_parameters_is_kept() {
+        assert!(!needs_rehash(
+            "$argon2id$v=19$m=19456,t=3,p=1$\
+            gZiV/M1gPc22ElAH/Jh1Hw$\
+            CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
+        ));
+    }
+
+    #[test]
+    fn a_hash_with_weaker_parameters_is_upgraded() {
+        assert!(needs_rehash(
+            "$argon2id$v=19$m=4096,t=2,p=1$\
+            gZiV/M1gPc22ElAH/Jh1Hw$\
+            CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
+        ));
+        assert!(needs_rehash(
+            "$argon2id$v=19$m=15000,t=1,p=1$\
+            gZiV/M1gPc22ElAH/Jh1Hw$\
+            CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
+        ));
+    }
+
+    #[test]
+    fn a_hash_from_another_argon2_variant_is_upgraded() {
+        assert!(needs_rehash(
+            "$argon2i$v=19$m=15000,t=2,p=1$\
+            gZiV/M1gPc22ElAH/Jh1Hw$\
+            CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
+        ));
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sha1::{Digest, Sha1};
+
+use crate::configuration::PasswordPolicySettings;
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+const MAX_PASSWORD_LENGTH: usize = 129;
+
+#[derive(thiserror::Error, Debug)]
+pub enum PasswordPolicyViolation {
+    #[error("The new password is too short. It should have 8 or more characters.")]
+    TooShort,
+    #[error("The new password is too long. It should have 129 or fewer characters.")]
+    TooLong,
+    #[error("The new password must be different from the current one.")]
+    SameAsCurrent,
+    #[error("The new password must not contain your username.")]
+    ContainsUsername,
+    #[error(
+        "The new password has appeared in a data breach and must not be used. \
+        Please choose another one."
+    )]
+    Breached,
+    #[error(
+        "The new password is too easy to guess. Use a longer password and avoid \
+        repeated or sequential characters."
+    )]
+    TooWeak,
+}
+
+/// The rules a new password must follow.
+pub struct PasswordPolicy {
+    min_entropy_bits: f64,
+    /// Upper-case hex SHA-1 digests, the format used by "Have I Been Pwned".
+    breached_password_hashes: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    pub fn new(settings: &PasswordPolicySettings) -> Result<Self, anyhow::Error> {
+        let corpus =
+            std::fs::read_to_string(&settings.breached_passwords_path).with_context(|| {
+                format!(
+                    "Failed to read the breached passwords list at {}.",
+                    settings.breached_passwords_path
+                )
+            })?;
+
+        Ok(Self {
+            min_entropy_bits: settings.min_entropy_bits as f64,
+            breached_password_hashes: parse_breached_password_hashes(&corpus),
+        })
+    }
+
+    /// Check a new password, returning the first rule it breaks.
+    /// `current_password` is only known when the user is changing a password
+    /// they can prove they own.
+    pub fn check(
+        &self,
+        new_password: &Secret<String>,
+        username: &str,
+        current_password: Option<&Secret<String>>,
+    ) -> Result<(), PasswordPolicyViolation> {
+        let password = new_password.expose_secret();
+        let length = password.chars().count();
+
+        if length < MIN_PASSWORD_LENGTH {
+            return Err(PasswordPolicyViolation::TooShort);
+        }
+        if length > MAX_PASSWORD_LENGTH {
+            return Err(PasswordPolicyViolation::TooLong);
+        }
+        if current_password.is_some_and(|current| current.expose_secret() == password) {
+            return Err(PasswordPolicyViolation::SameAsCurrent);
+        }
+        if !username.is_empty() && password.to_lowercase().contains(&username.to_lowercase()) {
+            return Err(PasswordPolicyViolation::ContainsUsername);
+        }
+        if self.breached_password_hashes.contains(&sha1_hex(password)) {
+            return Err(PasswordPolicyViolation::Breached);
+        }
+        if estimate_entropy_bits(password) < self.min_entropy_bits {
+            return Err(PasswordPolicyViolation::TooWeak);
+        }
+
+        Ok(())
+    }
+}
+
+/// One digest per line, optionally followed by `:<count>` as in the
+/// "Have I Been Pwned" downloads. Blank lines and `#` comments are skipped.
+fn parse_breached_password_hashes(corpus: &str) -> HashSet<String> {
+    corpus
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            line.split(':')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_uppercase()
+        })
+        .collect()
+}
+
+fn sha1_hex(password: &str) -> String {
+    hex::encode_upper(Sha1::digest(password.as_bytes()))
+}
+
+/// A rough estimate of how many bits of guessing a password resists,
+/// in the spirit of zxcvbn: every character is worth the size of the
+/// alphabet it was drawn from, except characters that repeat or continue
+/// a sequence (`aaa`, `abc`, `321`), which are worth a single bit.
+fn estimate_entropy_bits(password: &str) -> f64 {
+    let chars: Vec<char> = password.chars().collect();
+    let bits_per_char = (alphabet_size(&chars) as f64).log2();
+
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            let predictable = i > 0 && (c as i64 - chars[i - 1] as i64).abs() <= 1;
+            if predictable {
+                1.0
+            } else {
+                bits_per_char
+            }
+        })
+        .sum()
+}
+
+fn alphabet_size(chars: &[char]) -> u32 {
+    let mut size = 0;
+    if chars.iter().any(|c| c.is_ascii_lowercase()) {
+        size += 26;
+    }
+    if chars.iter().any(|c| c.is_ascii_uppercase()) {
+        size += 26;
+    }
+    if chars.iter().any(|c| c.is_ascii_digit()) {
+        size += 10;
+    }
+    if chars.iter().any(|c| c.is_ascii_punctuation() || *c == ' ') {
+        size += 33;
+    }
+    if chars.iter().any(|c| !c.is_ascii()) {
+        size += 100;
+    }
+    size.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        estimate_entropy_bits, parse_breached_password_hashes, sha1_hex, PasswordPolicy,
+        PasswordPolicyViolation,
+    };
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_entropy_bits: 50.0,
+            breached_password_hashes: parse_breached_password_hashes(&format!(
+                "{}:42",
+                sha1_hex("Tr0ub4dour&3")
+            )),
+        }
+    }
+
+    fn check(password: &str) -> Result<(), PasswordPolicyViolation> {
+        policy().check(&Secret::new(password.into()), "ursula", None)
+    }
+
+    #[test]
+    fn sha1_digests_are_upper_case_hex() {
+        assert_eq!(
+            sha1_hex("password"),
+            "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8"
+        );
+    }
+
+    #[test]
+    fn breached_hashes_are_parsed_case_insensitively_without_counts() {
+        let hashes = parse_breached_password_hashes(
+            "# comment\n\nabc:12\n5baa61e4c9b93f3f0682250b6cf8331b7ee68fd8\n",
+        );
+        assert_eq!(hashes.len(), 2);
+        assert!(hashes.contains("ABC"));
+        assert!(hashes.contains("5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8"));
+    }
+
+    #[test]
+    fn repeated_and_sequential_characters_are_worth_little() {
+        assert!(estimate_entropy_bits("aaaaaaaaaaaa") < 20.0);
+        assert!(estimate_entropy_bits("abcdefghijkl") < 20.0);
+        assert!(estimate_entropy_bits("987654321098") < 20.0);
+        assert!(estimate_entropy_bits("k7#Qm2!xP9@w") > 70.0);
+    }
+
+    #[test]
+    fn a_strong_password_is_accepted() {
+        assert_ok!(check("correct horse battery staple"));
+        assert_ok!(check(&uuid::Uuid::new_v4().to_string()));
+    }
+
+    #[test]
+    fn the_length_is_counted_in_characters() {
+        assert!(matches!(
+            check("€€€€€"),
+            Err(PasswordPolicyViolation::TooShort)
+        ));
+        let password: String = "k7#Qm2!xP9@wéàüøß€".chars().cycle().take(120).collect();
+        assert_ok!(check(&password));
+    }
+
+    #[test]
+    fn a_breached_password_is_rejected() {
+        assert!(matches!(
+            check("Tr0ub4dour&3"),
+            Err(PasswordPolicyViolation::Breached)
+        ));
+    }
+
+    #[test]
+    fn a_predictable_password_is_rejected() {
+        assert!(matches!(
+            check("aaaaaaaaaaaa"),
+            Err(PasswordPolicyViolation::TooWeak)
+        ));
+    }
+
+    #[test]
+    fn the_username_cannot_be_part_of_the_password() {
+        assert!(matches!(
+            check("my name is URSULA!"),
+            Err(PasswordPolicyViolation::ContainsUsername)
+        ));
+    }
+
+    #[test]
+    fn the_current_password_cannot_be_reused() {
+        let password = Secret::new("correct horse battery staple".to_string());
+        assert_err!(policy().check(&password, "ursula", Some(&password)));
+    }
+}
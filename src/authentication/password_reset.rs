@@ -0,0 +1,114 @@
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// How long a password reset link stays valid after being emailed.
+pub const RESET_TOKEN_TTL_MINUTES: i64 = 30;
+
+/// Generate a random 32-characters-long case-sensitive password reset token
+pub fn generate_reset_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+/// Tokens are stored hashed, so a leaked database does not leak
+/// usable reset links.
+fn hash_reset_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// The id and the stored address of the user with this email, regardless
+/// of case: it is not always typed the way it was saved.
+#[tracing::instrument(name = "Get user from email", skip(pool))]
+pub async fn get_user_by_email(
+    email: &str,
+    pool: &PgPool,
+) -> Result<Option<(Uuid, String)>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id, email AS "email!"
+        FROM users
+        WHERE lower(email) = lower($1)
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|r| (r.user_id, r.email)))
+}
+
+#[tracing::instrument(name = "Store password reset token", skip(token, pool))]
+pub async fn store_reset_token(
+    user_id: Uuid,
+    token: &str,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let now = Utc::now();
+    sqlx::query!(
+        r#"
+        INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        hash_reset_token(token),
+        user_id,
+        now,
+        now + Duration::minutes(RESET_TOKEN_TTL_MINUTES)
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the password reset token.")?;
+
+    Ok(())
+}
+
+/// Return the user the token was issued for, if it is still valid.
+#[tracing::instrument(name = "Validate password reset token", skip(token, pool))]
+pub async fn validate_reset_token(token: &str, pool: &PgPool) -> Result<Option<Uuid>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id
+        FROM password_reset_tokens
+        WHERE
+            token_hash = $1 AND
+            used_at IS NULL AND
+            expires_at > now()
+        "#,
+        hash_reset_token(token)
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|r| r.user_id))
+}
+
+/// Mark the token as used and return the user it was issued for.
+/// Returns `None` if the token is unknown, expired or has already been used.
+#[tracing::instrument(name = "Consume password reset token", skip(token, transaction))]
+pub async fn consume_reset_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    token: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens
+        SET used_at = now()
+        WHERE
+            token_hash = $1 AND
+            used_at IS NULL AND
+            expires_at > now()
+        RETURNING user_id
+        "#,
+        hash_reset_token(token)
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+
+    Ok(row.map(|r| r.user_id))
+}
@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+use redis::aio::ConnectionManager;
+
+use crate::configuration::LoginThrottleSettings;
+
+/// Tracks login attempts per account, per account and client IP,
+/// and per client IP.
+///
+/// Every password check costs an Argon2 verification on the blocking pool,
+/// so this protects both the accounts and the server itself.
+/// An account is only locked for the clients that failed to log into it:
+/// anybody else is merely slowed down, so that nobody can lock the admin
+/// out by guessing their password from another address.
+#[derive(Clone)]
+pub struct LoginThrottle {
+    redis: ConnectionManager,
+    settings: LoginThrottleSettings,
+}
+
+pub enum ThrottleDecision {
+    /// The attempt can go ahead, after waiting for the given delay.
+    Allow(Duration),
+    /// One of the limits has been hit: the attempt must be rejected
+    /// without checking the credentials.
+    Locked,
+}
+
+impl LoginThrottle {
+    pub fn new(redis: ConnectionManager, settings: LoginThrottleSettings) -> Self {
+        Self { redis, settings }
+    }
+
+    fn username_key(username: &str) -> String {
+        format!("login_failures:username:{}", username.to_lowercase())
+    }
+
+    fn username_and_ip_key(username: &str, ip: &str) -> String {
+        format!(
+            "login_failures:username_and_ip:{}:{ip}",
+            username.to_lowercase()
+        )
+    }
+
+    fn ip_key(ip: &str) -> String {
+        format!("login_failures:ip:{ip}")
+    }
+
+    fn second_factor_key(username: &str, ip: &str) -> String {
+        format!(
+            "second_factor_failures:username_and_ip:{}:{ip}",
+            username.to_lowercase()
+        )
+    }
+
+    /// Count the attempt as a failure up front, and decide whether it can go
+    /// ahead from the failures that came before it.
+    /// Counting and checking happen in a single transaction, so concurrent
+    /// attempts cannot all slip under the limits: `clear` takes the attempt
+    /// back if it succeeds.
+    #[tracing::instrument(name = "Register login attempt", skip(self))]
+    pub async fn register_attempt(
+        &self,
+        username: &str,
+        ip: &str,
+    ) -> Result<ThrottleDecision, anyhow::Error> {
+        let lockout = self.settings.lockout_seconds as i64;
+        let (username_attempts, username_and_ip_attempts, ip_attempts): (u64, u64, u64) =
+            redis::pipe()
+                .atomic()
+                .incr(Self::username_key(username), 1)
+                .expire(Self::username_key(username), lockout)
+                .ignore()
+                .incr(Self::username_and_ip_key(username, ip), 1)
+                .expire(Self::username_and_ip_key(username, ip), lockout)
+                .ignore()
+                .incr(Self::ip_key(ip), 1)
+                .expire(Self::ip_key(ip), lockout)
+                .ignore()
+                .query_async(&mut self.redis.clone())
+                .await?;
+        // Leave out the attempt that is being made.
+        let username_failures = username_attempts - 1;
+        let username_and_ip_failures = username_and_ip_attempts - 1;
+        let ip_failures = ip_attempts - 1;
+
+        if username_and_ip_failures >= self.settings.max_failures_per_username
+            || ip_failures >= self.settings.max_failures_per_ip
+        {
+            return Ok(ThrottleDecision::Locked);
+        }
+
+        Ok(ThrottleDecision::Allow(backoff_delay(
+            &self.settings,
+            username_failures.max(ip_failures),
+        )))
+    }
+
+    /// Count a second factor as invalid up front, like `register_attempt`,
+    /// and return how many invalid ones came before it.
+    /// They are kept across logins: entering the password again must not
+    /// grant fresh guesses.
+    #[tracing::instrument(name = "Register second factor attempt", skip(self))]
+    pub async fn register_second_factor_attempt(
+        &self,
+        username: &str,
+        ip: &str,
+    ) -> Result<u64, anyhow::Error> {
+        let key = Self::second_factor_key(username, ip);
+        let (attempts,): (u64,) = redis::pipe()
+            .atomic()
+            .incr(&key, 1)
+            .expire(&key, self.settings.lockout_seconds as i64)
+            .ignore()
+            .query_async(&mut self.redis.clone())
+            .await?;
+        Ok(attempts - 1)
+    }
+
+    /// How many invalid second factors the account received from the IP.
+    #[tracing::instrument(name = "Get second factor failures", skip(self))]
+    pub async fn second_factor_failures(
+        &self,
+        username: &str,
+        ip: &str,
+    ) -> Result<u64, anyhow::Error> {
+        let failures: Option<u64> = redis::cmd("GET")
+            .arg(Self::second_factor_key(username, ip))
+            .query_async(&mut self.redis.clone())
+            .await?;
+        Ok(failures.unwrap_or(0))
+    }
+
+    /// Forget the failures of an account after a successful login,
+    /// and take back the attempt that was counted by `register_attempt`.
+    /// Previous failures from the IP are kept, so a single client cannot keep
+    /// guessing across accounts by logging into its own one.
+    /// With a second factor, the login only succeeds once it is verified.
+    #[tracing::instrument(name = "Clear login failures", skip(self))]
+    pub async fn clear(&self, username: &str, ip: &str) -> Result<(), anyhow::Error> {
+        redis::pipe()
+            .atomic()
+            .del(Self::username_key(username))
+            .del(Self::username_and_ip_key(username, ip))
+            .del(Self::second_factor_key(username, ip))
+            .decr(Self::ip_key(ip), 1)
+            .query_async::<_, ()>(&mut self.redis.clone())
+            .await?;
+        Ok(())
+    }
+}
+
+/// Exponential backoff: no delay for a first attempt,
+/// then the base delay doubling with every failure, up to the maximum.
+fn backoff_delay(settings: &LoginThrottleSettings, failures: u64) -> Duration {
+    if failures == 0 {
+        return Duration::ZERO;
+    }
+    let exponent = (failures - 1).min(16) as u32;
+    let delay = settings
+        .base_delay_milliseconds
+        .saturating_mul(2u64.pow(exponent))
+        .min(settings.max_delay_milliseconds);
+    Duration::from_millis(delay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::backoff_delay;
+    use crate::configuration::LoginThrottleSettings;
+    use std::time::Duration;
+
+    fn settings() -> LoginThrottleSettings {
+        LoginThrottleSettings {
+            max_failures_per_username: 5,
+            max_failures_per_ip: 50,
+            lockout_seconds: 900,
+            base_delay_milliseconds: 250,
+            max_delay_milliseconds: 4000,
+        }
+    }
+
+    #[test]
+    fn there_is_no_delay_without_previous_failures() {
+        assert_eq!(backoff_delay(&settings(), 0), Duration::ZERO);
+    }
+
+    #[test]
+    fn the_delay_doubles_with_every_failure() {
+        assert_eq!(backoff_delay(&settings(), 1), Duration::from_millis(250));
+        assert_eq!(backoff_delay(&settings(), 2), Duration::from_millis(500));
+        assert_eq!(backoff_delay(&settings(), 3), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn the_delay_is_capped() {
+        assert_eq!(backoff_delay(&settings(), 10), Duration::from_millis(4000));
+        assert_eq!(
+            backoff_delay(&settings(), u64::MAX),
+            Duration::from_millis(4000)
+        );
+    }
+}
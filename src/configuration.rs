@@ -5,14 +5,23 @@ use sqlx::{
     ConnectOptions,
 };
 
-use crate::{domain::SubscriberEmail, email_client::EmailClient};
+use crate::{
+    domain::SubscriberEmail, email_client::EmailClient, session_registry::SessionTimeouts,
+};
 
 #[derive(Clone, serde::Deserialize)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
+    pub email_delivery: EmailDeliverySettings,
     pub redis_uri: Secret<String>,
+    pub login_throttle: LoginThrottleSettings,
+    pub password_hashing: PasswordHashingSettings,
+    pub password_policy: PasswordPolicySettings,
+    pub webhooks: WebhookSettings,
+    /// Required by the application, but not by the workers.
+    pub email_events: Option<EmailEventsSettings>,
 }
 
 #[derive(Clone, serde::Deserialize)]
@@ -52,6 +61,126 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    /// Log out sessions that have not been used for this long.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub session_idle_timeout_seconds: u64,
+    /// Log out sessions this long after login, even if they are in use.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub session_absolute_timeout_seconds: u64,
+    /// Sites, besides our own, where subscribers may be sent once they
+    /// subscribed or confirmed, e.g. `https://www.example.com/blog`.
+    #[serde(default)]
+    pub redirect_allow_list: Vec<String>,
+    /// The reverse proxies or load balancers in front of us, as IP addresses
+    /// or CIDR ranges, e.g. `10.0.0.0/8`. `X-Forwarded-For` is only honoured
+    /// for requests coming from them.
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+}
+
+impl ApplicationSettings {
+    pub fn session_timeouts(&self) -> SessionTimeouts {
+        SessionTimeouts {
+            idle: chrono::Duration::seconds(self.session_idle_timeout_seconds as i64),
+            absolute: chrono::Duration::seconds(self.session_absolute_timeout_seconds as i64),
+        }
+    }
+}
+
+/// Limits applied to failed login attempts, tracked in Redis.
+#[derive(Clone, serde::Deserialize)]
+pub struct LoginThrottleSettings {
+    /// Failures after which a client is locked out of an account.
+    /// Other clients can still log into it.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_username: u64,
+    /// Failures after which a client is locked out of every account.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_ip: u64,
+    /// How long failures are remembered after the last one,
+    /// which is also how long a lockout lasts.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lockout_seconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub base_delay_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_delay_milliseconds: u64,
+}
+
+/// How outgoing webhooks are delivered.
+#[derive(Clone, serde::Deserialize)]
+pub struct WebhookSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_milliseconds: u64,
+    /// Events are dropped after this many failed deliveries.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts: u32,
+    /// Doubled after every failed delivery.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub base_retry_delay_seconds: u64,
+}
+
+impl WebhookSettings {
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.timeout_milliseconds)
+    }
+
+    /// How long to wait before the next attempt, after `n_attempts` failures.
+    pub fn retry_delay(&self, n_attempts: u32) -> chrono::Duration {
+        retry_delay(self.base_retry_delay_seconds, n_attempts)
+    }
+}
+
+/// How the delivery worker sends newsletter issues and welcome emails.
+#[derive(Clone, serde::Deserialize)]
+pub struct EmailDeliverySettings {
+    /// Emails are dropped after this many failed deliveries.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts: u32,
+    /// Doubled after every failed delivery.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub base_retry_delay_seconds: u64,
+}
+
+impl EmailDeliverySettings {
+    /// How long to wait before the next attempt, after `n_attempts` failures.
+    pub fn retry_delay(&self, n_attempts: u32) -> chrono::Duration {
+        retry_delay(self.base_retry_delay_seconds, n_attempts)
+    }
+}
+
+fn retry_delay(base_retry_delay_seconds: u64, n_attempts: u32) -> chrono::Duration {
+    let factor = 2u64.saturating_pow(n_attempts.saturating_sub(1));
+    chrono::Duration::seconds(base_retry_delay_seconds.saturating_mul(factor) as i64)
+}
+
+/// The HTTP Basic credentials the email provider sends along with bounce
+/// and spam-complaint events, as configured in its webhook URL.
+#[derive(Clone, serde::Deserialize)]
+pub struct EmailEventsSettings {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+/// Argon2id parameters for new password hashes.
+/// Stored hashes computed with weaker parameters are upgraded on login.
+#[derive(Clone, serde::Deserialize)]
+pub struct PasswordHashingSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub memory_kib: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub iterations: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub parallelism: u32,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct PasswordPolicySettings {
+    /// How many bits of guessing a new password must resist.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub min_entropy_bits: u32,
+    /// SHA-1 digests of breached passwords, loaded at startup.
+    pub breached_passwords_path: String,
 }
 
 #[derive(Clone, serde::Deserialize)]
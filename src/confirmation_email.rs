@@ -0,0 +1,59 @@
+//! The email asking new subscribers to confirm their subscription, written
+//! from the admin UI. Its templates can use `{{ confirmation_link }}` on top
+//! of the merge tags of issues.
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
+
+pub struct ConfirmationEmail {
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Get the confirmation email", skip(executor))]
+pub async fn get_confirmation_email(
+    executor: impl PgExecutor<'_>,
+) -> Result<ConfirmationEmail, anyhow::Error> {
+    let confirmation_email = sqlx::query_as!(
+        ConfirmationEmail,
+        r#"
+        SELECT subject, html_content, text_content, updated_at
+        FROM confirmation_email
+        "#
+    )
+    .fetch_one(executor)
+    .await
+    .context("Failed to fetch the confirmation email.")?;
+    Ok(confirmation_email)
+}
+
+#[tracing::instrument(
+    name = "Save the confirmation email",
+    skip(html_content, text_content, pool)
+)]
+pub async fn save_confirmation_email(
+    subject: &str,
+    html_content: &str,
+    text_content: &str,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE confirmation_email
+        SET
+            subject = $1,
+            html_content = $2,
+            text_content = $3,
+            updated_at = now()
+        "#,
+        subject,
+        html_content,
+        text_content
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the confirmation email.")?;
+    Ok(())
+}
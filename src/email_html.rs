@@ -0,0 +1,393 @@
+//! The HTML content of issues, made fit for email before it is stored.
+//!
+//! Many mail clients drop `<style>` blocks, so their rules are moved into the
+//! `style` attribute of the elements they match. The result then goes through
+//! an allow-list sanitizer: scripts, event handlers and unknown tags never
+//! reach the inboxes.
+//!
+//! The content is a template: merge tags and statements are set aside while
+//! the HTML is rewritten, and the rendered emails are sanitized again.
+use lol_html::{element, errors::RewritingError, rewrite_str, text, RewriteStrSettings};
+use uuid::Uuid;
+
+/// Gmail hides the end of emails with more HTML than this behind a
+/// "View entire message" link, unsubscribe link included.
+pub const GMAIL_CLIPPING_SIZE: usize = 102 * 1024;
+
+pub struct PreparedHtml {
+    pub html: String,
+    /// Meant to be shown to the author of the issue.
+    pub warnings: Vec<String>,
+}
+
+pub fn prepare_html(html: &str) -> PreparedHtml {
+    let (html, template_spans) = TemplateSpans::set_aside(html);
+    let mut warnings = Vec::new();
+    let html = match inline_css(&html) {
+        Ok((html, dropped)) => {
+            if !dropped.is_empty() {
+                warnings.push(format!(
+                    "These CSS rules cannot be inlined and were removed: {}.",
+                    dropped.join(", ")
+                ));
+            }
+            html
+        }
+        Err(e) => {
+            tracing::warn!(error.cause_chain = ?e, "Failed to inline the CSS of an issue.");
+            warnings.push("The CSS could not be inlined and was removed.".into());
+            html
+        }
+    };
+    PreparedHtml {
+        html: template_spans.put_back(&sanitize(&html)),
+        warnings,
+    }
+}
+
+/// The merge tags, statements and comments of a template, replaced by
+/// placeholders: the sanitizer would escape the `>` of `{% if a > b %}`,
+/// or the quotes of `{{ name | default("reader") }}` in an attribute.
+struct TemplateSpans {
+    marker: String,
+    spans: Vec<String>,
+}
+
+impl TemplateSpans {
+    fn set_aside(html: &str) -> (String, Self) {
+        let mut template_spans = Self {
+            marker: Uuid::new_v4().simple().to_string(),
+            spans: Vec::new(),
+        };
+        let mut output = String::with_capacity(html.len());
+        let mut rest = html;
+        while let Some(start) = ["{{", "{%", "{#"]
+            .iter()
+            .filter_map(|delimiter| rest.find(delimiter))
+            .min()
+        {
+            let end_delimiter = match &rest[start..start + 2] {
+                "{{" => "}}",
+                "{%" => "%}",
+                _ => "#}",
+            };
+            // Unterminated: the template is invalid anyway.
+            let Some(length) = rest[start + 2..].find(end_delimiter) else {
+                break;
+            };
+            let end = start + 2 + length + 2;
+            output.push_str(&rest[..start]);
+            output.push_str(&template_spans.placeholder(template_spans.spans.len()));
+            template_spans.spans.push(rest[start..end].to_owned());
+            rest = &rest[end..];
+        }
+        output.push_str(rest);
+        (output, template_spans)
+    }
+
+    /// Spans whose placeholder was removed, e.g. with a `<script>`, are lost.
+    fn put_back(&self, html: &str) -> String {
+        let mut html = html.to_owned();
+        for (i, span) in self.spans.iter().enumerate() {
+            html = html.replacen(&self.placeholder(i), span, 1);
+        }
+        html
+    }
+
+    /// Letters and digits only, so that it is left alone wherever it is.
+    fn placeholder(&self, i: usize) -> String {
+        format!("tpl{}n{i}e", self.marker)
+    }
+}
+
+/// The warning to show when an email with `html_size` bytes of HTML
+/// would be clipped.
+pub fn clipping_warning(html_size: usize) -> Option<String> {
+    (html_size > GMAIL_CLIPPING_SIZE).then(|| {
+        format!(
+            "The HTML version is {} KB: Gmail clips emails over {} KB, hiding their end.",
+            html_size.div_ceil(1024),
+            GMAIL_CLIPPING_SIZE / 1024
+        )
+    })
+}
+
+/// Full documents keep their `<html>` and `<body>` tags, which the
+/// sanitizer would drop along with the `<head>`.
+pub fn sanitize(html: &str) -> String {
+    match body(html) {
+        Some(body) => format!(
+            "<html><body>{}</body></html>",
+            sanitizer().clean(&html[body])
+        ),
+        None => sanitizer().clean(html).to_string(),
+    }
+}
+
+/// Sanitize HTML to be embedded in a page: only the content of the body
+/// of full documents is kept.
+pub fn sanitize_fragment(html: &str) -> String {
+    let fragment = body(html).map_or(html, |body| &html[body]);
+    sanitizer().clean(fragment).to_string()
+}
+
+fn sanitizer() -> ammonia::Builder<'static> {
+    let mut sanitizer = ammonia::Builder::default();
+    sanitizer
+        // Presentational attributes are still common in emails.
+        .add_generic_attributes([
+            "style",
+            "align",
+            "valign",
+            "width",
+            "height",
+            "bgcolor",
+            "border",
+            "cellpadding",
+            "cellspacing",
+        ])
+        .link_rel(None);
+    sanitizer
+}
+
+/// Where the content of the `<body>` of a full document is.
+fn body(html: &str) -> Option<std::ops::Range<usize>> {
+    // Lowercasing ASCII keeps the byte offsets.
+    let lowercase = html.to_ascii_lowercase();
+    let start = lowercase.find("<body")?;
+    let content_start = start + lowercase[start..].find('>')? + 1;
+    let content_end = lowercase.rfind("</body>")?;
+    (content_start <= content_end).then_some(content_start..content_end)
+}
+
+struct CssRule {
+    selector: String,
+    /// Ready to go in an attribute, without the trailing `;`.
+    declarations: String,
+    specificity: (usize, usize, usize),
+}
+
+/// Move the rules of the `<style>` blocks into `style` attributes.
+/// Returns the selectors of the rules that could not be inlined, e.g.
+/// `a:hover` or `@media` queries.
+fn inline_css(html: &str) -> Result<(String, Vec<String>), RewritingError> {
+    let mut css = String::new();
+    let html = rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![
+                text!("style", |chunk| {
+                    css.push_str(chunk.as_str());
+                    chunk.remove();
+                    Ok(())
+                }),
+                element!("style", |el| {
+                    el.remove();
+                    Ok(())
+                }),
+            ],
+            ..RewriteStrSettings::new()
+        },
+    )?;
+    let (mut rules, dropped) = parse_css(&css);
+    if rules.is_empty() {
+        return Ok((html, dropped));
+    }
+
+    // Rules are applied by prepending their declarations, the most specific
+    // first: the later a declaration comes in the attribute, the more it
+    // weighs, and the original inline style weighs most.
+    rules.sort_by_key(|rule| rule.specificity);
+    rules.reverse();
+    let handlers = rules
+        .iter()
+        .map(|rule| {
+            element!(rule.selector.as_str(), |el| {
+                let style = match el.get_attribute("style") {
+                    Some(style) if !style.trim().is_empty() => {
+                        format!("{}; {}", rule.declarations, style.trim())
+                    }
+                    _ => rule.declarations.clone(),
+                };
+                el.set_attribute("style", &style)?;
+                Ok(())
+            })
+        })
+        .collect();
+    let html = rewrite_str(
+        &html,
+        RewriteStrSettings {
+            element_content_handlers: handlers,
+            ..RewriteStrSettings::new()
+        },
+    )?;
+    Ok((html, dropped))
+}
+
+/// The rules that can be inlined, in order, and the selectors of the others.
+fn parse_css(css: &str) -> (Vec<CssRule>, Vec<String>) {
+    let css = strip_comments(css);
+    let mut rules = Vec::new();
+    let mut dropped = Vec::new();
+    let mut rest = css.as_str();
+    while let Some(open) = rest.find('{') {
+        let prelude = rest[..open].trim();
+        let Some(close) = matching_brace(&rest[open..]) else {
+            break;
+        };
+        let block = &rest[open + 1..open + close];
+        rest = &rest[open + close + 1..];
+        if prelude.starts_with('@') {
+            dropped.push(prelude.to_owned());
+            continue;
+        }
+        let declarations = block.trim().trim_end_matches(';').trim();
+        if declarations.is_empty() {
+            continue;
+        }
+        for selector in prelude.split(',').map(str::trim) {
+            // Only selectors that do not depend on the state of the page,
+            // e.g. `:hover`, are supported.
+            if selector.parse::<lol_html::Selector>().is_ok() {
+                rules.push(CssRule {
+                    selector: selector.to_owned(),
+                    declarations: declarations.replace('&', "&amp;"),
+                    specificity: specificity(selector),
+                });
+            } else {
+                dropped.push(selector.to_owned());
+            }
+        }
+    }
+    (rules, dropped)
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut stripped = String::new();
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// The position of the `}` closing the block `block` starts with.
+fn matching_brace(block: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in block.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// An approximation, good enough for the selectors of an email: ids,
+/// then classes, attributes and pseudo-classes, then element names.
+fn specificity(selector: &str) -> (usize, usize, usize) {
+    let ids = selector.matches('#').count();
+    let classes = selector.matches(['.', '[', ':']).count();
+    let elements = selector
+        .split(|c: char| c.is_whitespace() || matches!(c, '>' | '+' | '~'))
+        .filter(|part| part.starts_with(|c: char| c.is_ascii_alphabetic()))
+        .count();
+    (ids, classes, elements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clipping_warning, prepare_html, sanitize_fragment, GMAIL_CLIPPING_SIZE};
+
+    #[test]
+    fn scripts_and_event_handlers_are_removed() {
+        let prepared = prepare_html(
+            r#"<p onclick="steal()">Hi<script>steal()</script></p><a href="javascript:steal()">x</a>"#,
+        );
+        assert_eq!(prepared.html, "<p>Hi</p><a>x</a>");
+        assert!(prepared.warnings.is_empty());
+    }
+
+    #[test]
+    fn documents_keep_their_body() {
+        let prepared = prepare_html(
+            r#"<!DOCTYPE html><html><head><title>Issue</title></head><body bgcolor="white"><p>Hi</p></body></html>"#,
+        );
+        assert_eq!(prepared.html, "<html><body><p>Hi</p></body></html>");
+    }
+
+    #[test]
+    fn fragments_lose_the_document_around_them() {
+        let html = sanitize_fragment(
+            r#"<html><head><title>Issue</title></head><body><p>Hi</p><img src="x" onerror="steal()"></body></html>"#,
+        );
+        assert_eq!(html, r#"<p>Hi</p><img src="x">"#);
+    }
+
+    #[test]
+    fn merge_tags_are_kept() {
+        let prepared = prepare_html(r#"<p>Hi {{ name }}</p><a href="{{ unsubscribe_url }}">x</a>"#);
+        assert_eq!(
+            prepared.html,
+            r#"<p>Hi {{ name }}</p><a href="{{ unsubscribe_url }}">x</a>"#
+        );
+    }
+
+    #[test]
+    fn template_statements_and_arguments_are_kept_as_written() {
+        let html = r#"{% if name|length > 1 %}<p title='{{ name | replace("a", "b") }}'>Hi</p>{% endif %}{# "x" > y #}"#;
+        assert_eq!(
+            prepare_html(html).html,
+            r#"{% if name|length > 1 %}<p title="{{ name | replace("a", "b") }}">Hi</p>{% endif %}{# "x" > y #}"#
+        );
+    }
+
+    #[test]
+    fn css_rules_are_inlined_in_cascade_order() {
+        let prepared = prepare_html(
+            r#"<style>
+                /* Specificity wins over the order of the rules */
+                .lead { color: red; font-family: "Serif" }
+                p { color: black; margin: 0; }
+            </style>
+            <p class="lead" style="margin: 1em">Hi</p><p>There</p>"#,
+        );
+        assert_eq!(
+            prepared.html.trim(),
+            r#"<p style="color: black; margin: 0; color: red; font-family: &quot;Serif&quot;; margin: 1em">Hi</p><p style="color: black; margin: 0">There</p>"#
+        );
+        assert!(prepared.warnings.is_empty());
+    }
+
+    #[test]
+    fn rules_that_cannot_be_inlined_are_reported() {
+        let prepared = prepare_html(
+            "<style>a:hover { color: red } @media (max-width: 600px) { p { margin: 0 } }</style><p>Hi</p>",
+        );
+        assert_eq!(prepared.html, "<p>Hi</p>");
+        assert_eq!(
+            prepared.warnings,
+            ["These CSS rules cannot be inlined and were removed: a:hover, @media (max-width: 600px)."]
+        );
+    }
+
+    #[test]
+    fn large_emails_are_reported() {
+        assert_eq!(clipping_warning(GMAIL_CLIPPING_SIZE), None);
+        assert_eq!(
+            clipping_warning(GMAIL_CLIPPING_SIZE + 1).unwrap(),
+            "The HTML version is 103 KB: Gmail clips emails over 102 KB, hiding their end."
+        );
+    }
+}
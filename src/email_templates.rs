@@ -0,0 +1,150 @@
+//! Layouts managed from the admin UI, wrapping newsletter issues and the
+//! emails sent by the application.
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::merge_tags::Layout;
+
+pub struct EmailTemplate {
+    pub template_id: Uuid,
+    pub name: String,
+    pub html_layout: String,
+    pub text_layout: String,
+    pub is_default: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl EmailTemplate {
+    pub fn layout(&self) -> Layout {
+        Layout {
+            html: self.html_layout.clone(),
+            text: self.text_layout.clone(),
+        }
+    }
+}
+
+#[tracing::instrument(name = "List email templates", skip(pool))]
+pub async fn list_email_templates(pool: &PgPool) -> Result<Vec<EmailTemplate>, anyhow::Error> {
+    let templates = sqlx::query_as!(
+        EmailTemplate,
+        r#"
+        SELECT template_id, name, html_layout, text_layout, is_default, updated_at
+        FROM email_templates
+        ORDER BY name, created_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the email templates.")?;
+    Ok(templates)
+}
+
+#[tracing::instrument(name = "Get email template", skip(pool))]
+pub async fn get_email_template(
+    template_id: Uuid,
+    pool: &PgPool,
+) -> Result<Option<EmailTemplate>, anyhow::Error> {
+    let template = sqlx::query_as!(
+        EmailTemplate,
+        r#"
+        SELECT template_id, name, html_layout, text_layout, is_default, updated_at
+        FROM email_templates
+        WHERE template_id = $1
+        "#,
+        template_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the email template.")?;
+    Ok(template)
+}
+
+/// The layout of the emails sent by the application, if one was chosen.
+#[tracing::instrument(name = "Get the default layout", skip(executor))]
+pub async fn get_default_layout(
+    executor: impl PgExecutor<'_>,
+) -> Result<Option<Layout>, anyhow::Error> {
+    let layout = sqlx::query_as!(
+        Layout,
+        r#"
+        SELECT html_layout AS html, text_layout AS text
+        FROM email_templates
+        WHERE is_default
+        "#
+    )
+    .fetch_optional(executor)
+    .await
+    .context("Failed to fetch the default email template.")?;
+    Ok(layout)
+}
+
+/// Create a template, or update it if `template_id` is already taken.
+/// Making it the default unsets the previous default.
+#[tracing::instrument(name = "Save email template", skip(layout, pool))]
+pub async fn save_email_template(
+    template_id: Uuid,
+    name: &str,
+    layout: &Layout,
+    is_default: bool,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    if is_default {
+        sqlx::query!(
+            r#"UPDATE email_templates SET is_default = false WHERE is_default AND template_id <> $1"#,
+            template_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to unset the previous default email template.")?;
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO email_templates (
+            template_id, name, html_layout, text_layout, is_default, created_at, updated_at
+        )
+        VALUES ($1, $2, $3, $4, $5, now(), now())
+        ON CONFLICT (template_id) DO UPDATE SET
+            name = EXCLUDED.name,
+            html_layout = EXCLUDED.html_layout,
+            text_layout = EXCLUDED.text_layout,
+            is_default = EXCLUDED.is_default,
+            updated_at = EXCLUDED.updated_at
+        "#,
+        template_id,
+        name,
+        layout.html,
+        layout.text,
+        is_default
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to store the email template.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store an email template.")?;
+    Ok(())
+}
+
+/// Issues keep their own copy of the layout: they are not affected.
+#[tracing::instrument(name = "Delete email template", skip(pool))]
+pub async fn delete_email_template(
+    template_id: Uuid,
+    pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let n_deleted = sqlx::query!(
+        r#"DELETE FROM email_templates WHERE template_id = $1"#,
+        template_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete the email template.")?
+    .rows_affected();
+    Ok(n_deleted > 0)
+}
@@ -101,6 +101,7 @@ impl PgHasArrayType for HeaderPairRecord {
     }
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum NextAction {
     // Return transaction for later usage
     StartProcessing(Transaction<'static, Postgres>),
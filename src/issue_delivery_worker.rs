@@ -1,3 +1,5 @@
+use anyhow::Context;
+use chrono::Utc;
 use std::time::Duration;
 
 use sqlx::Executor;
@@ -5,23 +7,44 @@ use sqlx::{PgPool, Postgres, Transaction};
 use tracing::{field::display, Span};
 use uuid::Uuid;
 
-use crate::configuration::Settings;
+use crate::configuration::{EmailDeliverySettings, Settings};
 use crate::domain::SubscriberEmail;
 use crate::email_client::EmailClient;
+use crate::email_templates::get_default_layout;
+use crate::merge_tags::{render_content, Layout, MergeTags};
 use crate::startup::get_connection_pool;
+use crate::suppression_list::is_suppressed;
+use crate::tracking::{Recipient, TrackingLinks};
+use crate::webhooks::{enqueue_webhook_event, WebhookEvent};
+use crate::welcome_email::get_welcome_email;
 
 // ! There is no expiry mechanism for our idempotency keys
 
 pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database);
     let email_client = configuration.email_client.client();
+    let tracking_links = TrackingLinks::new(
+        configuration.application.base_url,
+        configuration.application.hmac_secret,
+    );
 
-    worker_loop(connection_pool, email_client).await
+    worker_loop(
+        connection_pool,
+        email_client,
+        tracking_links,
+        configuration.email_delivery,
+    )
+    .await
 }
 
-async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    tracking_links: TrackingLinks,
+    settings: EmailDeliverySettings,
+) -> Result<(), anyhow::Error> {
     loop {
-        match try_execute_task(&pool, &email_client).await {
+        match try_execute_task(&pool, &email_client, &tracking_links, &settings).await {
             Ok(ExecutionOutcome::TaskCompleted) => {}
             Ok(ExecutionOutcome::EmptyQueue) => tokio::time::sleep(Duration::from_secs(10)).await,
             Err(_) => tokio::time::sleep(Duration::from_secs(1)).await,
@@ -38,37 +61,49 @@ pub enum ExecutionOutcome {
 pub async fn try_execute_task(
     pool: &PgPool,
     email_client: &EmailClient,
+    tracking_links: &TrackingLinks,
+    settings: &EmailDeliverySettings,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
-    let task = dequeue_task(pool).await?;
-    if task.is_none() {
+    let Some((transaction, task)) = dequeue_task(pool).await? else {
         return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    let email = task.subscriber_email.clone();
+    if let Some(issue_id) = task.newsletter_issue_id {
+        Span::current().record("newsletter_issue_id", display(issue_id));
     }
+    Span::current().record("subscriber_email", display(&email));
 
-    let (transaction, issue_id, email) = task.unwrap();
-    Span::current()
-        .record("newsletter_issue_id", &display(issue_id))
-        .record("subscriber_email", &display(&email));
-
-    match SubscriberEmail::parse(email.clone()) {
-        Ok(email) => {
-            let issue = get_issue(pool, issue_id).await?;
-            if let Err(e) = email_client
-                .send_email(
-                    &email,
-                    &issue.title,
-                    &issue.html_content,
-                    &issue.text_content,
-                )
-                .await
-            {
-                tracing::error!(
-                error.cause_chain = ?e,
-                error.message = %e,
-                "Failed to deliver issue to a confirmed subscriber. \
-                Skipping.",
+    let outcome = match SubscriberEmail::parse(email) {
+        Ok(email) if is_suppressed(pool, &email).await? => {
+            tracing::info!("Skipping a confirmed subscriber. Their address is suppressed.");
+            Ok(())
+        }
+        Ok(email) => match get_subscriber(pool, email.as_ref()).await? {
+            Some(subscriber) => match task.newsletter_issue_id {
+                Some(issue_id) => {
+                    let issue = get_issue(pool, issue_id).await?;
+                    send_issue(
+                        email_client,
+                        tracking_links,
+                        &issue,
+                        issue_id,
+                        &email,
+                        subscriber,
+                    )
+                    .await
+                }
+                None => {
+                    send_welcome_email(pool, email_client, tracking_links, &email, subscriber).await
+                }
+            },
+            None => {
+                tracing::info!(
+                    "Skipping a recipient who unsubscribed or was suppressed \
+                    since the email was queued."
                 );
+                Ok(())
             }
-        }
+        },
         Err(e) => {
             tracing::error!(
             error.cause_chain = ?e,
@@ -76,69 +111,262 @@ pub async fn try_execute_task(
             "Skipping a confirmed subscriber. \
             Their stored contact details are invalid",
             );
+            Ok(())
         }
-    }
+    };
 
-    delete_task(transaction, issue_id, &email).await?;
+    if let Err(e) = outcome {
+        let n_attempts = task.n_attempts as u32 + 1;
+        if n_attempts < settings.max_attempts {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to deliver an email to a confirmed subscriber. It will be retried."
+            );
+            postpone_task(transaction, &task, settings.retry_delay(n_attempts)).await?;
+            return Ok(ExecutionOutcome::TaskCompleted);
+        }
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to deliver an email to a confirmed subscriber {} times. Giving up.",
+            n_attempts
+        );
+    }
+    delete_task(transaction, task.task_id).await?;
+    if let Some(issue_id) = task.newsletter_issue_id {
+        mark_issue_as_delivered_if_complete(pool, issue_id).await?;
+    }
 
     Ok(ExecutionOutcome::TaskCompleted)
 }
 
+/// Every recipient gets their own copy of the issue: the merge tags are
+/// filled in, and the HTML reports when it is opened and which links are
+/// clicked.
+async fn send_issue(
+    email_client: &EmailClient,
+    tracking_links: &TrackingLinks,
+    issue: &NewsletterIssue,
+    issue_id: Uuid,
+    email: &SubscriberEmail,
+    subscriber: Subscriber,
+) -> Result<(), anyhow::Error> {
+    let subscriber_id = subscriber.id;
+    let merge_tags = merge_tags(tracking_links, email, subscriber);
+    let content = render_content(
+        &issue.html_content,
+        &issue.text_content,
+        issue.layout().as_ref(),
+        &merge_tags,
+    )
+    .context("Failed to fill in the merge tags of the issue.")?;
+    let html_content = tracking_links.personalise_html(
+        &content.html,
+        Recipient {
+            newsletter_issue_id: issue_id,
+            subscriber_id,
+        },
+    );
+    email_client
+        .send_email(email, &issue.title, &html_content, &content.text)
+        .await?;
+    Ok(())
+}
+
+/// The welcome email is read when it is sent, and wrapped in the default
+/// layout like the other emails of the application. Its links are not
+/// tracked: it belongs to no issue.
+async fn send_welcome_email(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    tracking_links: &TrackingLinks,
+    email: &SubscriberEmail,
+    subscriber: Subscriber,
+) -> Result<(), anyhow::Error> {
+    let welcome_email = match get_welcome_email(pool).await? {
+        Some(welcome_email) if welcome_email.enabled => welcome_email,
+        _ => {
+            tracing::info!("Skipping a welcome email. It was disabled since it was queued.");
+            return Ok(());
+        }
+    };
+    let layout = get_default_layout(pool).await?;
+    let content = render_content(
+        &welcome_email.html_content,
+        &welcome_email.text_content,
+        layout.as_ref(),
+        &merge_tags(tracking_links, email, subscriber),
+    )
+    .context("Failed to fill in the merge tags of the welcome email.")?;
+    email_client
+        .send_email(email, &welcome_email.subject, &content.html, &content.text)
+        .await?;
+    Ok(())
+}
+
+fn merge_tags(
+    tracking_links: &TrackingLinks,
+    email: &SubscriberEmail,
+    subscriber: Subscriber,
+) -> MergeTags {
+    MergeTags {
+        name: subscriber.name,
+        email: email.as_ref().to_owned(),
+        unsubscribe_url: tracking_links.unsubscribe_url(subscriber.id),
+        preferences_url: tracking_links.preferences_url(subscriber.id),
+    }
+}
+
 type PgTransaction = Transaction<'static, Postgres>;
 
+struct Task {
+    task_id: Uuid,
+    /// `None` for welcome emails.
+    newsletter_issue_id: Option<Uuid>,
+    subscriber_email: String,
+    n_attempts: i16,
+}
+
 #[tracing::instrument(skip_all)]
-async fn dequeue_task(
-    pool: &PgPool,
-) -> Result<Option<(PgTransaction, Uuid, String)>, anyhow::Error> {
+async fn dequeue_task(pool: &PgPool) -> Result<Option<(PgTransaction, Task)>, anyhow::Error> {
     let mut transaction = pool.begin().await?;
-    let query = sqlx::query!(
+    let query = sqlx::query_as!(
+        Task,
         r#"
-        SELECT newsletter_issue_id, subscriber_email
+        SELECT task_id, newsletter_issue_id, subscriber_email, n_attempts
         FROM issue_delivery_queue
+        WHERE execute_after <= now()
+        ORDER BY execute_after
         FOR UPDATE
         SKIP LOCKED
         LIMIT 1
         "#
     );
 
-    let r = query.fetch_optional(&mut *transaction).await?;
-    if let Some(r) = r {
-        Ok(Some((
-            transaction,
-            r.newsletter_issue_id,
-            r.subscriber_email,
-        )))
-    } else {
-        Ok(None)
-    }
+    let task = query.fetch_optional(&mut *transaction).await?;
+    Ok(task.map(|task| (transaction, task)))
 }
 
 #[tracing::instrument(skip_all)]
-async fn delete_task(
+async fn delete_task(mut transaction: PgTransaction, task_id: Uuid) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE task_id = $1
+        "#,
+        task_id
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn postpone_task(
     mut transaction: PgTransaction,
-    issue_id: Uuid,
-    email: &str,
+    task: &Task,
+    delay: chrono::Duration,
 ) -> Result<(), anyhow::Error> {
     let query = sqlx::query!(
         r#"
-        DELETE FROM issue_delivery_queue
+        UPDATE issue_delivery_queue
+        SET
+            n_attempts = n_attempts + 1,
+            execute_after = $2
+        WHERE task_id = $1
+        "#,
+        task.task_id,
+        Utc::now() + delay
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to postpone an email delivery.")?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Record the end of the delivery once the queue holds no more tasks for
+/// the issue. It runs after the task was deleted, in its own transaction:
+/// when workers complete the last tasks concurrently, each of them sees the
+/// deletions of the others, and `delivered_at` makes sure only one of them
+/// emits the event.
+#[tracing::instrument(skip_all)]
+async fn mark_issue_as_delivered_if_complete(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let delivered = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET delivered_at = now()
         WHERE
             newsletter_issue_id = $1 AND
-            subscriber_email = $2
+            delivered_at IS NULL AND
+            NOT EXISTS (
+                SELECT 1 FROM issue_delivery_queue
+                WHERE newsletter_issue_id = $1
+            )
+        RETURNING title, n_recipients
         "#,
-        issue_id,
-        email
-    );
-    transaction.execute(query).await?;
+        issue_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    if let Some(delivered) = delivered {
+        enqueue_webhook_event(
+            &mut transaction,
+            &WebhookEvent::NewsletterIssueDelivered {
+                newsletter_issue_id: issue_id,
+                title: delivered.title,
+                recipients: delivered.n_recipients,
+            },
+        )
+        .await?;
+    }
     transaction.commit().await?;
 
     Ok(())
 }
 
+struct Subscriber {
+    id: Uuid,
+    name: String,
+}
+
+/// `None` unless the subscriber is still confirmed: bounces and complaints
+/// suppress the subscriber, not only the address.
+#[tracing::instrument(skip_all)]
+async fn get_subscriber(pool: &PgPool, email: &str) -> Result<Option<Subscriber>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"SELECT id, name FROM subscriptions WHERE email = $1 AND status = 'confirmed'"#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(subscriber)
+}
+
 struct NewsletterIssue {
     title: String,
     text_content: String,
     html_content: String,
+    html_layout: Option<String>,
+    text_layout: Option<String>,
+}
+
+impl NewsletterIssue {
+    fn layout(&self) -> Option<Layout> {
+        Some(Layout {
+            html: self.html_layout.clone()?,
+            text: self.text_layout.clone()?,
+        })
+    }
 }
 
 #[tracing::instrument(skip_all)]
@@ -146,7 +374,7 @@ async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, any
     let issue = sqlx::query_as!(
         NewsletterIssue,
         r#"
-        SELECT title, text_content, html_content
+        SELECT title, text_content, html_content, html_layout, text_layout
         FROM newsletter_issues
         WHERE 
             newsletter_issue_id = $1
@@ -1,11 +1,25 @@
 pub mod authentication;
 pub mod configuration;
+pub mod confirmation_email;
 pub mod domain;
 pub mod email_client;
+pub mod email_html;
+pub mod email_templates;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod markdown;
+pub mod merge_tags;
+pub mod openapi;
+pub mod redirect_allow_list;
 pub mod routes;
+pub mod session_registry;
 pub mod session_state;
 pub mod startup;
+pub mod suppression_list;
 pub mod telemetry;
+pub mod tracking;
+pub mod trusted_proxies;
 pub mod utils;
+pub mod webhook_delivery_worker;
+pub mod webhooks;
+pub mod welcome_email;
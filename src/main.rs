@@ -3,9 +3,10 @@ use std::fmt::{Debug, Display};
 use tokio::task::JoinError;
 use zero2prod::{
     configuration::get_configuration,
-    issue_delivery_worker::run_worker_until_stopped,
+    issue_delivery_worker,
     startup::Application,
     telemetry::{get_subscriber, init_subscriber},
+    webhook_delivery_worker,
 };
 
 #[tokio::main]
@@ -18,11 +19,17 @@ async fn main() -> anyhow::Result<()> {
     let application = Application::build(configuration.clone()).await?;
 
     let application_task = tokio::spawn(application.run_until_stopped());
-    let worker_task = tokio::spawn(run_worker_until_stopped(configuration));
+    let worker_task = tokio::spawn(issue_delivery_worker::run_worker_until_stopped(
+        configuration.clone(),
+    ));
+    let webhook_worker_task = tokio::spawn(webhook_delivery_worker::run_worker_until_stopped(
+        configuration,
+    ));
 
     tokio::select! {
         o = application_task => report_exit("API", o),
         o = worker_task => report_exit("Background worker", o),
+        o = webhook_worker_task => report_exit("Webhook worker", o),
     };
 
     Ok(())
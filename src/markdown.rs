@@ -0,0 +1,369 @@
+//! Newsletter issues written in Markdown, rendered once when they are
+//! published into the HTML and plain text contents the rest of the
+//! application works with.
+//!
+//! The HTML is safe to send as is: raw HTML in the source is shown as typed
+//! and links are restricted to web and email addresses. Merge tags such as
+//! `{{ name }}` are left untouched, to be filled in for every recipient.
+//! The only ones allowed as link destinations are the subscription links.
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+/// Plain text lines are wrapped at this width, as most email clients expect.
+const TEXT_WIDTH: usize = 72;
+
+pub struct MarkdownContent {
+    pub html: String,
+    pub text: String,
+}
+
+pub fn render_markdown(source: &str) -> MarkdownContent {
+    MarkdownContent {
+        html: to_html(source),
+        text: to_text(source),
+    }
+}
+
+fn parser(source: &str) -> Parser<'_> {
+    Parser::new_ext(source, Options::ENABLE_STRIKETHROUGH)
+}
+
+fn to_html(source: &str) -> String {
+    // Links are written by hand: the HTML writer would percent-encode the
+    // braces of merge tags.
+    let events = parser(source).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Link {
+            dest_url, title, ..
+        }) => {
+            let mut tag = String::from("<a");
+            if is_allowed_url(&dest_url) {
+                tag.push_str(&format!(
+                    r#" href="{}""#,
+                    htmlescape::encode_minimal(&dest_url)
+                ));
+            }
+            if !title.is_empty() {
+                tag.push_str(&format!(
+                    r#" title="{}""#,
+                    htmlescape::encode_minimal(&title)
+                ));
+            }
+            tag.push('>');
+            Event::InlineHtml(tag.into())
+        }
+        Event::End(TagEnd::Link) => Event::InlineHtml("</a>".into()),
+        event => event,
+    });
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events);
+    html
+}
+
+fn is_allowed_url(url: &str) -> bool {
+    let url = url.trim();
+    // Any other merge tag could render to a `javascript:` URL, e.g. the
+    // name of a subscriber.
+    if let Some(merge_tag) = url
+        .strip_prefix("{{")
+        .and_then(|url| url.strip_suffix("}}"))
+    {
+        return matches!(merge_tag.trim(), "unsubscribe_url" | "preferences_url");
+    }
+    let url = url.to_ascii_lowercase();
+    ["http://", "https://", "mailto:"]
+        .iter()
+        .any(|prefix| url.starts_with(prefix))
+}
+
+fn to_text(source: &str) -> String {
+    let mut writer = TextWriter::default();
+    for event in parser(source) {
+        writer.handle(event);
+    }
+    writer.flush();
+    writer.output.trim_end().to_owned()
+}
+
+#[derive(Default)]
+struct TextWriter {
+    output: String,
+    /// The inline content of the current block, not wrapped yet.
+    inline: String,
+    /// What starts the lines of the current block: quotes and the
+    /// indentation of list items.
+    prefixes: Vec<String>,
+    /// The bullet or number of a list item, written on its first line.
+    marker: Option<String>,
+    /// The next number of every open list, `None` for bullet lists.
+    lists: Vec<Option<u64>>,
+    /// Where the text of the open links and images starts in `inline`,
+    /// and where they point to.
+    links: Vec<(usize, String)>,
+    code_block: Option<String>,
+    /// Blocks are separated by a blank line, items of a tight list are not.
+    needs_blank_line: bool,
+}
+
+impl TextWriter {
+    fn handle(&mut self, event: Event<'_>) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => match &mut self.code_block {
+                Some(code) => code.push_str(&text),
+                None => self.inline.push_str(&text),
+            },
+            Event::Code(text) | Event::Html(text) | Event::InlineHtml(text) => {
+                self.inline.push_str(&text)
+            }
+            Event::SoftBreak => self.inline.push(' '),
+            Event::HardBreak => self.inline.push('\n'),
+            Event::Rule => {
+                self.flush();
+                self.write_lines(["---"]);
+                self.needs_blank_line = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag<'_>) {
+        match tag {
+            Tag::Paragraph | Tag::Heading { .. } => self.flush(),
+            Tag::BlockQuote(_) => {
+                self.flush();
+                self.prefixes.push("> ".into());
+            }
+            Tag::CodeBlock(_) => {
+                self.flush();
+                self.code_block = Some(String::new());
+            }
+            Tag::List(first_number) => {
+                self.flush();
+                self.lists.push(first_number);
+            }
+            Tag::Item => {
+                self.flush();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "- ".into(),
+                };
+                self.prefixes.push(" ".repeat(marker.len()));
+                self.marker = Some(marker);
+            }
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                self.links.push((self.inline.len(), dest_url.into_string()));
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => {
+                self.flush();
+                self.needs_blank_line = true;
+            }
+            TagEnd::Heading(level) => {
+                let heading = std::mem::take(&mut self.inline);
+                let heading = heading.trim();
+                let underline = match level {
+                    HeadingLevel::H1 => '=',
+                    HeadingLevel::H2 => '-',
+                    _ => {
+                        self.write_lines([format!("### {heading}")]);
+                        self.needs_blank_line = true;
+                        return;
+                    }
+                };
+                let width = heading.chars().count().min(TEXT_WIDTH);
+                self.write_lines([heading.to_owned(), underline.to_string().repeat(width)]);
+                self.needs_blank_line = true;
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush();
+                self.prefixes.pop();
+                self.needs_blank_line = true;
+            }
+            TagEnd::CodeBlock => {
+                let code = self.code_block.take().unwrap_or_default();
+                self.write_lines(
+                    code.trim_end_matches('\n')
+                        .lines()
+                        .map(|l| format!("    {l}")),
+                );
+                self.needs_blank_line = true;
+            }
+            TagEnd::List(_) => {
+                self.flush();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.needs_blank_line = true;
+                }
+            }
+            TagEnd::Item => {
+                self.flush();
+                self.prefixes.pop();
+                self.marker = None;
+            }
+            TagEnd::Link | TagEnd::Image => {
+                if let Some((start, url)) = self.links.pop() {
+                    let text = &self.inline[start..];
+                    if text != url.trim_start_matches("mailto:") {
+                        self.inline.push_str(&format!(" ({url})"));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Write the pending inline content, wrapped.
+    fn flush(&mut self) {
+        let inline = std::mem::take(&mut self.inline);
+        let inline = inline.trim();
+        if inline.is_empty() {
+            return;
+        }
+        let (first_prefix, prefix) = self.line_prefixes();
+        let options = textwrap::Options::new(TEXT_WIDTH)
+            .initial_indent(&first_prefix)
+            .subsequent_indent(&prefix)
+            // Long URLs must stay clickable.
+            .break_words(false);
+        let lines: Vec<String> = textwrap::wrap(inline, options)
+            .into_iter()
+            .map(|line| line.into_owned())
+            .collect();
+        self.write_block(lines);
+    }
+
+    /// Write lines that are not wrapped, after the prefixes.
+    fn write_lines(&mut self, lines: impl IntoIterator<Item = impl AsRef<str>>) {
+        let (first_prefix, prefix) = self.line_prefixes();
+        let lines: Vec<String> = lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let prefix = if i == 0 { &first_prefix } else { &prefix };
+                format!("{prefix}{}", line.as_ref())
+            })
+            .collect();
+        self.write_block(lines);
+    }
+
+    fn write_block(&mut self, lines: Vec<String>) {
+        if self.needs_blank_line && !self.output.is_empty() {
+            self.output.push('\n');
+        }
+        self.needs_blank_line = false;
+        for line in lines {
+            self.output.push_str(line.trim_end());
+            self.output.push('\n');
+        }
+    }
+
+    /// The prefix of the first line of the next block, and of the others.
+    /// The marker of a list item only goes on its first line.
+    fn line_prefixes(&mut self) -> (String, String) {
+        let prefix = self.prefixes.concat();
+        let first_prefix = match self.marker.take() {
+            Some(marker) => {
+                let n = self.prefixes.len() - 1;
+                self.prefixes[..n].concat() + &marker
+            }
+            None => prefix.clone(),
+        };
+        (first_prefix, prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_markdown;
+
+    #[test]
+    fn raw_html_is_shown_as_typed() {
+        let content = render_markdown("Hi <script>alert(1)</script> there");
+        assert_eq!(
+            content.html,
+            "<p>Hi &lt;script&gt;alert(1)&lt;/script&gt; there</p>\n"
+        );
+        assert_eq!(content.text, "Hi <script>alert(1)</script> there");
+    }
+
+    #[test]
+    fn only_web_and_email_links_are_kept() {
+        let content = render_markdown(
+            "[a](https://example.com/?a=1&b=2) [b](javascript:alert(1)) [c](mailto:a@example.com)",
+        );
+        assert_eq!(
+            content.html,
+            "<p><a href=\"https://example.com/?a=1&amp;b=2\">a</a> <a>b</a> \
+            <a href=\"mailto:a@example.com\">c</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn merge_tags_are_left_untouched() {
+        let content = render_markdown("Hi {{ name }}, [unsubscribe]({{unsubscribe_url}})");
+        assert_eq!(
+            content.html,
+            "<p>Hi {{ name }}, <a href=\"{{unsubscribe_url}}\">unsubscribe</a></p>\n"
+        );
+        assert_eq!(
+            content.text,
+            "Hi {{ name }}, unsubscribe ({{unsubscribe_url}})"
+        );
+    }
+
+    #[test]
+    fn only_the_subscription_links_can_be_link_destinations() {
+        let content = render_markdown(
+            "[a](<{{ preferences_url }}>) [b]({{name}}) [c]({{unsubscribe_url}}x) \
+            [d]({{unsubscribe_url}}{{name}})",
+        );
+        assert_eq!(
+            content.html,
+            "<p><a href=\"{{ preferences_url }}\">a</a> <a>b</a> <a>c</a> <a>d</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn plain_text_is_wrapped_and_structured() {
+        let source = "\
+# Weekly news
+
+This paragraph is long enough to be wrapped, as it goes well over the \
+seventy-two columns of a plain text email.
+
+- First, with a [link](https://example.com)
+- Second
+  1. Nested
+
+> Quoted
+
+    let code = true;";
+        let content = render_markdown(source);
+        assert_eq!(
+            content.text,
+            "\
+Weekly news
+===========
+
+This paragraph is long enough to be wrapped, as it goes well over the
+seventy-two columns of a plain text email.
+
+- First, with a link (https://example.com)
+- Second
+  1. Nested
+
+> Quoted
+
+    let code = true;"
+        );
+    }
+}
@@ -0,0 +1,386 @@
+//! Placeholders in the content of newsletter issues, such as `{{ name }}`,
+//! filled in for every recipient by the delivery worker.
+//!
+//! The content is a Jinja template. Values are escaped in the HTML content
+//! and inserted as-is in the plain text one. Unknown placeholders are errors
+//! rather than blanks, so a typo is caught when the issue is published
+//! instead of reaching the inboxes.
+//!
+//! Layouts are templates too, with the rendered content in `{{ content }}`.
+//! The confirmation email is one as well, with `{{ confirmation_link }}`.
+use minijinja::{context, AutoEscape, Environment, UndefinedBehavior, Value};
+use std::fmt::Write;
+
+use crate::email_html::sanitize;
+
+const HTML_TEMPLATE: &str = "issue.html";
+const TEXT_TEMPLATE: &str = "issue.txt";
+const HTML_LAYOUT: &str = "layout.html";
+const TEXT_LAYOUT: &str = "layout.txt";
+
+/// The values available to the templates of an issue.
+pub struct MergeTags {
+    pub name: String,
+    pub email: String,
+    pub unsubscribe_url: String,
+    pub preferences_url: String,
+}
+
+impl MergeTags {
+    fn context(&self) -> Value {
+        // Our URLs only contain characters that are safe in HTML, and escaping
+        // them would turn every `/` into `&#x2f;`.
+        context! {
+            name => self.name,
+            email => self.email,
+            unsubscribe_url => Value::from_safe_string(self.unsubscribe_url.clone()),
+            preferences_url => Value::from_safe_string(self.preferences_url.clone()),
+        }
+    }
+
+    /// The values for readers of the public archive, who are not
+    /// subscribers: the subscription links lead to the home page.
+    pub fn public(base_url: &str) -> Self {
+        Self {
+            name: "reader".into(),
+            email: String::new(),
+            unsubscribe_url: format!("{base_url}/"),
+            preferences_url: format!("{base_url}/"),
+        }
+    }
+
+    fn example() -> Self {
+        Self {
+            name: "Ursula Le Guin".into(),
+            email: "ursula@example.com".into(),
+            unsubscribe_url: "https://example.com/subscriptions/unsubscribe?token=token".into(),
+            preferences_url: "https://example.com/subscriptions/preferences?token=token".into(),
+        }
+    }
+}
+
+/// What goes around the content of an email: header, footer,
+/// unsubscribe block...
+#[derive(Clone)]
+pub struct Layout {
+    pub html: String,
+    pub text: String,
+}
+
+pub struct RenderedContent {
+    pub html: String,
+    pub text: String,
+}
+
+/// Render both versions of the content of an email for one recipient,
+/// wrapped in `layout` if there is one.
+pub fn render_content(
+    html_template: &str,
+    text_template: &str,
+    layout: Option<&Layout>,
+    merge_tags: &MergeTags,
+) -> Result<RenderedContent, minijinja::Error> {
+    render_in_context(
+        html_template,
+        text_template,
+        layout,
+        merge_tags,
+        merge_tags.context(),
+    )
+}
+
+/// Render the confirmation email of a new subscriber, wrapped in `layout`
+/// if there is one.
+pub fn render_confirmation_email(
+    html_template: &str,
+    text_template: &str,
+    layout: Option<&Layout>,
+    merge_tags: &MergeTags,
+    confirmation_link: &str,
+) -> Result<RenderedContent, minijinja::Error> {
+    render_in_context(
+        html_template,
+        text_template,
+        layout,
+        merge_tags,
+        confirmation_context(merge_tags, confirmation_link),
+    )
+}
+
+fn confirmation_context(merge_tags: &MergeTags, confirmation_link: &str) -> Value {
+    context! {
+        confirmation_link => Value::from_safe_string(confirmation_link.to_owned()),
+        ..merge_tags.context()
+    }
+}
+
+fn render_in_context(
+    html_template: &str,
+    text_template: &str,
+    layout: Option<&Layout>,
+    merge_tags: &MergeTags,
+    context: Value,
+) -> Result<RenderedContent, minijinja::Error> {
+    let environment = environment();
+    let content = RenderedContent {
+        // Templates can output markup of their own, e.g. with `| safe`.
+        html: sanitize(&environment.render_named_str(HTML_TEMPLATE, html_template, &context)?),
+        text: environment.render_named_str(TEXT_TEMPLATE, text_template, &context)?,
+    };
+    match layout {
+        Some(layout) => wrap_in_layout(layout, content, merge_tags),
+        None => Ok(content),
+    }
+}
+
+/// Put content that is already rendered in a layout.
+pub fn wrap_in_layout(
+    layout: &Layout,
+    content: RenderedContent,
+    merge_tags: &MergeTags,
+) -> Result<RenderedContent, minijinja::Error> {
+    let environment = environment();
+    let context = merge_tags.context();
+    Ok(RenderedContent {
+        html: environment.render_named_str(
+            HTML_LAYOUT,
+            &layout.html,
+            context! { content => Value::from_safe_string(content.html), ..context.clone() },
+        )?,
+        text: environment.render_named_str(
+            TEXT_LAYOUT,
+            &layout.text,
+            context! { content => content.text, ..context },
+        )?,
+    })
+}
+
+/// Check that the content of an issue can be rendered for any recipient.
+/// The error is meant to be shown to the author of the issue.
+pub fn validate_content(html_template: &str, text_template: &str) -> Result<(), String> {
+    let environment = environment();
+    let example = MergeTags::example().context();
+    for (name, template, label) in [
+        (HTML_TEMPLATE, html_template, "HTML content"),
+        (TEXT_TEMPLATE, text_template, "plain text content"),
+    ] {
+        if let Err(e) = environment.render_named_str(name, template, &example) {
+            return Err(describe_error(label, &e));
+        }
+    }
+    Ok(())
+}
+
+/// Check that the confirmation email can be rendered for any new
+/// subscriber, and that both versions include the confirmation link.
+pub fn validate_confirmation_email(html_template: &str, text_template: &str) -> Result<(), String> {
+    const EXAMPLE_LINK: &str = "https://example.com/subscriptions/confirm?subscription_token=token";
+    let environment = environment();
+    let example = confirmation_context(&MergeTags::example(), EXAMPLE_LINK);
+    for (name, template, label) in [
+        (HTML_TEMPLATE, html_template, "HTML content"),
+        (TEXT_TEMPLATE, text_template, "plain text content"),
+    ] {
+        match environment.render_named_str(name, template, &example) {
+            Ok(rendered) if rendered.contains(EXAMPLE_LINK) => {}
+            Ok(_) => {
+                return Err(format!(
+                    "The {label} does not include {{{{ confirmation_link }}}}."
+                ))
+            }
+            Err(e) => return Err(describe_error(label, &e)),
+        }
+    }
+    Ok(())
+}
+
+/// Render the content of an issue as recipients will see it, with example
+/// values. The error is meant to be shown to the author of the issue.
+pub fn preview_content(
+    html_template: &str,
+    text_template: &str,
+    layout: Option<&Layout>,
+) -> Result<RenderedContent, String> {
+    validate_content(html_template, text_template)?;
+    render_content(html_template, text_template, layout, &MergeTags::example())
+        .map_err(|e| describe_error("layout", &e))
+}
+
+/// Check that a layout can be rendered for any recipient, and that it
+/// does include the content.
+pub fn validate_layout(layout: &Layout) -> Result<(), String> {
+    const EXAMPLE_CONTENT: &str = "The content of the email.";
+    let environment = environment();
+    let example = context! { content => EXAMPLE_CONTENT, ..MergeTags::example().context() };
+    for (name, template, label) in [
+        (HTML_LAYOUT, &layout.html, "HTML layout"),
+        (TEXT_LAYOUT, &layout.text, "plain text layout"),
+    ] {
+        match environment.render_named_str(name, template, &example) {
+            Ok(rendered) if rendered.contains(EXAMPLE_CONTENT) => {}
+            Ok(_) => return Err(format!("The {label} does not include {{{{ content }}}}.")),
+            Err(e) => return Err(describe_error(label, &e)),
+        }
+    }
+    Ok(())
+}
+
+fn describe_error(label: &str, e: &minijinja::Error) -> String {
+    let mut message = format!("The {label} is not a valid template: {}", e.kind());
+    if let Some(detail) = e.detail() {
+        write!(message, ", {detail}").unwrap();
+    }
+    if let Some(line) = e.line() {
+        write!(message, " (line {line})").unwrap();
+    }
+    message.push('.');
+    message
+}
+
+fn environment() -> Environment<'static> {
+    let mut environment = Environment::new();
+    environment.set_undefined_behavior(UndefinedBehavior::Strict);
+    environment.set_auto_escape_callback(|name| match name {
+        HTML_TEMPLATE | HTML_LAYOUT => AutoEscape::Html,
+        _ => AutoEscape::None,
+    });
+    environment.set_keep_trailing_newline(true);
+    environment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        render_content, validate_confirmation_email, validate_content, validate_layout, Layout,
+        MergeTags,
+    };
+
+    fn merge_tags() -> MergeTags {
+        MergeTags {
+            name: "Le Guin & <co>".into(),
+            ..MergeTags::example()
+        }
+    }
+
+    #[test]
+    fn values_are_escaped_in_html_only() {
+        let content = render_content(
+            "<p>Hi {{ name }}</p>",
+            "Hi {{ name }}\n",
+            None,
+            &merge_tags(),
+        )
+        .unwrap();
+        assert_eq!(content.html, "<p>Hi Le Guin &amp; &lt;co&gt;</p>");
+        assert_eq!(content.text, "Hi Le Guin & <co>\n");
+    }
+
+    #[test]
+    fn urls_are_not_mangled() {
+        let content = render_content(
+            r#"<a href="{{ unsubscribe_url }}">Unsubscribe</a>"#,
+            "",
+            None,
+            &merge_tags(),
+        )
+        .unwrap();
+        assert_eq!(
+            content.html,
+            r#"<a href="https://example.com/subscriptions/unsubscribe?token=token">Unsubscribe</a>"#
+        );
+    }
+
+    #[test]
+    fn rendered_html_is_sanitized() {
+        let content = render_content(
+            r#"{{ "<script>steal()</script>" | safe }}<a href="{{ name }}">x</a>"#,
+            "",
+            None,
+            &MergeTags {
+                name: "javascript:steal()".into(),
+                ..MergeTags::example()
+            },
+        )
+        .unwrap();
+        assert_eq!(content.html, "<a>x</a>");
+    }
+
+    #[test]
+    fn content_without_placeholders_is_left_alone() {
+        let content = render_content("<p>a &amp; b</p>", "a & b", None, &merge_tags()).unwrap();
+        assert_eq!(content.html, "<p>a &amp; b</p>");
+        assert_eq!(content.text, "a & b");
+    }
+
+    #[test]
+    fn unknown_placeholders_and_broken_syntax_are_rejected() {
+        assert!(validate_content("{{ name }} {{ unsubscribe_url }}", "{{ email }}").is_ok());
+        let error = validate_content("<p>\n{{ nmae }}</p>", "").unwrap_err();
+        assert_eq!(
+            error,
+            "The HTML content is not a valid template: undefined value (line 2)."
+        );
+        let error = validate_content("", "Hi {{ name ").unwrap_err();
+        assert!(error.starts_with("The plain text content is not a valid template"));
+    }
+
+    #[test]
+    fn layouts_wrap_the_rendered_content() {
+        let layout = Layout {
+            html: "<body>{{ content }}<p>{{ name }}</p></body>".into(),
+            text: "{{ content }}\n--\n{{ name }}".into(),
+        };
+        let content = render_content(
+            "<p>Hi {{ name }}</p>",
+            "Hi {{ name }}",
+            Some(&layout),
+            &merge_tags(),
+        )
+        .unwrap();
+        assert_eq!(
+            content.html,
+            "<body><p>Hi Le Guin &amp; &lt;co&gt;</p><p>Le Guin &amp; &lt;co&gt;</p></body>"
+        );
+        assert_eq!(content.text, "Hi Le Guin & <co>\n--\nLe Guin & <co>");
+    }
+
+    #[test]
+    fn the_confirmation_email_must_include_the_confirmation_link() {
+        assert!(validate_confirmation_email(
+            r#"<a href="{{ confirmation_link }}">Confirm</a>"#,
+            "Visit {{ confirmation_link }}, {{ name }}."
+        )
+        .is_ok());
+        let error = validate_confirmation_email("{{ confirmation_link }}", "Welcome!").unwrap_err();
+        assert_eq!(
+            error,
+            "The plain text content does not include {{ confirmation_link }}."
+        );
+        // Issues have no confirmation link.
+        assert!(validate_content("{{ confirmation_link }}", "").is_err());
+    }
+
+    #[test]
+    fn layouts_must_include_the_content() {
+        let layout = Layout {
+            html: "<body>{{ content }}</body>".into(),
+            text: "{{ content }}".into(),
+        };
+        assert!(validate_layout(&layout).is_ok());
+        let error = validate_layout(&Layout {
+            text: "No content".into(),
+            ..layout.clone()
+        })
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "The plain text layout does not include {{ content }}."
+        );
+        let error = validate_layout(&Layout {
+            html: "{{ content }}{{ unsubscribe }}".into(),
+            ..layout
+        })
+        .unwrap_err();
+        assert!(error.starts_with("The HTML layout is not a valid template"));
+    }
+}
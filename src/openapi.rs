@@ -0,0 +1,1676 @@
+//! The routes of the application and their OpenAPI description, served at
+//! `/openapi.json`.
+//!
+//! `startup::run` registers its routes from the operations listed here:
+//! an endpoint cannot be served without being documented.
+use actix_web::http::{Method, StatusCode};
+use actix_web::{web, FromRequest, Handler, Responder, ResponseError, Route};
+use serde_json::{json, Map, Value};
+
+use crate::routes::{self, ApiError, ConfirmationError, EmailEventError, SubscribeError};
+
+/// An error type whose responses are part of the documented contract.
+pub trait DocumentedError: ResponseError + Sized {
+    /// One instance of every variant, with what it means for the client.
+    /// Status codes are taken from `ResponseError`, so they cannot drift.
+    fn examples() -> Vec<(Self, &'static str)>;
+}
+
+/// A set of routes sharing a path prefix and the middleware guarding them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RouteGroup {
+    Public,
+    Login,
+    PasswordReset,
+    /// The pages reached from the links at the bottom of an issue.
+    SubscriptionManagement,
+    Admin,
+    Api,
+}
+
+impl RouteGroup {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::Public => "",
+            Self::Login => "/login",
+            Self::PasswordReset => "/password-reset",
+            Self::SubscriptionManagement => "/subscriptions",
+            Self::Admin => "/admin",
+            Self::Api => "/api/v1",
+        }
+    }
+}
+
+/// Registers the routes of `group`, relative to its prefix.
+/// Meant for `App::configure` or `Scope::configure`.
+pub fn register_routes(group: RouteGroup) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |config| {
+        // Routes sharing a path must belong to the same resource: the first
+        // resource matching the path would answer 405 to the other methods.
+        let mut resources: Vec<(&str, Vec<Operation>)> = Vec::new();
+        for operation in operations().into_iter().filter(|o| o.group == group) {
+            let path = operation.path.strip_prefix(group.prefix()).unwrap();
+            match resources.iter_mut().find(|(p, _)| *p == path) {
+                Some((_, operations)) => operations.push(operation),
+                None => resources.push((path, vec![operation])),
+            }
+        }
+        for (path, operations) in resources {
+            let mut resource = web::resource(path);
+            for operation in operations {
+                if let Some(form_config) = operation.form_config {
+                    resource = resource.app_data(form_config);
+                }
+                resource = resource.route((operation.route)());
+            }
+            config.service(resource);
+        }
+    }
+}
+
+pub fn openapi_document() -> Value {
+    let mut paths = Map::new();
+    for operation in operations() {
+        let path_item = paths
+            .entry(operation.path)
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .unwrap();
+        path_item.insert(operation.method.into(), Value::Object(operation.spec));
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "zero2prod",
+            "description": "A newsletter delivery service.",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "session": {
+                    "type": "apiKey",
+                    "in": "cookie",
+                    "name": "id",
+                    "description": "The session cookie set by `POST /login`.",
+                },
+                "emailProvider": {
+                    "type": "http",
+                    "scheme": "basic",
+                    "description": "The credentials configured under `email_events`.",
+                },
+                "apiToken": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "A personal API token, created from `/admin/api-tokens`.",
+                },
+            },
+            "schemas": schemas(),
+        },
+    })
+}
+
+fn operations() -> Vec<Operation> {
+    let in_group = |group: RouteGroup, operations: Vec<Operation>| {
+        operations
+            .into_iter()
+            .map(move |operation| operation.group(group))
+    };
+    public_operations()
+        .into_iter()
+        .chain(in_group(
+            RouteGroup::SubscriptionManagement,
+            subscription_management_operations(),
+        ))
+        .chain(in_group(RouteGroup::Login, login_operations()))
+        .chain(in_group(
+            RouteGroup::PasswordReset,
+            password_reset_operations(),
+        ))
+        .chain(admin_operations())
+        .chain(api_operations())
+        .collect()
+}
+
+/// Pages and endpoints anybody can reach.
+fn public_operations() -> Vec<Operation> {
+    vec![
+        Operation::new("get", "/", "Home page", routes::home)
+            .tag("pages")
+            .html_page(),
+        Operation::new(
+            "get",
+            "/health_check",
+            "Check that the service is up",
+            routes::health_check,
+        )
+        .tag("health")
+        .response(StatusCode::OK, "The service is up."),
+        Operation::new(
+            "get",
+            "/openapi.json",
+            "This document",
+            routes::openapi_json,
+        )
+        .tag("health")
+        .json_response(
+            StatusCode::OK,
+            "The OpenAPI document.",
+            json!({ "type": "object" }),
+        ),
+        Operation::new(
+            "post",
+            "/subscriptions",
+            "Subscribe to the newsletter",
+            routes::subscribe,
+        )
+        .tag("subscriptions")
+        .form_config(web::FormConfig::default().error_handler(routes::subscribe_form_error_handler))
+        .form(
+            &[
+                ("email", string(), "The email address to send issues to."),
+                ("name", string(), "How the subscriber wants to be greeted."),
+                (
+                    "redirect_to",
+                    string(),
+                    "A page of our site to show instead of ours, once subscribed.",
+                ),
+            ],
+            false,
+        )
+        .optional_fields(&["redirect_to"])
+        .html_page()
+        .redirect("To `redirect_to`, once the confirmation email is sent.")
+        .errors::<SubscribeError>(),
+        Operation::new(
+            "get",
+            "/subscriptions/confirm",
+            "Confirm a subscription",
+            routes::confirm,
+        )
+        .tag("subscriptions")
+        .query(
+            "subscription_token",
+            true,
+            "The token sent in the confirmation email.",
+        )
+        .query(
+            "redirect_to",
+            false,
+            "A page of our site to show instead of ours, once confirmed.",
+        )
+        .html_page()
+        .redirect(
+            "To `redirect_to`, once the subscription is confirmed. \
+                The welcome email, if enabled, is queued.",
+        )
+        .errors::<ConfirmationError>(),
+        Operation::new(
+            "post",
+            "/webhooks/email-events",
+            "Receive bounces and spam complaints from the email provider",
+            routes::receive_email_event,
+        )
+        .tag("subscriptions")
+        .security("emailProvider", &[])
+        .json_body(schema_ref("EmailEvent"))
+        .response(
+            StatusCode::OK,
+            "The event has been processed, or ignored if it is about an unknown address.",
+        )
+        .errors::<EmailEventError>(),
+        Operation::new(
+            "get",
+            "/archive",
+            "Public archive of the newsletter issues",
+            routes::archive,
+        )
+        .tag("pages")
+        .query(
+            "page",
+            false,
+            "Starts at 1, with the 20 most recent issues in the archive.",
+        )
+        .html_page()
+        .response(StatusCode::NOT_FOUND, "There is no such page."),
+        Operation::new(
+            "get",
+            "/archive/{slug}",
+            "Newsletter issue of the public archive",
+            routes::archived_issue,
+        )
+        .tag("pages")
+        .path_parameter("slug", "Generated from the title of the issue.")
+        .html_page()
+        .response(
+            StatusCode::NOT_FOUND,
+            "There is no issue with this slug in the archive.",
+        ),
+        Operation::new(
+            "get",
+            "/feed.rss",
+            "RSS feed of the public archive",
+            routes::rss_feed,
+        )
+        .tag("pages")
+        .feed("application/rss+xml"),
+        Operation::new(
+            "get",
+            "/feed.atom",
+            "Atom feed of the public archive",
+            routes::atom_feed,
+        )
+        .tag("pages")
+        .feed("application/atom+xml"),
+        Operation::new(
+            "get",
+            "/o/{token}",
+            "Record the opening of a newsletter issue",
+            routes::track_open,
+        )
+        .tag("tracking")
+        .path_parameter(
+            "token",
+            "Signed by the server, it identifies the issue and the recipient.",
+        )
+        .insert_response(
+            StatusCode::OK,
+            json!({
+                "description": "A transparent 1x1 GIF, never cached.",
+                "content": { "image/gif": { "schema": { "type": "string", "format": "binary" } } },
+            }),
+        )
+        .response(StatusCode::NOT_FOUND, "The token is not genuine."),
+        Operation::new(
+            "get",
+            "/r/{token}",
+            "Follow a link of a newsletter issue",
+            routes::track_click,
+        )
+        .tag("tracking")
+        .path_parameter(
+            "token",
+            "Signed by the server, it identifies the issue, the recipient and the link.",
+        )
+        .insert_response(
+            StatusCode::FOUND,
+            json!({
+                "description": "To the target of the link. The click is recorded.",
+                "headers": { "Location": { "schema": string() } },
+            }),
+        )
+        .response(StatusCode::NOT_FOUND, "The token is not genuine."),
+    ]
+}
+
+/// The pages linked from the bottom of every issue.
+fn subscription_management_operations() -> Vec<Operation> {
+    vec![
+        Operation::new(
+            "get",
+            "/subscriptions/unsubscribe",
+            "Unsubscribe form",
+            routes::unsubscribe_form,
+        )
+        .tag("subscriptions")
+        .query(
+            "token",
+            true,
+            "The token of the link at the bottom of an issue.",
+        )
+        .html_page()
+        .response(StatusCode::NOT_FOUND, "The token is not genuine."),
+        Operation::new(
+            "post",
+            "/subscriptions/unsubscribe",
+            "Unsubscribe",
+            routes::unsubscribe,
+        )
+        .tag("subscriptions")
+        .form(
+            &[(
+                "token",
+                string(),
+                "The token of the link at the bottom of an issue.",
+            )],
+            true,
+        )
+        .redirect("Back to the form, which confirms the subscriber is gone.")
+        .response(StatusCode::NOT_FOUND, "The token is not genuine.")
+        .csrf_failure(),
+        Operation::new(
+            "get",
+            "/subscriptions/preferences",
+            "Subscription preferences form",
+            routes::preferences_form,
+        )
+        .tag("subscriptions")
+        .query(
+            "token",
+            true,
+            "The token of the link at the bottom of an issue.",
+        )
+        .html_page()
+        .response(StatusCode::NOT_FOUND, "The token is not genuine."),
+        Operation::new(
+            "post",
+            "/subscriptions/preferences",
+            "Update subscription preferences",
+            routes::update_preferences,
+        )
+        .tag("subscriptions")
+        .form(
+            &[
+                (
+                    "token",
+                    string(),
+                    "The token of the link at the bottom of an issue.",
+                ),
+                ("name", string(), "The name of the subscriber."),
+            ],
+            true,
+        )
+        .redirect("Back to the form.")
+        .response(StatusCode::NOT_FOUND, "The token is not genuine.")
+        .csrf_failure(),
+    ]
+}
+
+fn login_operations() -> Vec<Operation> {
+    vec![
+        Operation::new("get", "/login", "Login form", routes::login_form)
+            .tag("authentication")
+            .html_page(),
+        Operation::new(
+            "post",
+            "/login",
+            "Log in with a username and a password",
+            routes::login,
+        )
+        .tag("authentication")
+        .form(
+            &[
+                ("username", string(), "The username."),
+                ("password", password(), "The password."),
+            ],
+            true,
+        )
+        .redirect(
+            "To `/admin/dashboard` on success, to `/login/two-factor` if a second \
+                factor is required, back to `/login` otherwise.",
+        )
+        .csrf_failure(),
+        Operation::new(
+            "get",
+            "/login/two-factor",
+            "Second factor form",
+            routes::second_factor_form,
+        )
+        .tag("authentication")
+        .html_page(),
+        Operation::new(
+            "post",
+            "/login/two-factor",
+            "Complete a login with a second factor",
+            routes::verify_second_factor,
+        )
+        .tag("authentication")
+        .form(
+            &[(
+                "code",
+                string(),
+                "A code from the authenticator app, or a recovery code.",
+            )],
+            true,
+        )
+        .redirect("To `/admin/dashboard` on success, back to `/login/two-factor` otherwise.")
+        .csrf_failure(),
+    ]
+}
+
+fn password_reset_operations() -> Vec<Operation> {
+    vec![
+        Operation::new(
+            "get",
+            "/password-reset",
+            "Password reset request form",
+            routes::password_reset_request_form,
+        )
+        .tag("authentication")
+        .html_page(),
+        Operation::new(
+            "post",
+            "/password-reset",
+            "Request a password reset link",
+            routes::request_password_reset,
+        )
+        .tag("authentication")
+        .form(
+            &[(
+                "email",
+                string(),
+                "The email address of the account. The response does not \
+                    tell whether an account uses it.",
+            )],
+            true,
+        )
+        .redirect("Back to `/password-reset`.")
+        .csrf_failure(),
+        Operation::new(
+            "get",
+            "/password-reset/confirm",
+            "New password form",
+            routes::password_reset_form,
+        )
+        .tag("authentication")
+        .query("token", true, "The token sent in the password reset email.")
+        .html_page(),
+        Operation::new(
+            "post",
+            "/password-reset/confirm",
+            "Choose a new password",
+            routes::reset_password,
+        )
+        .tag("authentication")
+        .form(
+            &[
+                (
+                    "token",
+                    string(),
+                    "The token sent in the password reset email.",
+                ),
+                ("new_password", password(), "The new password."),
+                ("new_password_check", password(), "The new password, again."),
+            ],
+            true,
+        )
+        .redirect("To `/login` on success, back to the form otherwise.")
+        .csrf_failure(),
+    ]
+}
+
+fn admin_operations() -> Vec<Operation> {
+    vec![
+        Operation::admin(
+            "get",
+            "/admin/dashboard",
+            "Admin dashboard",
+            routes::admin_dashboard,
+        )
+        .html_page(),
+        Operation::admin(
+            "get",
+            "/admin/newsletters",
+            "Newsletter issue form",
+            routes::newsletter_form,
+        )
+        .query(
+            "from",
+            false,
+            "An issue written in Markdown, to start the new one from.",
+        )
+        .html_page()
+        .response(
+            StatusCode::NOT_FOUND,
+            "There is no issue written in Markdown with this id.",
+        ),
+        Operation::admin(
+            "post",
+            "/admin/newsletters",
+            "Publish a newsletter issue",
+            routes::publish_newsletter,
+        )
+        .security_api_token("newsletters:publish")
+        .form(
+            &[
+                ("title", string(), "The subject of the email."),
+                (
+                    "markdown_content",
+                    string(),
+                    "The body in Markdown, a template with merge tags. \
+                        When given, both contents below are rendered from it.",
+                ),
+                (
+                    "text_content",
+                    string(),
+                    "The plain text body, a template with merge tags.",
+                ),
+                (
+                    "html_content",
+                    string(),
+                    "The HTML body, a template with merge tags. It is sanitized \
+                        and the rules of its `<style>` blocks are inlined.",
+                ),
+                (
+                    "template_id",
+                    uuid(),
+                    "The email template to wrap the issue in. Empty for none.",
+                ),
+                (
+                    "exclude_from_archive",
+                    string(),
+                    "Sent when checked: the issue is not shown in the public archive.",
+                ),
+                (
+                    "idempotency_key",
+                    string(),
+                    "Submitting the same key again does not publish the issue twice.",
+                ),
+            ],
+            true,
+        )
+        .optional_fields(&[
+            "markdown_content",
+            "text_content",
+            "html_content",
+            "template_id",
+            "exclude_from_archive",
+        ])
+        .redirect(
+            "Back to `/admin/newsletters`, with warnings about CSS that could not be \
+                inlined or an email that Gmail would clip.",
+        )
+        .response(
+            StatusCode::BAD_REQUEST,
+            "The issue has no content, or there is no template with this id.",
+        ),
+        Operation::admin(
+            "post",
+            "/admin/newsletters/preview",
+            "Preview a newsletter issue",
+            routes::preview_newsletter,
+        )
+        .form(
+            &[
+                ("title", string(), "The subject of the email."),
+                (
+                    "markdown_content",
+                    string(),
+                    "The body in Markdown, a template with merge tags.",
+                ),
+                (
+                    "template_id",
+                    uuid(),
+                    "The email template to wrap the issue in. Empty for none.",
+                ),
+                (
+                    "exclude_from_archive",
+                    string(),
+                    "Kept in the form, to publish the issue afterwards.",
+                ),
+                (
+                    "idempotency_key",
+                    string(),
+                    "Kept in the form, to publish the issue afterwards.",
+                ),
+            ],
+            true,
+        )
+        .optional_fields(&["template_id", "exclude_from_archive"])
+        .html_page()
+        .response(
+            StatusCode::BAD_REQUEST,
+            "There is no template with this id.",
+        ),
+        Operation::admin(
+            "get",
+            "/admin/newsletters/{id}",
+            "Newsletter issue report",
+            routes::newsletter_issue_report,
+        )
+        .path_parameter("id", "The id of the issue.")
+        .html_page()
+        .response(StatusCode::NOT_FOUND, "There is no issue with this id."),
+        Operation::admin(
+            "post",
+            "/admin/newsletters/{id}/archive",
+            "Add an issue to the public archive, or remove it",
+            routes::update_archive_status,
+        )
+        .path_parameter("id", "The id of the issue.")
+        .form(
+            &[(
+                "in_archive",
+                json!({ "type": "boolean" }),
+                "Whether the issue is shown in the public archive.",
+            )],
+            true,
+        )
+        .redirect("Back to the report of the issue.")
+        .response(StatusCode::NOT_FOUND, "There is no issue with this id."),
+        Operation::admin(
+            "get",
+            "/admin/password",
+            "Change password form",
+            routes::change_password_form,
+        )
+        .html_page(),
+        Operation::admin(
+            "post",
+            "/admin/password",
+            "Change the password",
+            routes::change_password,
+        )
+        .form(
+            &[
+                ("current_password", password(), "The current password."),
+                ("new_password", password(), "The new password."),
+                ("new_password_check", password(), "The new password, again."),
+            ],
+            true,
+        )
+        .redirect("Back to `/admin/password`."),
+        Operation::admin(
+            "get",
+            "/admin/email",
+            "Recovery email form",
+            routes::change_email_form,
+        )
+        .html_page(),
+        Operation::admin(
+            "post",
+            "/admin/email",
+            "Change the address password reset links are sent to",
+            routes::change_email,
+        )
+        .form(
+            &[
+                ("email", string(), "The new email address."),
+                ("current_password", password(), "The current password."),
+            ],
+            true,
+        )
+        .redirect("Back to `/admin/email`."),
+        Operation::admin(
+            "get",
+            "/admin/two-factor",
+            "Two-factor authentication settings",
+            routes::two_factor_form,
+        )
+        .html_page(),
+        Operation::admin(
+            "post",
+            "/admin/two-factor/enable",
+            "Enable two-factor authentication",
+            routes::enable_two_factor,
+        )
+        .form(
+            &[(
+                "code",
+                string(),
+                "A code from the authenticator app, to prove the enrolment worked.",
+            )],
+            true,
+        )
+        .html_page()
+        .redirect("Back to `/admin/two-factor` if the code is wrong."),
+        Operation::admin(
+            "post",
+            "/admin/two-factor/disable",
+            "Disable two-factor authentication",
+            routes::disable_two_factor,
+        )
+        .form(
+            &[(
+                "code",
+                string(),
+                "A code from the authenticator app, or a recovery code.",
+            )],
+            true,
+        )
+        .redirect("Back to `/admin/two-factor`."),
+        Operation::admin(
+            "get",
+            "/admin/sessions",
+            "Active sessions",
+            routes::sessions_list,
+        )
+        .html_page(),
+        Operation::admin(
+            "post",
+            "/admin/sessions/revoke",
+            "Revoke a session",
+            routes::revoke_session,
+        )
+        .form(&[("session_id", uuid(), "The session to revoke.")], true)
+        .redirect("Back to `/admin/sessions`."),
+        Operation::admin(
+            "post",
+            "/admin/sessions/revoke-others",
+            "Revoke every other session",
+            routes::revoke_other_sessions,
+        )
+        .form(&[], true)
+        .redirect("Back to `/admin/sessions`."),
+        Operation::admin(
+            "get",
+            "/admin/api-tokens",
+            "API tokens",
+            routes::api_tokens_list,
+        )
+        .html_page(),
+        Operation::admin(
+            "post",
+            "/admin/api-tokens",
+            "Create an API token",
+            routes::create_api_token,
+        )
+        .form(
+            &[
+                ("name", string(), "What the token is for."),
+                (
+                    "scope",
+                    json!({ "type": "array", "items": schema_ref("ApiScope") }),
+                    "What the token is allowed to do. Repeat the field for every scope.",
+                ),
+            ],
+            true,
+        )
+        .html_page()
+        .redirect("Back to `/admin/api-tokens` if the form is invalid."),
+        Operation::admin(
+            "post",
+            "/admin/api-tokens/revoke",
+            "Revoke an API token",
+            routes::revoke_api_token,
+        )
+        .form(&[("token_id", uuid(), "The token to revoke.")], true)
+        .redirect("Back to `/admin/api-tokens`."),
+        Operation::admin(
+            "get",
+            "/admin/suppressions",
+            "Suppression list",
+            routes::suppressions_list,
+        )
+        .html_page(),
+        Operation::admin(
+            "post",
+            "/admin/suppressions",
+            "Suppress an email address",
+            routes::add_suppressed_email,
+        )
+        .form(
+            &[
+                ("email", string(), "The address to never send emails to."),
+                (
+                    "reason",
+                    string(),
+                    "Why, for future reference. Can be empty.",
+                ),
+            ],
+            true,
+        )
+        .redirect("Back to `/admin/suppressions`."),
+        Operation::admin(
+            "post",
+            "/admin/suppressions/delete",
+            "Remove an address from the suppression list",
+            routes::remove_suppressed_email,
+        )
+        .form(
+            &[("email", string(), "The address to send emails to again.")],
+            true,
+        )
+        .redirect("Back to `/admin/suppressions`."),
+        Operation::admin(
+            "post",
+            "/admin/suppressions/import",
+            "Suppress many email addresses at once",
+            routes::import_suppressed_emails,
+        )
+        .form(
+            &[
+                (
+                    "emails",
+                    string(),
+                    "Addresses separated by new lines, commas or semicolons. \
+                    Invalid entries are reported and skipped.",
+                ),
+                (
+                    "reason",
+                    string(),
+                    "Why, for future reference. Can be empty.",
+                ),
+            ],
+            true,
+        )
+        .redirect("Back to `/admin/suppressions`."),
+        Operation::admin(
+            "get",
+            "/admin/templates",
+            "Email templates",
+            routes::email_templates_list,
+        )
+        .html_page(),
+        Operation::admin(
+            "post",
+            "/admin/templates",
+            "Create an email template",
+            routes::create_email_template,
+        )
+        .form(&template_fields(), true)
+        .optional_fields(&["is_default"])
+        .redirect("Back to `/admin/templates`."),
+        Operation::admin(
+            "post",
+            "/admin/templates/delete",
+            "Delete an email template",
+            routes::delete_email_template,
+        )
+        .form(
+            &[(
+                "template_id",
+                uuid(),
+                "The template to delete. Published issues keep their copy.",
+            )],
+            true,
+        )
+        .redirect("Back to `/admin/templates`."),
+        Operation::admin(
+            "get",
+            "/admin/templates/{id}",
+            "Email template form",
+            routes::email_template_form,
+        )
+        .path_parameter("id", "The id of the template.")
+        .html_page()
+        .response(StatusCode::NOT_FOUND, "There is no template with this id."),
+        Operation::admin(
+            "post",
+            "/admin/templates/{id}",
+            "Update an email template",
+            routes::update_email_template,
+        )
+        .path_parameter("id", "The id of the template.")
+        .form(&template_fields(), true)
+        .optional_fields(&["is_default"])
+        .redirect("Back to `/admin/templates/{id}`.")
+        .response(StatusCode::NOT_FOUND, "There is no template with this id."),
+        Operation::admin(
+            "get",
+            "/admin/confirmation-email",
+            "Confirmation email form",
+            routes::confirmation_email_form,
+        )
+        .html_page(),
+        Operation::admin(
+            "post",
+            "/admin/confirmation-email",
+            "Update the confirmation email",
+            routes::update_confirmation_email,
+        )
+        .form(
+            &[
+                ("subject", string(), "The subject of the email."),
+                (
+                    "html_content",
+                    string(),
+                    "The HTML body, a template with merge tags and \
+                    `{{ confirmation_link }}`. It is sanitized and the rules of its \
+                    `<style>` blocks are inlined.",
+                ),
+                (
+                    "text_content",
+                    string(),
+                    "The plain text body, a template with merge tags and \
+                    `{{ confirmation_link }}`.",
+                ),
+            ],
+            true,
+        )
+        .redirect("Back to `/admin/confirmation-email`."),
+        Operation::admin(
+            "get",
+            "/admin/welcome-email",
+            "Welcome email form",
+            routes::welcome_email_form,
+        )
+        .html_page(),
+        Operation::admin(
+            "post",
+            "/admin/welcome-email",
+            "Update the welcome email",
+            routes::update_welcome_email,
+        )
+        .form(
+            &[
+                (
+                    "enabled",
+                    string(),
+                    "Sent when checked: new subscribers get the email once they confirm.",
+                ),
+                ("subject", string(), "The subject of the email."),
+                (
+                    "html_content",
+                    string(),
+                    "The HTML body, a template with merge tags. It is sanitized \
+                        and the rules of its `<style>` blocks are inlined.",
+                ),
+                (
+                    "text_content",
+                    string(),
+                    "The plain text body, a template with merge tags.",
+                ),
+            ],
+            true,
+        )
+        .optional_fields(&["enabled"])
+        .redirect("Back to `/admin/welcome-email`."),
+        Operation::admin(
+            "get",
+            "/admin/webhooks",
+            "Webhook endpoints",
+            routes::webhooks_list,
+        )
+        .html_page(),
+        Operation::admin(
+            "post",
+            "/admin/webhooks",
+            "Add a webhook endpoint",
+            routes::create_webhook_endpoint,
+        )
+        .form(
+            &[(
+                "url",
+                string(),
+                "Where to post events. Only `http` and `https` URLs are accepted.",
+            )],
+            true,
+        )
+        .html_page()
+        .redirect("Back to `/admin/webhooks` if the URL is invalid."),
+        Operation::admin(
+            "post",
+            "/admin/webhooks/delete",
+            "Delete a webhook endpoint",
+            routes::delete_webhook_endpoint,
+        )
+        .form(
+            &[(
+                "endpoint_id",
+                uuid(),
+                "The endpoint to stop sending events to.",
+            )],
+            true,
+        )
+        .redirect("Back to `/admin/webhooks`."),
+        Operation::admin("post", "/admin/logout", "Log out", routes::log_out)
+            .form(&[], true)
+            .redirect("To `/login`."),
+    ]
+}
+
+fn api_operations() -> Vec<Operation> {
+    vec![
+        Operation::api(
+            "get",
+            "/api/v1/newsletters",
+            "List newsletter issues",
+            routes::list_newsletter_issues,
+        )
+        .security_api_token("newsletters:read")
+        .json_response(
+            StatusCode::OK,
+            "Every issue, most recent first.",
+            schema_ref("NewsletterIssueList"),
+        ),
+        Operation::api(
+            "post",
+            "/api/v1/newsletters",
+            "Publish a newsletter issue",
+            routes::create_newsletter_issue,
+        )
+        .security_api_token("newsletters:publish")
+        .header(
+            "Idempotency-Key",
+            false,
+            "Retrying with the same key does not publish the issue twice.",
+        )
+        .json_body(schema_ref("NewNewsletterIssue"))
+        .json_response(
+            StatusCode::ACCEPTED,
+            "The issue is being delivered. `Location` points to it.",
+            schema_ref("NewsletterIssue"),
+        )
+        .problems(&[StatusCode::BAD_REQUEST]),
+        Operation::api(
+            "get",
+            "/api/v1/newsletters/{id}",
+            "Get a newsletter issue",
+            routes::get_newsletter_issue,
+        )
+        .security_api_token("newsletters:read")
+        .path_parameter("id", "The id of the issue.")
+        .json_response(
+            StatusCode::OK,
+            "The issue and its delivery status.",
+            schema_ref("NewsletterIssue"),
+        )
+        .problems(&[StatusCode::NOT_FOUND]),
+        Operation::api(
+            "get",
+            "/api/v1/subscribers",
+            "List subscribers",
+            routes::list_subscribers,
+        )
+        .security_api_token("subscribers:read")
+        .query(
+            "limit",
+            false,
+            "The size of the page, from 1 to 100. Defaults to 50.",
+        )
+        .query("cursor", false, "The `next_cursor` of the previous page.")
+        .json_response(
+            StatusCode::OK,
+            "A page of subscribers, oldest first.",
+            schema_ref("SubscriberPage"),
+        )
+        .problems(&[StatusCode::BAD_REQUEST]),
+        Operation::api(
+            "post",
+            "/api/v1/subscribers",
+            "Create a subscriber",
+            routes::create_subscriber,
+        )
+        .security_api_token("subscribers:write")
+        .json_body(schema_ref("NewSubscriber"))
+        .json_response(
+            StatusCode::CREATED,
+            "The new subscriber. `Location` points to it.",
+            schema_ref("Subscriber"),
+        )
+        .problems(&[StatusCode::BAD_REQUEST, StatusCode::CONFLICT]),
+        Operation::api(
+            "get",
+            "/api/v1/subscribers/{id}",
+            "Get a subscriber",
+            routes::get_subscriber,
+        )
+        .security_api_token("subscribers:read")
+        .path_parameter("id", "The id of the subscriber.")
+        .json_response(StatusCode::OK, "The subscriber.", schema_ref("Subscriber"))
+        .problems(&[StatusCode::NOT_FOUND]),
+        Operation::api(
+            "patch",
+            "/api/v1/subscribers/{id}",
+            "Update a subscriber",
+            routes::update_subscriber,
+        )
+        .security_api_token("subscribers:write")
+        .path_parameter("id", "The id of the subscriber.")
+        .json_body(schema_ref("SubscriberUpdate"))
+        .json_response(
+            StatusCode::OK,
+            "The updated subscriber.",
+            schema_ref("Subscriber"),
+        )
+        .problems(&[
+            StatusCode::BAD_REQUEST,
+            StatusCode::NOT_FOUND,
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ]),
+        Operation::api(
+            "delete",
+            "/api/v1/subscribers/{id}",
+            "Delete a subscriber",
+            routes::delete_subscriber,
+        )
+        .security_api_token("subscribers:write")
+        .path_parameter("id", "The id of the subscriber.")
+        .response(StatusCode::NO_CONTENT, "The subscriber has been deleted.")
+        .problems(&[StatusCode::NOT_FOUND]),
+    ]
+}
+
+fn schemas() -> Value {
+    let delivery_status = json!({
+        "type": "string",
+        "enum": ["in_progress", "completed"],
+    });
+    let subscription_status = json!({
+        "type": "string",
+        "enum": ["pending_confirmation", "confirmed", "suppressed"],
+    });
+    let content = json!({
+        "description": "Both bodies are Jinja templates, rendered for every subscriber \
+            with `name`, `email`, `unsubscribe_url` and `preferences_url`. \
+            Values are escaped in the HTML body. The HTML body is sanitized and \
+            the rules of its `<style>` blocks are inlined before it is stored.",
+        "type": "object",
+        "required": ["text", "html"],
+        "properties": {
+            "text": string(),
+            "html": string(),
+        },
+    });
+
+    json!({
+        "Problem": {
+            "description": "An RFC 7807 problem document.",
+            "type": "object",
+            "required": ["type", "title", "status"],
+            "properties": {
+                "type": string(),
+                "title": string(),
+                "status": { "type": "integer" },
+                "detail": string(),
+            },
+        },
+        "EmailEvent": {
+            "description": "A Postmark bounce or spam-complaint webhook. \
+                Hard bounces and complaints suppress the subscriber, soft bounces are counted \
+                and other record types are ignored.",
+            "type": "object",
+            "required": ["RecordType"],
+            "properties": {
+                "RecordType": { "type": "string", "examples": ["Bounce", "SpamComplaint"] },
+                "Type": { "type": "string", "examples": ["HardBounce", "SoftBounce"] },
+                "Email": string(),
+            },
+        },
+        "ApiScope": {
+            "type": "string",
+            "enum": crate::authentication::ApiScope::ALL.map(|scope| scope.as_str()),
+        },
+        "NewNewsletterIssue": {
+            "type": "object",
+            "required": ["title", "content"],
+            "properties": {
+                "title": string(),
+                "content": content,
+            },
+        },
+        "NewsletterIssue": {
+            "type": "object",
+            "required": ["newsletter_issue_id", "title", "content", "published_at", "delivery"],
+            "properties": {
+                "newsletter_issue_id": uuid(),
+                "title": string(),
+                "content": content,
+                "published_at": { "type": "string", "format": "date-time" },
+                "delivery": {
+                    "type": "object",
+                    "required": ["status", "recipients", "pending"],
+                    "properties": {
+                        "status": delivery_status,
+                        "recipients": { "type": "integer" },
+                        "pending": { "type": "integer" },
+                    },
+                },
+            },
+        },
+        "NewsletterIssueList": {
+            "type": "object",
+            "required": ["newsletter_issues"],
+            "properties": {
+                "newsletter_issues": {
+                    "type": "array",
+                    "items": schema_ref("NewsletterIssue"),
+                },
+            },
+        },
+        "NewSubscriber": {
+            "type": "object",
+            "required": ["email", "name"],
+            "properties": {
+                "email": { "type": "string", "format": "email" },
+                "name": string(),
+                "confirmed": {
+                    "type": "boolean",
+                    "default": false,
+                    "description": "Skip the confirmation email.",
+                },
+            },
+        },
+        "SubscriberUpdate": {
+            "description": "Fields that are left out are not changed. \
+                Suppression is managed by the suppression list: \
+                the status cannot be changed to or from `suppressed`.",
+            "type": "object",
+            "properties": {
+                "name": string(),
+                "status": subscription_status,
+            },
+        },
+        "Subscriber": {
+            "type": "object",
+            "required": ["id", "email", "name", "status", "subscribed_at"],
+            "properties": {
+                "id": uuid(),
+                "email": { "type": "string", "format": "email" },
+                "name": string(),
+                "status": subscription_status,
+                "subscribed_at": { "type": "string", "format": "date-time" },
+            },
+        },
+        "SubscriberPage": {
+            "type": "object",
+            "required": ["subscribers"],
+            "properties": {
+                "subscribers": {
+                    "type": "array",
+                    "items": schema_ref("Subscriber"),
+                },
+                "next_cursor": {
+                    "type": "string",
+                    "description": "Absent on the last page.",
+                },
+            },
+        },
+    })
+}
+
+/// The fields of the form to create or update an email template.
+fn template_fields() -> [(&'static str, Value, &'static str); 4] {
+    [
+        ("name", string(), "Shown when choosing a template."),
+        (
+            "html_layout",
+            string(),
+            "The HTML layout, a template with merge tags and `{{ content }}`.",
+        ),
+        (
+            "text_layout",
+            string(),
+            "The plain text layout, a template with merge tags and `{{ content }}`.",
+        ),
+        (
+            "is_default",
+            string(),
+            "Sent when checked: the layout of the emails sent by the application.",
+        ),
+    ]
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn password() -> Value {
+    json!({ "type": "string", "format": "password" })
+}
+
+fn uuid() -> Value {
+    json!({ "type": "string", "format": "uuid" })
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{name}") })
+}
+
+struct Operation {
+    method: &'static str,
+    path: &'static str,
+    group: RouteGroup,
+    spec: Map<String, Value>,
+    route: Box<dyn Fn() -> Route>,
+    form_config: Option<web::FormConfig>,
+}
+
+impl Operation {
+    fn new<F, Args>(method: &'static str, path: &'static str, summary: &str, handler: F) -> Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        let mut spec = Map::new();
+        spec.insert("summary".into(), summary.into());
+        spec.insert("responses".into(), json!({}));
+        let http_method = Method::from_bytes(method.to_ascii_uppercase().as_bytes()).unwrap();
+        Self {
+            method,
+            path,
+            group: RouteGroup::Public,
+            spec,
+            route: Box::new(move || web::method(http_method.clone()).to(handler.clone())),
+            form_config: None,
+        }
+    }
+
+    /// A page or form of the admin area, which requires a browser session.
+    fn admin<F, Args>(method: &'static str, path: &'static str, summary: &str, handler: F) -> Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        let operation = Self::new(method, path, summary, handler)
+            .group(RouteGroup::Admin)
+            .tag("admin")
+            .security("session", &[])
+            .redirect_when_anonymous();
+        if method == "get" {
+            operation
+        } else {
+            operation.csrf_failure()
+        }
+    }
+
+    /// An endpoint of the JSON API, which requires an API token.
+    fn api<F, Args>(method: &'static str, path: &'static str, summary: &str, handler: F) -> Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        Self::new(method, path, summary, handler)
+            .group(RouteGroup::Api)
+            .tag("api")
+            .problems(&[StatusCode::UNAUTHORIZED, StatusCode::FORBIDDEN])
+    }
+
+    fn group(mut self, group: RouteGroup) -> Self {
+        self.group = group;
+        self
+    }
+
+    /// How to parse the form of this route, e.g. to answer an invalid one
+    /// with a page rather than a plain 400.
+    fn form_config(mut self, form_config: web::FormConfig) -> Self {
+        self.form_config = Some(form_config);
+        self
+    }
+
+    fn tag(mut self, tag: &str) -> Self {
+        self.spec.insert("tags".into(), json!([tag]));
+        self
+    }
+
+    fn security(mut self, scheme: &str, scopes: &[&str]) -> Self {
+        let security = self
+            .spec
+            .entry("security")
+            .or_insert_with(|| json!([]))
+            .as_array_mut()
+            .unwrap();
+        security.push(json!({ scheme: scopes }));
+        self
+    }
+
+    fn security_api_token(self, scope: &str) -> Self {
+        self.security("apiToken", &[scope])
+    }
+
+    fn parameter(mut self, location: &str, name: &str, required: bool, description: &str) -> Self {
+        let parameters = self
+            .spec
+            .entry("parameters")
+            .or_insert_with(|| json!([]))
+            .as_array_mut()
+            .unwrap();
+        parameters.push(json!({
+            "name": name,
+            "in": location,
+            "required": required,
+            "description": description,
+            "schema": string(),
+        }));
+        self
+    }
+
+    fn query(self, name: &str, required: bool, description: &str) -> Self {
+        self.parameter("query", name, required, description)
+    }
+
+    fn header(self, name: &str, required: bool, description: &str) -> Self {
+        self.parameter("header", name, required, description)
+    }
+
+    fn path_parameter(self, name: &str, description: &str) -> Self {
+        self.parameter("path", name, true, description)
+    }
+
+    /// An URL-encoded form. Forms protected against CSRF must also carry
+    /// the token embedded in the page they come from.
+    fn form(mut self, fields: &[(&str, Value, &str)], csrf_protected: bool) -> Self {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for (name, schema, description) in fields {
+            let mut schema = schema.clone();
+            schema["description"] = (*description).into();
+            properties.insert((*name).into(), schema);
+            required.push(*name);
+        }
+        if csrf_protected {
+            properties.insert(
+                "csrf_token".into(),
+                json!({
+                    "type": "string",
+                    "description": "The CSRF token embedded in the form. \
+                        Not needed when authenticating with an API token.",
+                }),
+            );
+            required.push("csrf_token");
+        }
+        self.spec.insert(
+            "requestBody".into(),
+            json!({
+                "required": true,
+                "content": {
+                    "application/x-www-form-urlencoded": {
+                        "schema": {
+                            "type": "object",
+                            "required": required,
+                            "properties": properties,
+                        },
+                    },
+                },
+            }),
+        );
+        self
+    }
+
+    /// Form fields that can be left out.
+    fn optional_fields(mut self, names: &[&str]) -> Self {
+        let schema =
+            &mut self.spec["requestBody"]["content"]["application/x-www-form-urlencoded"]["schema"];
+        if let Some(required) = schema["required"].as_array_mut() {
+            required.retain(|name| !names.iter().any(|n| name == n));
+        }
+        self
+    }
+
+    fn json_body(mut self, schema: Value) -> Self {
+        self.spec.insert(
+            "requestBody".into(),
+            json!({
+                "required": true,
+                "content": { "application/json": { "schema": schema } },
+            }),
+        );
+        self
+    }
+
+    fn insert_response(mut self, status: StatusCode, response: Value) -> Self {
+        self.spec["responses"][status.as_str()] = response;
+        self
+    }
+
+    fn response(self, status: StatusCode, description: &str) -> Self {
+        self.insert_response(status, json!({ "description": description }))
+    }
+
+    fn json_response(self, status: StatusCode, description: &str, schema: Value) -> Self {
+        self.insert_response(
+            status,
+            json!({
+                "description": description,
+                "content": { "application/json": { "schema": schema } },
+            }),
+        )
+    }
+
+    fn html_page(self) -> Self {
+        self.insert_response(
+            StatusCode::OK,
+            json!({
+                "description": "An HTML page.",
+                "content": { "text/html": { "schema": string() } },
+            }),
+        )
+    }
+
+    /// The 20 most recent issues of the archive, with conditional requests.
+    fn feed(self, media_type: &str) -> Self {
+        self.header(
+            "If-None-Match",
+            false,
+            "The `ETag` of the version of the feed the client has.",
+        )
+        .header(
+            "If-Modified-Since",
+            false,
+            "The `Last-Modified` date of the version of the feed the client has.",
+        )
+        .insert_response(
+            StatusCode::OK,
+            json!({
+                "description": "The 20 most recent issues of the public archive.",
+                "headers": {
+                    "ETag": { "schema": string() },
+                    "Last-Modified": { "schema": string() },
+                },
+                "content": { media_type: { "schema": string() } },
+            }),
+        )
+        .response(
+            StatusCode::NOT_MODIFIED,
+            "The client already has this version of the feed.",
+        )
+    }
+
+    fn redirect(self, description: &str) -> Self {
+        self.insert_response(
+            StatusCode::SEE_OTHER,
+            json!({
+                "description": description,
+                "headers": { "Location": { "schema": string() } },
+            }),
+        )
+    }
+
+    fn redirect_when_anonymous(self) -> Self {
+        self.redirect("To `/login` when the user is not logged in.")
+    }
+
+    fn csrf_failure(self) -> Self {
+        self.response(
+            StatusCode::FORBIDDEN,
+            "The CSRF token is missing or does not match the session.",
+        )
+    }
+
+    /// Variants sharing a status code are described together.
+    fn errors<E: DocumentedError>(mut self) -> Self {
+        let mut descriptions: Vec<(StatusCode, String)> = Vec::new();
+        for (error, description) in E::examples() {
+            let status = error.status_code();
+            match descriptions.iter_mut().find(|(s, _)| *s == status) {
+                Some((_, previous)) => *previous = format!("{previous} {description}"),
+                None => descriptions.push((status, description.to_owned())),
+            }
+        }
+        for (status, description) in descriptions {
+            self = self.response(status, &description);
+        }
+        self
+    }
+
+    /// Problem documents, for the given statuses and unexpected errors.
+    fn problems(mut self, statuses: &[StatusCode]) -> Self {
+        for (error, description) in ApiError::examples() {
+            let status = error.status_code();
+            if statuses.contains(&status) || status == StatusCode::INTERNAL_SERVER_ERROR {
+                self = self.insert_response(
+                    status,
+                    json!({
+                        "description": description,
+                        "content": {
+                            "application/problem+json": { "schema": schema_ref("Problem") },
+                        },
+                    }),
+                );
+            }
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{openapi_document, operations};
+
+    #[test]
+    fn routes_are_registered_under_the_prefix_of_their_group() {
+        for operation in operations() {
+            assert!(
+                operation.path.starts_with(operation.group.prefix()),
+                "{} {} is not under {}",
+                operation.method,
+                operation.path,
+                operation.group.prefix()
+            );
+        }
+    }
+
+    #[test]
+    fn routes_are_registered_once() {
+        let operations = operations();
+        for (i, operation) in operations.iter().enumerate() {
+            assert!(
+                !operations[..i]
+                    .iter()
+                    .any(|o| o.method == operation.method && o.path == operation.path),
+                "{} {} is registered twice",
+                operation.method,
+                operation.path
+            );
+        }
+    }
+
+    #[test]
+    fn errors_sharing_a_status_code_are_all_described() {
+        let document = openapi_document();
+        let description = document["paths"]["/subscriptions/confirm"]["get"]["responses"]["400"]
+            ["description"]
+            .as_str()
+            .unwrap();
+        assert_eq!(
+            description,
+            "The link has no token. `redirect_to` is not a page of our site."
+        );
+    }
+
+    #[test]
+    fn schema_references_resolve() {
+        let document = openapi_document();
+        let text = document.to_string();
+        for reference in text.split(r##""$ref":"#/components/schemas/"##).skip(1) {
+            let name = reference.split('"').next().unwrap();
+            assert!(
+                document["components"]["schemas"].get(name).is_some(),
+                "Unknown schema: {name}"
+            );
+        }
+    }
+
+    #[test]
+    fn path_parameters_are_declared() {
+        let document = openapi_document();
+        for (path, item) in document["paths"].as_object().unwrap() {
+            for (method, operation) in item.as_object().unwrap() {
+                let n_declared = operation["parameters"]
+                    .as_array()
+                    .map(|parameters| parameters.iter().filter(|p| p["in"] == "path").count())
+                    .unwrap_or_default();
+                assert_eq!(
+                    path.matches('{').count(),
+                    n_declared,
+                    "{method} {path} does not declare its path parameters"
+                );
+            }
+        }
+    }
+}
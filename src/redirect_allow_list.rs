@@ -0,0 +1,106 @@
+//! Where subscribers may be sent once they subscribed or confirmed, e.g.
+//! back to the page of our site that showed the subscription form.
+//! Anything else would turn those routes into an open redirect.
+use anyhow::Context;
+use reqwest::Url;
+
+pub struct RedirectAllowList {
+    base_url: Url,
+    allowed: Vec<Url>,
+}
+
+impl RedirectAllowList {
+    /// Any page under the base URL is allowed, as well as any page under
+    /// one of the `allowed` URLs, e.g. the marketing site.
+    pub fn new(base_url: &str, allowed: &[String]) -> Result<Self, anyhow::Error> {
+        let base_url = Url::parse(base_url)
+            .with_context(|| format!("The base URL {base_url} is not a valid URL."))?;
+        let mut allowed = allowed
+            .iter()
+            .map(|url| {
+                Url::parse(url).with_context(|| {
+                    format!("The redirect allow-list entry {url} is not a valid URL.")
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        allowed.push(base_url.clone());
+        Ok(Self { base_url, allowed })
+    }
+
+    /// The absolute URL to redirect to, or `None` if `target` is not allowed.
+    /// Relative targets are resolved against the base URL.
+    pub fn resolve(&self, target: &str) -> Option<String> {
+        let url = self.base_url.join(target.trim()).ok()?;
+        if !matches!(url.scheme(), "http" | "https")
+            || !url.username().is_empty()
+            || url.password().is_some()
+        {
+            return None;
+        }
+        self.allowed
+            .iter()
+            .any(|allowed| allowed.origin() == url.origin() && is_under(url.path(), allowed.path()))
+            .then(|| url.into())
+    }
+}
+
+/// `/blog/post` is under `/blog`, `/blogger` is not.
+fn is_under(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => prefix.ends_with('/') || rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RedirectAllowList;
+
+    fn allow_list() -> RedirectAllowList {
+        RedirectAllowList::new(
+            "https://newsletter.example.com",
+            &["https://www.example.com/blog".to_owned()],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn pages_of_our_own_sites_are_allowed() {
+        let allow_list = allow_list();
+        for (target, expected) in [
+            ("/archive", "https://newsletter.example.com/archive"),
+            (
+                "https://newsletter.example.com/archive?page=2",
+                "https://newsletter.example.com/archive?page=2",
+            ),
+            (
+                "https://www.example.com/blog",
+                "https://www.example.com/blog",
+            ),
+            (
+                "https://www.example.com/blog/thanks",
+                "https://www.example.com/blog/thanks",
+            ),
+        ] {
+            assert_eq!(allow_list.resolve(target).as_deref(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn other_urls_are_rejected() {
+        let allow_list = allow_list();
+        for target in [
+            "https://evil.example.com/",
+            "//evil.example.com/archive",
+            "/\\evil.example.com",
+            "http://newsletter.example.com/archive",
+            "https://newsletter.example.com:8443/",
+            "https://www.example.com/",
+            "https://www.example.com/blogger",
+            "https://user@newsletter.example.com/",
+            "javascript:alert(1)",
+        ] {
+            assert_eq!(allow_list.resolve(target), None, "{target} was allowed");
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    authentication::{csrf_token, list_api_tokens, ApiScope, UserId},
+    session_state::TypedSession,
+    utils::e500,
+};
+
+pub async fn api_tokens_list(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let tokens = list_api_tokens(*user_id.into_inner(), &pool)
+        .await
+        .map_err(e500)?;
+    let mut rows_html = String::new();
+    for token in tokens {
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>
+                    <form action="/admin/api-tokens/revoke" method="post">
+                        <input hidden type="text" name="token_id" value="{}">
+                        <input hidden type="text" name="csrf_token" value="{}" />
+                        <button type="submit">Revoke</button>
+                    </form>
+                </td>
+            </tr>"#,
+            htmlescape::encode_minimal(&token.name),
+            token.scopes.join(", "),
+            token.created_at.format("%Y-%m-%d %H:%M UTC"),
+            token
+                .last_used_at
+                .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_else(|| "Never".into()),
+            token.token_id,
+            csrf_token,
+        )
+        .unwrap();
+    }
+
+    let mut scopes_html = String::new();
+    for scope in ApiScope::ALL {
+        writeln!(
+            scopes_html,
+            r#"<label><input type="checkbox" name="scope" value="{}"> {}</label><br>"#,
+            scope.as_str(),
+            scope.description(),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>API tokens</title>
+                </head>
+                <body>
+                    {msg_html}
+                    <table>
+                        <tr>
+                            <th>Name</th>
+                            <th>Scopes</th>
+                            <th>Created</th>
+                            <th>Last used</th>
+                            <th></th>
+                        </tr>
+                        {rows_html}
+                    </table>
+                    <h2>New token</h2>
+                    <form action="/admin/api-tokens" method="post">
+                        <label>Name
+                            <input type="text" placeholder="e.g. Release pipeline" name="name">
+                        </label>
+                        <br>
+                        {scopes_html}
+                        <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                        <button type="submit">Create token</button>
+                    </form>
+                    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+                </body>
+            </html>
+            "#,
+        )))
+}
@@ -0,0 +1,95 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{self, ApiScope, UserId},
+    utils::{e500, see_other},
+};
+
+/// Checkboxes share the same field name: the form is read as a list of pairs.
+#[tracing::instrument(name = "Create an API token", skip_all, fields(user_id = %&*user_id))]
+pub async fn create_api_token(
+    form: web::Form<Vec<(String, String)>>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let mut name = String::new();
+    let mut scopes = Vec::new();
+    for (key, value) in form.0 {
+        match key.as_str() {
+            "name" => name = value.trim().to_owned(),
+            "scope" => match ApiScope::parse(&value) {
+                Some(scope) if !scopes.contains(&scope) => scopes.push(scope),
+                Some(_) => {}
+                None => {
+                    // Flash messages are rendered as HTML.
+                    FlashMessage::error(format!(
+                        "Unknown scope: {}.",
+                        htmlescape::encode_minimal(&value)
+                    ))
+                    .send();
+                    return Ok(see_other("/admin/api-tokens"));
+                }
+            },
+            _ => {}
+        }
+    }
+
+    if name.is_empty() {
+        FlashMessage::error("The token must have a name.").send();
+        return Ok(see_other("/admin/api-tokens"));
+    }
+    if scopes.is_empty() {
+        FlashMessage::error("The token must have at least one scope.").send();
+        return Ok(see_other("/admin/api-tokens"));
+    }
+
+    let token = authentication::create_api_token(*user_id, &name, &scopes, &pool)
+        .await
+        .map_err(e500)?;
+
+    // Tokens are stored hashed: this is the only time they are shown.
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>API tokens</title>
+                </head>
+                <body>
+                    <p>The token <b>{name}</b> has been created.</p>
+                    <p>Copy it now: it will not be shown again.</p>
+                    <p><code>{token}</code></p>
+                    <p>Send it in the <code>Authorization: Bearer</code> header.</p>
+                    <p><a href="/admin/api-tokens">&lt;- Back</a></p>
+                </body>
+            </html>
+            "#,
+            name = htmlescape::encode_minimal(&name),
+            token = token.expose_secret(),
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RevokeFormData {
+    token_id: Uuid,
+}
+
+#[tracing::instrument(name = "Revoke an API token", skip_all, fields(user_id = %&*user_id))]
+pub async fn revoke_api_token(
+    form: web::Form<RevokeFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    authentication::revoke_api_token(*user_id.into_inner(), form.0.token_id, &pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("The API token has been revoked.").send();
+    Ok(see_other("/admin/api-tokens"))
+}
@@ -0,0 +1,64 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    authentication::csrf_token, confirmation_email::get_confirmation_email,
+    session_state::TypedSession, utils::e500,
+};
+
+pub async fn confirmation_email_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let confirmation_email = get_confirmation_email(pool.get_ref()).await.map_err(e500)?;
+    let subject = htmlescape::encode_attribute(&confirmation_email.subject);
+    let html_content = htmlescape::encode_minimal(&confirmation_email.html_content);
+    let text_content = htmlescape::encode_minimal(&confirmation_email.text_content);
+    let updated_at = confirmation_email.updated_at.format("%Y-%m-%d %H:%M UTC");
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Confirmation email</title>
+                </head>
+                <body>
+                    {msg_html}
+                    <h1>Confirmation email</h1>
+                    <p>Last updated: {updated_at}</p>
+                    <form action="/admin/confirmation-email" method="post">
+                        <label>Subject:<br>
+                            <input type="text" name="subject" value="{subject}">
+                        </label>
+                        <br>
+                        <label>HTML content:<br>
+                            <textarea name="html_content" rows="20" cols="50">{html_content}</textarea>
+                        </label>
+                        <br>
+                        <label>Plain text content:<br>
+                            <textarea name="text_content" rows="20" cols="50">{text_content}</textarea>
+                        </label>
+                        <p>Both contents must include <code>{{{{ confirmation_link }}}}</code>.
+                        They can use the same merge tags as issues, e.g.
+                        <code>{{{{ name }}}}</code>. They are wrapped in the default email template.</p>
+                        <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                        <button type="submit">Save</button>
+                    </form>
+                    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+                </body>
+            </html>
+            "#,
+        )))
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::confirmation_email_form;
+pub use post::update_confirmation_email;
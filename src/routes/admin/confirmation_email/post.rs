@@ -0,0 +1,48 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use crate::{
+    confirmation_email::save_confirmation_email,
+    email_html::prepare_html,
+    merge_tags::validate_confirmation_email,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    subject: String,
+    html_content: String,
+    text_content: String,
+}
+
+impl FormData {
+    /// The message to show when the form cannot be saved as is.
+    fn validate(&self) -> Result<(), String> {
+        if self.subject.trim().is_empty() {
+            return Err("The confirmation email needs a subject.".into());
+        }
+        validate_confirmation_email(&self.html_content, &self.text_content)
+    }
+}
+
+#[tracing::instrument(name = "Update the confirmation email", skip(form, pool))]
+pub async fn update_confirmation_email(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Err(e) = form.validate() {
+        FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+        return Ok(see_other("/admin/confirmation-email"));
+    }
+    // Sanitized and with its CSS inlined, like the HTML of issues.
+    let html = prepare_html(&form.html_content);
+    save_confirmation_email(form.subject.trim(), &html.html, &form.text_content, &pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("The confirmation email has been saved.").send();
+    for warning in &html.warnings {
+        FlashMessage::warning(htmlescape::encode_minimal(warning)).send();
+    }
+    Ok(see_other("/admin/confirmation-email"))
+}
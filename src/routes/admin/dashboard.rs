@@ -3,13 +3,19 @@ use anyhow::Context;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::{authentication::UserId, utils::e500};
+use crate::{
+    authentication::{csrf_token, UserId},
+    session_state::TypedSession,
+    utils::e500,
+};
 
 pub async fn admin_dashboard(
     pool: web::Data<PgPool>,
     user_id: web::ReqData<UserId>,
+    session: TypedSession,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
+    let csrf_token = csrf_token(&session).map_err(e500)?;
 
     let username = get_username(*user_id, &pool).await.map_err(e500)?;
 
@@ -27,11 +33,21 @@ pub async fn admin_dashboard(
                     <p>Welcome {username}!</p>
                     <ol>
                         <li><a href="/admin/password">Change password</a></li>
+                        <li><a href="/admin/email">Recovery email</a></li>
+                        <li><a href="/admin/two-factor">Two-factor authentication</a></li>
+                        <li><a href="/admin/sessions">Active sessions</a></li>
+                        <li><a href="/admin/api-tokens">API tokens</a></li>
+                        <li><a href="/admin/webhooks">Webhooks</a></li>
+                        <li><a href="/admin/suppressions">Suppression list</a></li>
+                        <li><a href="/admin/templates">Email templates</a></li>
+                        <li><a href="/admin/confirmation-email">Confirmation email</a></li>
+                        <li><a href="/admin/welcome-email">Welcome email</a></li>
                         <li>
                             <a href="/admin/newsletters">Newsletter</a></li>
                         </li>
                         <li>
                             <form name="logoutForm" action="/admin/logout" method="post">
+                                <input hidden type="text" name="csrf_token" value="{csrf_token}" />
                                 <input type="submit" value="Logout">
                             </form>
                         </li>
@@ -0,0 +1,91 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{csrf_token, UserId},
+    session_state::TypedSession,
+    utils::e500,
+};
+
+pub async fn change_email_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let current_email = match get_email(**user_id, &pool).await.map_err(e500)? {
+        Some(email) => format!(
+            "<p>Password reset links are sent to <b>{}</b>.</p>",
+            htmlescape::encode_minimal(&email)
+        ),
+        None => "<p>No email address is set: you cannot reset your password \
+            if you forget it.</p>"
+            .to_owned(),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+        <!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Recovery email</title>
+            </head>
+            <body>
+                {msg_html}
+                {current_email}
+                <form action="/admin/email" method="post">
+                    <label>New email address
+                    <input
+                        type="email"
+                        placeholder="Enter the new email address"
+                        name="email"
+                    >
+                    </label>
+                    <br>
+                    <label>Current password
+                    <input
+                        type="password"
+                        placeholder="Enter current password"
+                        name="current_password"
+                    >
+                    </label>
+                    <br>
+                    <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                    <button type="submit">Change email</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+        </html>
+        "#,
+        )))
+}
+
+#[tracing::instrument(name = "Get the email of a user", skip(pool))]
+async fn get_email(user_id: Uuid, pool: &PgPool) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT email
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve the email of the user.")?;
+
+    Ok(row.email)
+}
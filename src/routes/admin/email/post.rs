@@ -0,0 +1,99 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::Secret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{validate_credentials, AuthError, Credentials, PasswordHashingPolicy, UserId},
+    domain::SubscriberEmail,
+    routes::admin::dashboard::get_username,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    email: String,
+    current_password: Secret<String>,
+}
+
+enum EmailUpdate {
+    Updated,
+    /// Another user already receives their password reset links there.
+    AlreadyTaken,
+}
+
+/// Change the address password reset links are sent to.
+/// The current password is required, so that whoever gets hold of an open
+/// session cannot take over the account through a password reset.
+#[tracing::instrument(
+    name = "Change the email of a user",
+    skip(form, pool, password_hashing_policy, user_id)
+)]
+pub async fn change_email(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    password_hashing_policy: web::Data<PasswordHashingPolicy>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let FormData {
+        email,
+        current_password,
+    } = form.0;
+
+    let email = match SubscriberEmail::parse(email) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+            return Ok(see_other("/admin/email"));
+        }
+    };
+
+    let credentials = Credentials {
+        username: get_username(*user_id, &pool).await.map_err(e500)?,
+        password: current_password,
+    };
+    if let Err(e) = validate_credentials(credentials, &password_hashing_policy, &pool).await {
+        return match e {
+            AuthError::InvalidCredentials(_) => {
+                FlashMessage::error("The current password is incorrect.").send();
+                Ok(see_other("/admin/email"))
+            }
+            AuthError::UnexpectedError(_) => Err(e500(e)),
+        };
+    }
+
+    match update_email(*user_id, &email, &pool).await.map_err(e500)? {
+        EmailUpdate::Updated => FlashMessage::info("Your email address has been changed.").send(),
+        EmailUpdate::AlreadyTaken => {
+            FlashMessage::error("This email address is already used by another account.").send()
+        }
+    }
+    Ok(see_other("/admin/email"))
+}
+
+async fn update_email(
+    user_id: Uuid,
+    email: &SubscriberEmail,
+    pool: &PgPool,
+) -> Result<EmailUpdate, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE users
+        SET email = $1
+        WHERE user_id = $2
+        "#,
+        email.as_ref(),
+        user_id
+    )
+    .execute(pool)
+    .await;
+
+    match result {
+        Ok(_) => Ok(EmailUpdate::Updated),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(EmailUpdate::AlreadyTaken),
+        Err(e) => Err(e).context("Failed to update the email of the user."),
+    }
+}
@@ -1,13 +1,21 @@
 use crate::authentication::UserId;
+use crate::session_registry::SessionRegistry;
 use crate::session_state::TypedSession;
-use crate::utils::see_other;
+use crate::utils::{e500, see_other};
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 
 pub async fn log_out(
     session: TypedSession,
-    _user_id: web::ReqData<UserId>,
+    session_registry: web::Data<SessionRegistry>,
+    user_id: web::ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
+    if let Some(session_id) = session.get_session_id().map_err(e500)? {
+        session_registry
+            .revoke(**user_id, session_id)
+            .await
+            .map_err(e500)?;
+    }
     session.log_out();
     FlashMessage::info("You have successfully logged out.").send();
     Ok(see_other("/login"))
@@ -1,9 +1,27 @@
+mod api_tokens;
+mod confirmation_email;
 mod dashboard;
+mod email;
 mod logout;
 mod newsletter;
 mod password;
+mod sessions;
+mod suppressions;
+mod templates;
+mod two_factor;
+mod webhooks;
+mod welcome_email;
 
-pub use dashboard::admin_dashboard;
+pub use api_tokens::*;
+pub use confirmation_email::*;
+pub use dashboard::{admin_dashboard, get_username};
+pub use email::*;
 pub use logout::*;
 pub use newsletter::*;
 pub use password::*;
+pub use sessions::*;
+pub use suppressions::*;
+pub use templates::*;
+pub use two_factor::*;
+pub use webhooks::*;
+pub use welcome_email::*;
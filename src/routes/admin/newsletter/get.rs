@@ -1,20 +1,107 @@
-use crate::authentication::UserId;
+use crate::{
+    authentication::{csrf_token, UserId},
+    email_templates::list_email_templates,
+    session_state::TypedSession,
+    utils::{e404, e500},
+};
 use actix_web::{http::header::ContentType, web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
 use std::fmt::Write;
 use uuid::Uuid;
 
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    /// An issue to start from, if it was written in Markdown.
+    from: Option<Uuid>,
+}
+
+/// What the editor is writing, kept across previews.
+#[derive(Default)]
+pub(super) struct Draft {
+    pub title: String,
+    pub markdown_content: String,
+    /// `None` to preselect the default template.
+    pub template_id: Option<String>,
+    pub idempotency_key: Option<String>,
+    pub exclude_from_archive: bool,
+}
+
 pub async fn newsletter_form(
     _user_id: web::ReqData<UserId>,
+    parameters: web::Query<Parameters>,
     flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let mut msg_html = String::new();
-
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
+    let draft = match parameters.0.from {
+        Some(newsletter_issue_id) => get_draft(&pool, newsletter_issue_id)
+            .await
+            .map_err(e500)?
+            .ok_or_else(|| {
+                e404("There is no newsletter issue written in Markdown with this id.")
+            })?,
+        None => Draft::default(),
+    };
+    newsletter_page(&pool, &session, &msg_html, &draft, "").await
+}
+
+/// The form to write an issue, with the preview of `draft` next to it.
+pub(super) async fn newsletter_page(
+    pool: &PgPool,
+    session: &TypedSession,
+    msg_html: &str,
+    draft: &Draft,
+    preview_html: &str,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = csrf_token(session).map_err(e500)?;
+    let idempotency_key = match &draft.idempotency_key {
+        Some(key) => htmlescape::encode_attribute(key),
+        None => Uuid::new_v4().to_string(),
+    };
+    let title = htmlescape::encode_attribute(&draft.title);
+    let markdown_content = htmlescape::encode_minimal(&draft.markdown_content);
+    let exclude_from_archive = if draft.exclude_from_archive {
+        " checked"
+    } else {
+        ""
+    };
+
+    let templates = list_email_templates(pool).await.map_err(e500)?;
+    let mut templates_html = String::from(r#"<option value="">No template</option>"#);
+    for template in templates {
+        let selected = match &draft.template_id {
+            Some(template_id) => *template_id == template.template_id.to_string(),
+            None => template.is_default,
+        };
+        write!(
+            templates_html,
+            r#"<option value="{}"{}>{}</option>"#,
+            template.template_id,
+            if selected { " selected" } else { "" },
+            htmlescape::encode_minimal(&template.name),
+        )
+        .unwrap();
+    }
 
-    let idempotency_key = Uuid::new_v4();
+    let issues = list_issues(pool).await.map_err(e500)?;
+    let mut issues_html = String::new();
+    for issue in issues {
+        writeln!(
+            issues_html,
+            r#"<li><a href="/admin/newsletters/{}">{}</a> ({})</li>"#,
+            issue.newsletter_issue_id,
+            htmlescape::encode_minimal(&issue.title),
+            issue.published_at.format("%Y-%m-%d %H:%M UTC"),
+        )
+        .unwrap();
+    }
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
@@ -27,39 +114,305 @@ pub async fn newsletter_form(
                 </head>
                 <body>
                 {msg_html}
+                <div style="display: flex; gap: 2em;">
                 <form action="/admin/newsletters" method="post">
                     <label>Title:<br>
                         <input
                             type="text"
                             placeholder="Enter the issue title"
                             name="title"
+                            value="{title}"
                         >
                     </label>
                     <br>
-                    <label>Plain text content:<br>
+                    <label>Content, in Markdown:<br>
                         <textarea
-                            placeholder="Enter the content in plain text"
-                            name="text_content"
-                            rows="20"
-                            cols="50"
-                        ></textarea>
+                            placeholder="Enter the content in Markdown"
+                            name="markdown_content"
+                            rows="30"
+                            cols="72"
+                        >{markdown_content}</textarea>
                     </label>
                     <br>
-                    <label>HTML content:<br>
-                        <textarea
-                            placeholder="Enter the content in HTML format"
-                            name="html_content"
-                            rows="20"
-                            cols="50"
-                        ></textarea>
+                    <label>Template:<br>
+                        <select name="template_id">{templates_html}</select>
+                    </label>
+                    <br>
+                    <label>
+                        <input type="checkbox" name="exclude_from_archive"{exclude_from_archive}>
+                        Keep this issue out of the public archive
                     </label>
                     <br>
+                    <p>The HTML and plain text versions of the email are generated from the
+                    Markdown. It can use <code>{{{{ name }}}}</code>, <code>{{{{ email }}}}</code>,
+                    <code>{{{{ unsubscribe_url }}}}</code> and <code>{{{{ preferences_url }}}}</code>:
+                    they are replaced for every subscriber.</p>
                     <input hidden type="text" name="idempotency_key" value="{idempotency_key}" />
+                    <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                    <button type="submit" formaction="/admin/newsletters/preview">Preview</button>
                     <button type="submit">Publish</button>
                 </form>
+                {preview_html}
+                </div>
+                <h2>Published issues</h2>
+                <ul>
+                    {issues_html}
+                </ul>
                 <p><a href="/admin/dashboard">&lt;- Back</a></p>
                 </body>
             </html>
             "#,
         )))
 }
+
+/// A new draft with the content of an issue written in Markdown.
+#[tracing::instrument(skip(pool))]
+async fn get_draft(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<Draft>, anyhow::Error> {
+    let issue = sqlx::query!(
+        r#"
+        SELECT title, markdown_content AS "markdown_content!"
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND markdown_content IS NOT NULL
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the newsletter issue.")?;
+    Ok(issue.map(|issue| Draft {
+        title: issue.title,
+        markdown_content: issue.markdown_content,
+        ..Draft::default()
+    }))
+}
+
+struct IssueSummary {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(skip_all)]
+async fn list_issues(pool: &PgPool) -> Result<Vec<IssueSummary>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        IssueSummary,
+        r#"
+        SELECT newsletter_issue_id, title, published_at
+        FROM newsletter_issues
+        ORDER BY published_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the newsletter issues.")?;
+    Ok(issues)
+}
+
+struct IssueReport {
+    title: String,
+    published_at: DateTime<Utc>,
+    n_recipients: i32,
+    /// Issues written in Markdown can be used as the start of a new one.
+    has_markdown: bool,
+    in_archive: bool,
+    slug: String,
+    n_pending: i64,
+    /// Recipients who opened the issue at least once.
+    n_unique_opens: i64,
+    n_opens: i64,
+}
+
+struct LinkClicks {
+    url: String,
+    /// Recipients who clicked the link at least once.
+    n_unique_clicks: i64,
+    n_clicks: i64,
+}
+
+/// Open rates are a lower bound: the pixel is not loaded by clients
+/// that block remote images.
+pub async fn newsletter_issue_report(
+    newsletter_issue_id: web::Path<Uuid>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let report = get_issue_report(&pool, newsletter_issue_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404("There is no newsletter issue with this id."))?;
+
+    let delivery = if report.n_pending > 0 {
+        format!("In progress, {} emails left to send", report.n_pending)
+    } else {
+        "Completed".to_owned()
+    };
+    let open_rate = if report.n_recipients > 0 {
+        format!(
+            "{:.1}%",
+            100.0 * report.n_unique_opens as f64 / report.n_recipients as f64
+        )
+    } else {
+        "-".to_owned()
+    };
+
+    let links = get_link_clicks(&pool, newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+    let links_html = if links.is_empty() {
+        "<p>No link has been clicked yet.</p>".to_owned()
+    } else {
+        let mut rows = String::new();
+        for link in links {
+            writeln!(
+                rows,
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                htmlescape::encode_minimal(&link.url),
+                link.n_unique_clicks,
+                link.n_clicks,
+            )
+            .unwrap();
+        }
+        format!(
+            "<table>\n<tr><th>Link</th><th>Unique clicks</th><th>Total clicks</th></tr>\n{rows}</table>"
+        )
+    };
+
+    let copy_html = if report.has_markdown {
+        format!(
+            r#"<p><a href="/admin/newsletters?from={newsletter_issue_id}">Start a new issue from this one</a></p>"#
+        )
+    } else {
+        String::new()
+    };
+
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+    let archive_html = if report.in_archive {
+        format!(
+            r#"<p>In the public archive, at <a href="/archive/{slug}">/archive/{slug}</a>.</p>
+                    <form action="/admin/newsletters/{newsletter_issue_id}/archive" method="post">
+                        <input hidden type="text" name="in_archive" value="false">
+                        <input hidden type="text" name="csrf_token" value="{csrf_token}">
+                        <button type="submit">Remove from the archive</button>
+                    </form>"#,
+            slug = report.slug,
+        )
+    } else {
+        format!(
+            r#"<p>Not in the public archive.</p>
+                    <form action="/admin/newsletters/{newsletter_issue_id}/archive" method="post">
+                        <input hidden type="text" name="in_archive" value="true">
+                        <input hidden type="text" name="csrf_token" value="{csrf_token}">
+                        <button type="submit">Add to the archive</button>
+                    </form>"#
+        )
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>{title}</title>
+                </head>
+                <body>
+                    {msg_html}
+                    <h1>{title}</h1>
+                    <table>
+                        <tr><th>Published</th><td>{published_at}</td></tr>
+                        <tr><th>Delivery</th><td>{delivery}</td></tr>
+                        <tr><th>Recipients</th><td>{n_recipients}</td></tr>
+                        <tr><th>Unique opens</th><td>{n_unique_opens}</td></tr>
+                        <tr><th>Open rate</th><td>{open_rate}</td></tr>
+                        <tr><th>Total opens</th><td>{n_opens}</td></tr>
+                    </table>
+                    <h2>Links</h2>
+                    {links_html}
+                    <h2>Archive</h2>
+                    {archive_html}
+                    {copy_html}
+                    <p><a href="/admin/newsletters">&lt;- Back</a></p>
+                </body>
+            </html>
+            "#,
+            title = htmlescape::encode_minimal(&report.title),
+            published_at = report.published_at.format("%Y-%m-%d %H:%M UTC"),
+            n_recipients = report.n_recipients,
+            n_unique_opens = report.n_unique_opens,
+            n_opens = report.n_opens,
+        )))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_issue_report(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<IssueReport>, anyhow::Error> {
+    let report = sqlx::query_as!(
+        IssueReport,
+        r#"
+        SELECT
+            title,
+            published_at,
+            n_recipients,
+            markdown_content IS NOT NULL AS "has_markdown!",
+            in_archive,
+            slug,
+            (
+                SELECT COUNT(*) FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "n_pending!",
+            (
+                SELECT COUNT(*) FROM newsletter_issue_opens o
+                WHERE o.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "n_unique_opens!",
+            (
+                SELECT COALESCE(SUM(n_opens), 0) FROM newsletter_issue_opens o
+                WHERE o.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "n_opens!"
+        FROM newsletter_issues i
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the report of the newsletter issue.")?;
+    Ok(report)
+}
+
+/// The links of an issue that were clicked, most popular first.
+#[tracing::instrument(skip(pool))]
+async fn get_link_clicks(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Vec<LinkClicks>, anyhow::Error> {
+    let links = sqlx::query_as!(
+        LinkClicks,
+        r#"
+        SELECT
+            url,
+            COUNT(*) AS "n_unique_clicks!",
+            SUM(n_clicks) AS "n_clicks!"
+        FROM newsletter_issue_clicks
+        WHERE newsletter_issue_id = $1
+        GROUP BY url
+        ORDER BY 3 DESC, url
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the link clicks of the newsletter issue.")?;
+    Ok(links)
+}
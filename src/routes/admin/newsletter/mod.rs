@@ -1,5 +1,6 @@
 mod get;
 mod post;
 
-pub use get::newsletter_form;
-pub use post::publish_newsletter;
+pub use get::{newsletter_form, newsletter_issue_report};
+pub(crate) use post::{enqueue_delivery_tasks, insert_newsletter_issue};
+pub use post::{preview_newsletter, publish_newsletter, update_archive_status};
@@ -1,12 +1,20 @@
+use super::get::{newsletter_page, Draft};
 use crate::{
     authentication::UserId,
+    email_html::{clipping_warning, prepare_html, PreparedHtml},
+    email_templates::get_email_template,
     idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
-    utils::{e400, e500, see_other},
+    markdown::{render_markdown, MarkdownContent},
+    merge_tags::{preview_content, Layout},
+    session_state::TypedSession,
+    utils::{e400, e404, e500, see_other},
+    webhooks::{enqueue_webhook_event, WebhookEvent},
 };
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
-use sqlx::Executor;
+use sqlx::{Acquire, Executor};
+use std::fmt::Write;
 
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
@@ -14,9 +22,50 @@ use uuid::Uuid;
 #[derive(serde::Deserialize)]
 pub struct FormData {
     title: String,
-    text_content: String,
-    html_content: String,
+    /// When given, both contents are rendered from it.
+    #[serde(default)]
+    markdown_content: String,
+    text_content: Option<String>,
+    html_content: Option<String>,
     idempotency_key: String,
+    /// The id of the email template, empty for none.
+    #[serde(default)]
+    template_id: String,
+    /// Unchecked boxes are not submitted at all.
+    #[serde(default)]
+    exclude_from_archive: Option<String>,
+}
+
+struct IssueContent {
+    html: String,
+    text: String,
+    markdown: Option<String>,
+    /// About the HTML content, which is sanitized and has its CSS inlined.
+    warnings: Vec<String>,
+}
+
+impl FormData {
+    fn content(&self) -> Result<IssueContent, actix_web::Error> {
+        let (html, text, markdown) =
+            if !self.markdown_content.trim().is_empty() {
+                let MarkdownContent { html, text } = render_markdown(&self.markdown_content);
+                (html, text, Some(self.markdown_content.clone()))
+            } else {
+                match (&self.html_content, &self.text_content) {
+                    (Some(html), Some(text)) => (html.clone(), text.clone(), None),
+                    _ => return Err(e400(
+                        "The issue needs Markdown content, or both HTML and plain text content.",
+                    )),
+                }
+            };
+        let PreparedHtml { html, warnings } = prepare_html(&html);
+        Ok(IssueContent {
+            html,
+            text,
+            markdown,
+            warnings,
+        })
+    }
 }
 
 #[tracing::instrument(name = "Publish a newsletter issue", skip_all, fields(user_id = %&*user_id))]
@@ -26,14 +75,31 @@ pub async fn publish_newsletter(
     user_id: web::ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
+    let IssueContent {
+        html: html_content,
+        text: text_content,
+        markdown: markdown_content,
+        mut warnings,
+    } = form.content()?;
     // Destructure the form to please the borrow checker
     let FormData {
         title,
-        text_content,
-        html_content,
         idempotency_key,
+        template_id,
+        exclude_from_archive,
+        ..
     } = form.0;
     let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
+    let layout = get_layout(&template_id, &pool).await?;
+    // A broken placeholder must not be found halfway through the delivery.
+    let preview = match preview_content(&html_content, &text_content, layout.as_ref()) {
+        Ok(preview) => preview,
+        Err(message) => {
+            FlashMessage::error(htmlescape::encode_minimal(&message)).send();
+            return Ok(see_other("/admin/newsletters"));
+        }
+    };
+    warnings.extend(clipping_warning(preview.html.len()));
 
     // Return early if we have a saved response in the Database
     let mut transaction = match try_processing(&pool, &idempotency_key, *user_id)
@@ -43,20 +109,30 @@ pub async fn publish_newsletter(
         NextAction::StartProcessing(t) => t,
         NextAction::ReturnSavedResponse(saved_response) => {
             success_message().send();
+            send_warnings(&warnings);
             return Ok(saved_response);
         }
     };
 
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
-        .await
-        .context("Failed to store newsletter issue details.")
-        .map_err(e500)?;
+    let issue_id = insert_newsletter_issue(
+        &mut transaction,
+        &title,
+        &text_content,
+        &html_content,
+        markdown_content.as_deref(),
+        layout.as_ref(),
+        exclude_from_archive.is_none(),
+    )
+    .await
+    .context("Failed to store newsletter issue details.")
+    .map_err(e500)?;
     enqueue_delivery_tasks(&mut transaction, issue_id)
         .await
         .context("Failed to enqueue delivery tasks.")
         .map_err(e500)?;
 
     success_message().send();
+    send_warnings(&warnings);
     let response = see_other("/admin/newsletters");
     let response = save_response(transaction, &idempotency_key, *user_id, response)
         .await
@@ -69,39 +145,227 @@ fn success_message() -> FlashMessage {
     FlashMessage::success("The newsletter issue has been accepted - emails will go out shortly.")
 }
 
+/// The issue is published all the same.
+fn send_warnings(warnings: &[String]) {
+    for warning in warnings {
+        FlashMessage::warning(htmlescape::encode_minimal(warning)).send();
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct ArchiveFormData {
+    in_archive: bool,
+}
+
+#[tracing::instrument(name = "Update the archive status of an issue", skip(form, pool))]
+pub async fn update_archive_status(
+    newsletter_issue_id: web::Path<Uuid>,
+    form: web::Form<ArchiveFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let in_archive = form.0.in_archive;
+    let result = sqlx::query!(
+        "UPDATE newsletter_issues SET in_archive = $1 WHERE newsletter_issue_id = $2",
+        in_archive,
+        newsletter_issue_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to update the archive status of the newsletter issue.")
+    .map_err(e500)?;
+    if result.rows_affected() == 0 {
+        return Err(e404("There is no newsletter issue with this id."));
+    }
+    if in_archive {
+        FlashMessage::info("The issue is now in the public archive.").send();
+    } else {
+        FlashMessage::info("The issue has been removed from the public archive.").send();
+    }
+    Ok(see_other(&format!(
+        "/admin/newsletters/{newsletter_issue_id}"
+    )))
+}
+
+/// The layout of the template with this id, empty for none.
+async fn get_layout(template_id: &str, pool: &PgPool) -> Result<Option<Layout>, actix_web::Error> {
+    if template_id.is_empty() {
+        return Ok(None);
+    }
+    let template_id = Uuid::parse_str(template_id).map_err(e400)?;
+    let template = get_email_template(template_id, pool)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e400("There is no email template with this id."))?;
+    Ok(Some(template.layout()))
+}
+
+#[derive(serde::Deserialize)]
+pub struct PreviewFormData {
+    title: String,
+    markdown_content: String,
+    idempotency_key: String,
+    #[serde(default)]
+    template_id: String,
+    #[serde(default)]
+    exclude_from_archive: Option<String>,
+}
+
+/// Show the issue as subscribers will receive it, next to the form.
+/// Nothing is stored: the draft goes back into the form.
+#[tracing::instrument(name = "Preview a newsletter issue", skip_all)]
+pub async fn preview_newsletter(
+    form: web::Form<PreviewFormData>,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let PreviewFormData {
+        title,
+        markdown_content,
+        idempotency_key,
+        template_id,
+        exclude_from_archive,
+    } = form.0;
+    let layout = get_layout(&template_id, &pool).await?;
+    let MarkdownContent { html, text } = render_markdown(&markdown_content);
+    let PreparedHtml { html, mut warnings } = prepare_html(&html);
+    let preview_html = match preview_content(&html, &text, layout.as_ref()) {
+        Ok(preview) => {
+            warnings.extend(clipping_warning(preview.html.len()));
+            let mut warnings_html = String::new();
+            for warning in warnings {
+                writeln!(
+                    warnings_html,
+                    "<p><i>{}</i></p>",
+                    htmlescape::encode_minimal(&warning)
+                )
+                .unwrap();
+            }
+            format!(
+                r#"<div>
+                    <h2>Preview</h2>
+                    {warnings_html}
+                    <p>Subject: {title}</p>
+                    <iframe sandbox title="HTML version" srcdoc="{html}" width="600" height="400"></iframe>
+                    <pre>{text}</pre>
+                </div>"#,
+                title = htmlescape::encode_minimal(&title),
+                html = htmlescape::encode_attribute(&preview.html),
+                text = htmlescape::encode_minimal(&preview.text),
+            )
+        }
+        Err(message) => format!(
+            "<div><h2>Preview</h2><p><i>{}</i></p></div>",
+            htmlescape::encode_minimal(&message)
+        ),
+    };
+    let draft = Draft {
+        title,
+        markdown_content,
+        template_id: Some(template_id),
+        idempotency_key: Some(idempotency_key),
+        exclude_from_archive: exclude_from_archive.is_some(),
+    };
+    newsletter_page(&pool, &session, "", &draft, &preview_html).await
+}
+
 #[tracing::instrument(skip_all)]
-async fn insert_newsletter_issue(
+pub(crate) async fn insert_newsletter_issue(
     transaction: &mut Transaction<'_, Postgres>,
     title: &str,
     text_content: &str,
     html_content: &str,
+    markdown_content: Option<&str>,
+    layout: Option<&Layout>,
+    in_archive: bool,
 ) -> Result<Uuid, sqlx::Error> {
     let newsletter_issue_id = Uuid::new_v4();
-    let query = sqlx::query!(
-        r#"
-        INSERT INTO newsletter_issues (
+    // Another issue with the same title can take the slug between the time
+    // we pick it and the insert: pick again, in a savepoint since the failed
+    // insert aborts the transaction.
+    const MAX_ATTEMPTS: usize = 5;
+    for attempt in 1.. {
+        let slug = unique_slug(transaction, title).await?;
+        let mut savepoint = (&mut **transaction).begin().await?;
+        let query = sqlx::query!(
+            r#"
+            INSERT INTO newsletter_issues (
+                newsletter_issue_id,
+                title,
+                text_content,
+                html_content,
+                markdown_content,
+                html_layout,
+                text_layout,
+                in_archive,
+                slug,
+                published_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW())
+            "#,
             newsletter_issue_id,
             title,
             text_content,
             html_content,
-            published_at
-        )
-        VALUES ($1, $2, $3, $4, NOW())
-        "#,
-        newsletter_issue_id,
-        title,
-        text_content,
-        html_content,
-    );
-    transaction.execute(query).await?;
+            markdown_content,
+            layout.map(|l| l.html.as_str()),
+            layout.map(|l| l.text.as_str()),
+            in_archive,
+            slug,
+        );
+        match savepoint.execute(query).await {
+            Ok(_) => {
+                savepoint.commit().await?;
+                break;
+            }
+            Err(sqlx::Error::Database(e))
+                if e.constraint() == Some("newsletter_issues_slug_key")
+                    && attempt < MAX_ATTEMPTS =>
+            {
+                savepoint.rollback().await?;
+            }
+            Err(e) => return Err(e),
+        }
+    }
     Ok(newsletter_issue_id)
 }
 
+/// The slug of the issue in the archive, derived from its title.
+/// Issues with the same title get a number: `title`, `title-2`...
+async fn unique_slug(
+    transaction: &mut Transaction<'_, Postgres>,
+    title: &str,
+) -> Result<String, sqlx::Error> {
+    const MAX_LENGTH: usize = 80;
+    let mut base = slug::slugify(title);
+    // Slugs are ASCII: any byte is a char boundary.
+    base.truncate(MAX_LENGTH);
+    let base = match base.trim_end_matches('-') {
+        "" => "issue",
+        base => base,
+    };
+    // Only lowercase letters, digits and `-` are left: nothing to escape.
+    let taken: Vec<String> = sqlx::query_scalar!(
+        r#"SELECT slug FROM newsletter_issues WHERE slug = $1 OR slug LIKE $1 || '-%'"#,
+        base
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    let slug = std::iter::once(base.to_owned())
+        .chain((2..).map(|n| format!("{base}-{n}")))
+        .find(|slug| !taken.contains(slug))
+        .expect("There are finitely many taken slugs");
+    Ok(slug)
+}
+
+/// Queue the issue for every confirmed subscriber and record how many they are.
 #[tracing::instrument(skip_all)]
-async fn enqueue_delivery_tasks(
+pub(crate) async fn enqueue_delivery_tasks(
     transaction: &mut Transaction<'_, Postgres>,
     newsletter_issue_id: Uuid,
 ) -> Result<(), sqlx::Error> {
+    // Suppressed subscribers, who bounced or complained, are left out
+    // along with those who never confirmed.
     let query = sqlx::query!(
         r#"
         INSERT INTO issue_delivery_queue (
@@ -114,179 +378,37 @@ async fn enqueue_delivery_tasks(
         "#,
         newsletter_issue_id
     );
-    transaction.execute(query).await?;
-
-    Ok(())
-}
-
-// ! BELLOW IS AN IMPLEMENTATION WITH BASIC AUTHENTICATION
-// ! IS ONLY FOR DEMONSTRATION PURPOSES
-// ! IT IS NOT CURRENTLY BEING USED
-
-// #[derive(serde::Deserialize)]
-// pub struct BodyData {
-//     title: String,
-//     content: Content,
-// }
-
-// #[derive(serde::Deserialize)]
-// pub struct Content {
-//     text: String,
-//     html: String,
-// }
+    let n_recipients = transaction.execute(query).await?.rows_affected();
 
-// #[tracing::instrument(name = "Publish a newsletter issue", skip(body,pool,email_client, request), fields(username=tracing::field::Empty, user_id=tracing::field::Empty))]
-// pub async fn publish_newsletter(
-//     body: web::Json<BodyData>,
-//     pool: web::Data<PgPool>,
-//     email_client: web::Data<EmailClient>,
-//     request: HttpRequest,
-// ) -> Result<HttpResponse, PublishError> {
-//     let credentials = basic_authentication(request.headers()).map_err(PublishError::AuthError)?;
-//     tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    let n_recipients = n_recipients as i32;
 
-//     let user_id = validate_credentials(credentials, &pool)
-//         .await
-//         // We match on `AuthError`'s variants, but we pass the **whole** error
-//         // into the constructors for `PublishError` variants. This ensures that
-//         // the context of the top-level wrapper is preserved when the error is
-//         // logged by our middleware.
-//         .map_err(|e| match e {
-//             AuthError::InvalidCredentials(_) => PublishError::AuthError(e.into()),
-//             AuthError::UnexpectedError(_) => PublishError::UnexpectedError(e.into()),
-//         })?;
-//     tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
-
-//     let subscribers = get_confirmed_subscriber(&pool).await?;
-//     for subscriber in subscribers {
-//         match subscriber {
-//             Ok(subscriber) => {
-//                 email_client
-//                     .send_email(
-//                         &subscriber.email,
-//                         &body.title,
-//                         &body.content.html,
-//                         &body.content.text,
-//                     )
-//                     .await
-//                     .with_context(|| {
-//                         format!("Failed to send newsletter issue to {}", subscriber.email)
-//                     })?;
-//             }
-//             Err(error) => {
-//                 tracing::warn!(
-//                     // Record the error chain as a structured field
-//                     // on the log record.
-//                     error.cause_chain = ?error,
-//                     // Using `\` to split a long string literal over
-//                     // two lines, without creating a `\n` character.
-//                     "Skipping a confirmed subscriber.\
-//                     Their stored contact details are invalid."
-//                 );
-//             }
-//         }
-//     }
-//     Ok(HttpResponse::Ok().finish())
-// }
-
-// struct ConfirmedSubscriber {
-//     email: SubscriberEmail,
-// }
-
-// #[tracing::instrument(name = "Get confirmed subscriber", skip(pool))]
-// async fn get_confirmed_subscriber(
-//     pool: &PgPool,
-// ) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
-//     let rows = sqlx::query!(
-//         r#"
-//         SELECT email
-//         FROM subscriptions
-//         WHERE status = 'confirmed'
-//         "#,
-//     )
-//     .fetch_all(pool)
-//     .await?;
-
-//     // Map into the domain type
-//     let confirmed_subscribers = rows
-//         .into_iter()
-//         .map(|r| match SubscriberEmail::parse(r.email) {
-//             Ok(email) => Ok(ConfirmedSubscriber { email }),
-//             Err(error) => Err(anyhow::anyhow!(error)),
-//         })
-//         .collect();
-
-//     Ok(confirmed_subscribers)
-// }
-
-// #[derive(thiserror::Error)]
-// pub enum PublishError {
-//     #[error("Authentication Failed")]
-//     AuthError(#[source] anyhow::Error),
-//     #[error(transparent)]
-//     UnexpectedError(#[from] anyhow::Error),
-// }
-
-// impl std::fmt::Debug for PublishError {
-//     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-//         error_chain_fmt(self, f)
-//     }
-// }
-
-// impl ResponseError for PublishError {
-//     fn error_response(&self) -> HttpResponse {
-//         match self {
-//             PublishError::UnexpectedError(_) => {
-//                 HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
-//             }
-//             PublishError::AuthError(_) => {
-//                 let mut response = HttpResponse::new(StatusCode::UNAUTHORIZED);
-//                 let header_value = HeaderValue::from_str(r#"Basic realm="publish""#).unwrap();
-//                 response
-//                     .headers_mut()
-//                     // actix_web::http::header provides a collection of constants
-//                     // for the names of several well-known/standard HTTP headers
-//                     .insert(header::WWW_AUTHENTICATE, header_value);
-
-//                 response
-//             }
-//         }
-//     }
-
-//     // `status_code` is invoked by the default `error_response`
-//     // implementation. We are providing a bespoke `error_response` implementation
-//     // therefore there is no need to maintain a `status_code` implementation.
-// }
-
-// fn basic_authentication(headers: &HeaderMap) -> Result<Credentials, anyhow::Error> {
-//     // The header value, if present, must be a valid UTF8 string
-//     let header_value = headers
-//         .get("Authorization")
-//         .context("The 'Authorization' header was missing.")?
-//         .to_str()
-//         .context("The 'Authorization' header was not a valid UTF8 string.")?;
-//     let base4encoded_segment = header_value
-//         .strip_prefix("Basic ")
-//         .context("The authorization scheme was not 'Basic'.")?;
-//     let decode_bytes = base64::engine::general_purpose::STANDARD
-//         .decode(base4encoded_segment)
-//         .context("Failed to base64-decode 'Basic' crededntials.")?;
-//     let decoded_credentials = String::from_utf8(decode_bytes)
-//         .context("The decoded credential string is not valid UTF8.")?;
-
-//     // Split into 2 segments, using ":" as delimiter
-//     let mut credentials = decoded_credentials.splitn(2, ':');
-//     let username = credentials
-//         .next()
-//         .ok_or_else(|| anyhow::anyhow!("A username must be provided in the 'Basic' auth."))?
-//         .to_string();
-//     let password = credentials
-//         .next()
-//         .ok_or_else(|| anyhow::anyhow!("A password must be provided in the 'Basic' auth."))?
-//         .to_string();
+    // With nobody to send it to, the issue is delivered right away:
+    // no worker will ever pick it up.
+    let issue = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET
+            n_recipients = $2,
+            delivered_at = CASE WHEN $2 = 0 THEN now() END
+        WHERE newsletter_issue_id = $1
+        RETURNING title
+        "#,
+        newsletter_issue_id,
+        n_recipients
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    if n_recipients == 0 {
+        enqueue_webhook_event(
+            transaction,
+            &WebhookEvent::NewsletterIssueDelivered {
+                newsletter_issue_id,
+                title: issue.title,
+                recipients: 0,
+            },
+        )
+        .await?;
+    }
 
-//     Ok(Credentials {
-//         username,
-//         password: Secret::new(password),
-//     })
-// }
+    Ok(())
+}
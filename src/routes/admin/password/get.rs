@@ -2,12 +2,18 @@ use actix_web::{http::header::ContentType, web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
 use std::fmt::Write;
 
-use crate::authentication::UserId;
+use crate::{
+    authentication::{csrf_token, UserId},
+    session_state::TypedSession,
+    utils::e500,
+};
 
 pub async fn change_password_form(
     flash_messages: IncomingFlashMessages,
     _user_id: web::ReqData<UserId>,
+    session: TypedSession,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = csrf_token(&session).map_err(e500)?;
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
@@ -50,6 +56,7 @@ pub async fn change_password_form(
                     >
                     </label>
                     <br>
+                    <input hidden type="text" name="csrf_token" value="{csrf_token}" />
                     <button type="submit">Change password</button>
                 </form>
                 <p><a href="/admin/dashboard">&lt;- Back</a></p>
@@ -4,7 +4,10 @@ use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 
 use crate::{
-    authentication::{self, validate_credentials, AuthError, Credentials, UserId},
+    authentication::{
+        self, validate_credentials, AuthError, Credentials, PasswordHashingPolicy, PasswordPolicy,
+        UserId,
+    },
     routes::admin::dashboard::get_username,
     utils::{e500, see_other},
 };
@@ -19,6 +22,8 @@ pub struct FormData {
 pub async fn change_password(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
+    password_hashing_policy: web::Data<PasswordHashingPolicy>,
+    password_policy: web::Data<PasswordPolicy>,
     user_id: web::ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
@@ -34,26 +39,15 @@ pub async fn change_password(
         return Ok(see_other("/admin/password"));
     }
 
-    // Check password size
-    if form.new_password.expose_secret().len() < 8 {
-        FlashMessage::error("The new password is too short. It should have 8 or more characters.")
-            .send();
-        return Ok(see_other("/admin/password"));
-    } else if form.new_password.expose_secret().len() > 129 {
-        FlashMessage::error(
-            "The new password is too long. It should have 129 or fewer characters.",
-        )
-        .send();
-        return Ok(see_other("/admin/password"));
-    }
-
     let username = get_username(*user_id, &pool).await.map_err(e500)?;
+    // The policy is only applied for users who know the current password:
+    // it would otherwise tell a hijacked session whether a guess is right.
     let credentials = Credentials {
-        username,
-        password: form.0.current_password,
+        username: username.clone(),
+        password: form.current_password.clone(),
     };
 
-    if let Err(e) = validate_credentials(credentials, &pool).await {
+    if let Err(e) = validate_credentials(credentials, &password_hashing_policy, &pool).await {
         return match e {
             AuthError::InvalidCredentials(_) => {
                 FlashMessage::error("The current password is incorrect.").send();
@@ -63,9 +57,21 @@ pub async fn change_password(
         };
     }
 
-    authentication::change_password(*user_id, form.0.new_password, &pool)
-        .await
-        .map_err(e500)?;
+    if let Err(violation) =
+        password_policy.check(&form.new_password, &username, Some(&form.current_password))
+    {
+        FlashMessage::error(violation.to_string()).send();
+        return Ok(see_other("/admin/password"));
+    }
+
+    authentication::change_password(
+        *user_id,
+        form.0.new_password,
+        &password_hashing_policy,
+        pool.get_ref(),
+    )
+    .await
+    .map_err(e500)?;
     FlashMessage::error("Your password has been changed.").send();
     Ok(see_other("/admin/password"))
 }
@@ -0,0 +1,86 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use std::fmt::Write;
+
+use crate::{
+    authentication::{csrf_token, SessionId, UserId},
+    session_registry::SessionRegistry,
+    session_state::TypedSession,
+    utils::e500,
+};
+
+pub async fn sessions_list(
+    flash_messages: IncomingFlashMessages,
+    session_registry: web::Data<SessionRegistry>,
+    user_id: web::ReqData<UserId>,
+    session_id: web::ReqData<SessionId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let sessions = session_registry
+        .list(*user_id.into_inner())
+        .await
+        .map_err(e500)?;
+    let current_session_id = *session_id.into_inner();
+    let mut rows_html = String::new();
+    for session in sessions {
+        let action = if session.session_id == current_session_id {
+            "<b>This session</b>".to_string()
+        } else {
+            format!(
+                r#"<form action="/admin/sessions/revoke" method="post">
+                    <input hidden type="text" name="session_id" value="{}">
+                    <input hidden type="text" name="csrf_token" value="{}" />
+                    <button type="submit">Revoke</button>
+                </form>"#,
+                session.session_id, csrf_token
+            )
+        };
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            session.metadata.created_at.format("%Y-%m-%d %H:%M UTC"),
+            session.metadata.last_seen_at.format("%Y-%m-%d %H:%M UTC"),
+            htmlescape::encode_minimal(&session.metadata.ip),
+            htmlescape::encode_minimal(&session.metadata.user_agent),
+            action,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Active sessions</title>
+                </head>
+                <body>
+                    {msg_html}
+                    <table>
+                        <tr>
+                            <th>Logged in</th>
+                            <th>Last seen</th>
+                            <th>IP address</th>
+                            <th>Browser</th>
+                            <th></th>
+                        </tr>
+                        {rows_html}
+                    </table>
+                    <form action="/admin/sessions/revoke-others" method="post">
+                        <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                        <button type="submit">Log out all other sessions</button>
+                    </form>
+                    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+                </body>
+            </html>
+            "#,
+        )))
+}
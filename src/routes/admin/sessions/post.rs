@@ -0,0 +1,44 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{SessionId, UserId},
+    session_registry::SessionRegistry,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    session_id: Uuid,
+}
+
+#[tracing::instrument(name = "Revoke a session", skip(form, session_registry))]
+pub async fn revoke_session(
+    form: web::Form<FormData>,
+    session_registry: web::Data<SessionRegistry>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // Sessions are indexed by user: the id of somebody else's session
+    // would not match anything.
+    session_registry
+        .revoke(*user_id.into_inner(), form.0.session_id)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("The session has been revoked.").send();
+    Ok(see_other("/admin/sessions"))
+}
+
+#[tracing::instrument(name = "Revoke other sessions", skip(session_registry))]
+pub async fn revoke_other_sessions(
+    session_registry: web::Data<SessionRegistry>,
+    user_id: web::ReqData<UserId>,
+    session_id: web::ReqData<SessionId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    session_registry
+        .revoke_all_except(*user_id.into_inner(), Some(*session_id.into_inner()))
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("All other sessions have been revoked.").send();
+    Ok(see_other("/admin/sessions"))
+}
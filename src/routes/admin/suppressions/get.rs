@@ -0,0 +1,95 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    authentication::csrf_token, session_state::TypedSession,
+    suppression_list::list_suppressed_emails, utils::e500,
+};
+
+pub async fn suppressions_list(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let suppressed_emails = list_suppressed_emails(&pool).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for suppressed in suppressed_emails {
+        let email = htmlescape::encode_minimal(&suppressed.email);
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{email}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>
+                    <form action="/admin/suppressions/delete" method="post">
+                        <input hidden type="text" name="email" value="{email}">
+                        <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                        <button type="submit">Remove</button>
+                    </form>
+                </td>
+            </tr>"#,
+            htmlescape::encode_minimal(suppressed.reason.as_deref().unwrap_or_default()),
+            suppressed.created_at.format("%Y-%m-%d %H:%M UTC"),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Suppression list</title>
+                </head>
+                <body>
+                    {msg_html}
+                    <p>These addresses are never sent any email, whether they are subscribed or not.</p>
+                    <table>
+                        <tr>
+                            <th>Email</th>
+                            <th>Reason</th>
+                            <th>Added</th>
+                            <th></th>
+                        </tr>
+                        {rows_html}
+                    </table>
+                    <h2>Add an address</h2>
+                    <form action="/admin/suppressions" method="post">
+                        <label>Email
+                            <input type="text" placeholder="Enter an email address" name="email">
+                        </label>
+                        <label>Reason
+                            <input type="text" placeholder="e.g. Legal request" name="reason">
+                        </label>
+                        <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                        <button type="submit">Suppress</button>
+                    </form>
+                    <h2>Import addresses</h2>
+                    <form action="/admin/suppressions/import" method="post">
+                        <label>Emails, one per line
+                            <textarea name="emails" rows="10" cols="50"></textarea>
+                        </label>
+                        <br>
+                        <label>Reason
+                            <input type="text" placeholder="e.g. Role addresses" name="reason">
+                        </label>
+                        <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                        <button type="submit">Import</button>
+                    </form>
+                    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+                </body>
+            </html>
+            "#,
+        )))
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::suppressions_list;
+pub use post::{add_suppressed_email, import_suppressed_emails, remove_suppressed_email};
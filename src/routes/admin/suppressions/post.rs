@@ -0,0 +1,93 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use crate::{
+    domain::SubscriberEmail,
+    suppression_list::{parse_email_list, suppress_emails, unsuppress_email},
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    email: String,
+    reason: String,
+}
+
+/// An empty reason is stored as no reason at all.
+fn reason(reason: &str) -> Option<&str> {
+    Some(reason.trim()).filter(|reason| !reason.is_empty())
+}
+
+#[tracing::instrument(name = "Add an email to the suppression list", skip(form, pool))]
+pub async fn add_suppressed_email(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // Flash messages are rendered as HTML and quote the input.
+    let escaped_email = htmlescape::encode_minimal(form.email.trim());
+    let Ok(email) = SubscriberEmail::parse(form.email.trim().to_owned()) else {
+        FlashMessage::error(format!("{escaped_email} is not a valid email address.")).send();
+        return Ok(see_other("/admin/suppressions"));
+    };
+    let n_added = suppress_emails(&[email], reason(&form.reason), &pool)
+        .await
+        .map_err(e500)?;
+    if n_added == 0 {
+        FlashMessage::info(format!("{escaped_email} was already suppressed.")).send();
+    } else {
+        FlashMessage::info(format!("{escaped_email} will not be sent any email.")).send();
+    }
+    Ok(see_other("/admin/suppressions"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ImportFormData {
+    emails: String,
+    reason: String,
+}
+
+/// Invalid entries are reported, but do not prevent the others from being imported.
+#[tracing::instrument(name = "Import emails into the suppression list", skip(form, pool))]
+pub async fn import_suppressed_emails(
+    form: web::Form<ImportFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (emails, invalid) = parse_email_list(&form.emails);
+    if emails.is_empty() && invalid.is_empty() {
+        FlashMessage::error("There were no addresses to import.").send();
+        return Ok(see_other("/admin/suppressions"));
+    }
+
+    let n_added = suppress_emails(&emails, reason(&form.reason), &pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info(format!("{} new addresses suppressed.", n_added)).send();
+    if !invalid.is_empty() {
+        FlashMessage::error(format!(
+            "These entries are not valid email addresses: {}.",
+            htmlescape::encode_minimal(&invalid.join(", "))
+        ))
+        .send();
+    }
+    Ok(see_other("/admin/suppressions"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RemoveFormData {
+    email: String,
+}
+
+#[tracing::instrument(name = "Remove an email from the suppression list", skip(form, pool))]
+pub async fn remove_suppressed_email(
+    form: web::Form<RemoveFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let escaped_email = htmlescape::encode_minimal(&form.email);
+    if unsuppress_email(&form.email, &pool).await.map_err(e500)? {
+        FlashMessage::info(format!("{escaped_email} can be sent emails again.")).send();
+    } else {
+        FlashMessage::error(format!("{escaped_email} was not suppressed.")).send();
+    }
+    Ok(see_other("/admin/suppressions"))
+}
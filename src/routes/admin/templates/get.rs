@@ -0,0 +1,167 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{
+    authentication::csrf_token,
+    email_templates::{get_email_template, list_email_templates, EmailTemplate},
+    session_state::TypedSession,
+    utils::{e404, e500},
+};
+
+pub async fn email_templates_list(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let templates = list_email_templates(&pool).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for template in templates {
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td><a href="/admin/templates/{}">{}</a></td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>
+                    <form action="/admin/templates/delete" method="post">
+                        <input hidden type="text" name="template_id" value="{}">
+                        <input hidden type="text" name="csrf_token" value="{}" />
+                        <button type="submit">Delete</button>
+                    </form>
+                </td>
+            </tr>"#,
+            template.template_id,
+            htmlescape::encode_minimal(&template.name),
+            if template.is_default { "Default" } else { "" },
+            template.updated_at.format("%Y-%m-%d %H:%M UTC"),
+            template.template_id,
+            csrf_token,
+        )
+        .unwrap();
+    }
+    let form_html = template_form_html("/admin/templates", None, &csrf_token, "Create template");
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Email templates</title>
+                </head>
+                <body>
+                    {msg_html}
+                    <table>
+                        <tr>
+                            <th>Name</th>
+                            <th></th>
+                            <th>Last updated</th>
+                            <th></th>
+                        </tr>
+                        {rows_html}
+                    </table>
+                    <h2>New template</h2>
+                    {form_html}
+                    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+                </body>
+            </html>
+            "#,
+        )))
+}
+
+pub async fn email_template_form(
+    template_id: web::Path<Uuid>,
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let template = get_email_template(template_id.into_inner(), &pool)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404("There is no email template with this id."))?;
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let form_html = template_form_html(
+        &format!("/admin/templates/{}", template.template_id),
+        Some(&template),
+        &csrf_token,
+        "Save",
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>{name}</title>
+                </head>
+                <body>
+                    {msg_html}
+                    <h1>{name}</h1>
+                    {form_html}
+                    <p><a href="/admin/templates">&lt;- Back</a></p>
+                </body>
+            </html>
+            "#,
+            name = htmlescape::encode_minimal(&template.name),
+        )))
+}
+
+/// The form to create a template, or to edit `template`.
+fn template_form_html(
+    action: &str,
+    template: Option<&EmailTemplate>,
+    csrf_token: &str,
+    submit_label: &str,
+) -> String {
+    let (name, html_layout, text_layout, is_default) = match template {
+        Some(t) => (
+            htmlescape::encode_attribute(&t.name),
+            htmlescape::encode_minimal(&t.html_layout),
+            htmlescape::encode_minimal(&t.text_layout),
+            t.is_default,
+        ),
+        None => Default::default(),
+    };
+    let checked = if is_default { " checked" } else { "" };
+    format!(
+        r#"<form action="{action}" method="post">
+            <label>Name:<br>
+                <input type="text" name="name" value="{name}">
+            </label>
+            <br>
+            <label>HTML layout:<br>
+                <textarea name="html_layout" rows="20" cols="50">{html_layout}</textarea>
+            </label>
+            <br>
+            <label>Plain text layout:<br>
+                <textarea name="text_layout" rows="20" cols="50">{text_layout}</textarea>
+            </label>
+            <br>
+            <label>
+                <input type="checkbox" name="is_default" value="on"{checked}>
+                Default template, also used for confirmation emails
+            </label>
+            <p>Layouts must include <code>{{{{ content }}}}</code>, where the body of the email
+            goes. They can use the same merge tags as issues, e.g.
+            <code>{{{{ unsubscribe_url }}}}</code>.</p>
+            <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+            <button type="submit">{submit_label}</button>
+        </form>"#
+    )
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::{email_template_form, email_templates_list};
+pub use post::{create_email_template, delete_email_template, update_email_template};
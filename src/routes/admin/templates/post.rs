@@ -0,0 +1,119 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    email_templates::{self, get_email_template, save_email_template},
+    merge_tags::{validate_layout, Layout},
+    utils::{e404, e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    name: String,
+    html_layout: String,
+    text_layout: String,
+    /// Unchecked boxes are not submitted at all.
+    #[serde(default)]
+    is_default: Option<String>,
+}
+
+impl FormData {
+    /// The message to show when the form cannot be saved as is.
+    fn validate(&self) -> Result<Layout, String> {
+        if self.name.trim().is_empty() {
+            return Err("The template needs a name.".into());
+        }
+        let layout = Layout {
+            html: self.html_layout.clone(),
+            text: self.text_layout.clone(),
+        };
+        validate_layout(&layout)?;
+        Ok(layout)
+    }
+}
+
+#[tracing::instrument(name = "Create an email template", skip(form, pool))]
+pub async fn create_email_template(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let layout = match form.validate() {
+        Ok(layout) => layout,
+        Err(e) => {
+            FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+            return Ok(see_other("/admin/templates"));
+        }
+    };
+    let name = form.name.trim();
+    save_email_template(
+        Uuid::new_v4(),
+        name,
+        &layout,
+        form.is_default.is_some(),
+        &pool,
+    )
+    .await
+    .map_err(e500)?;
+    FlashMessage::info(format!(
+        "The template {} has been created.",
+        htmlescape::encode_minimal(name)
+    ))
+    .send();
+    Ok(see_other("/admin/templates"))
+}
+
+#[tracing::instrument(name = "Update an email template", skip(form, pool))]
+pub async fn update_email_template(
+    template_id: web::Path<Uuid>,
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let template_id = template_id.into_inner();
+    let form_location = format!("/admin/templates/{template_id}");
+    if get_email_template(template_id, &pool)
+        .await
+        .map_err(e500)?
+        .is_none()
+    {
+        return Err(e404("There is no email template with this id."));
+    }
+    let layout = match form.validate() {
+        Ok(layout) => layout,
+        Err(e) => {
+            FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+            return Ok(see_other(&form_location));
+        }
+    };
+    save_email_template(
+        template_id,
+        form.name.trim(),
+        &layout,
+        form.is_default.is_some(),
+        &pool,
+    )
+    .await
+    .map_err(e500)?;
+    FlashMessage::info("Your changes have been saved.").send();
+    Ok(see_other(&form_location))
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteFormData {
+    template_id: Uuid,
+}
+
+#[tracing::instrument(name = "Delete an email template", skip(form, pool))]
+pub async fn delete_email_template(
+    form: web::Form<DeleteFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if email_templates::delete_email_template(form.0.template_id, &pool)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info("The template has been deleted.").send();
+    }
+    Ok(see_other("/admin/templates"))
+}
@@ -0,0 +1,107 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use qrcode::{render::svg, QrCode};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    authentication::{
+        csrf_token, generate_totp_secret, get_totp_settings, provisioning_uri,
+        store_pending_totp_secret, UserId,
+    },
+    routes::admin::dashboard::get_username,
+    session_state::TypedSession,
+    utils::e500,
+};
+use secrecy::ExposeSecret;
+
+pub async fn two_factor_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let settings = get_totp_settings(*user_id, &pool).await.map_err(e500)?;
+    let body = if settings.enabled {
+        format!(
+            r#"<p>Two-factor authentication is <b>enabled</b>.</p>
+        <form action="/admin/two-factor/disable" method="post">
+            <label>Authentication code
+                <input
+                    type="text"
+                    placeholder="Enter a code or a recovery code"
+                    name="code"
+                >
+            </label>
+            <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+            <button type="submit">Disable two-factor authentication</button>
+        </form>"#
+        )
+    } else {
+        // Keep showing the same secret until the enrolment is confirmed,
+        // otherwise a mistyped code would invalidate the QR code just scanned.
+        let secret = match settings.secret {
+            Some(secret) => secret,
+            None => {
+                let secret = generate_totp_secret();
+                store_pending_totp_secret(*user_id, &secret, &pool)
+                    .await
+                    .map_err(e500)?;
+                secret
+            }
+        };
+        let username = get_username(*user_id, &pool).await.map_err(e500)?;
+        let uri = provisioning_uri(&secret, &username);
+        let qr_code = QrCode::new(uri.as_bytes())
+            .map_err(e500)?
+            .render::<svg::Color>()
+            .min_dimensions(200, 200)
+            .build();
+        format!(
+            r#"<p>Two-factor authentication is <b>disabled</b>.</p>
+            <p>Scan this QR code with your authenticator app:</p>
+            {qr_code}
+            <p>Or enter the secret manually: <code>{secret}</code></p>
+            <p><small>{uri}</small></p>
+            <form action="/admin/two-factor/enable" method="post">
+                <label>Authentication code
+                    <input
+                        type="text"
+                        inputmode="numeric"
+                        placeholder="Enter the code shown in your app"
+                        name="code"
+                    >
+                </label>
+                <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                <button type="submit">Enable two-factor authentication</button>
+            </form>"#,
+            secret = secret.expose_secret(),
+            uri = htmlescape::encode_minimal(&uri),
+        )
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Two-factor authentication</title>
+                </head>
+                <body>
+                    {msg_html}
+                    {body}
+                    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+                </body>
+            </html>
+            "#,
+        )))
+}
@@ -0,0 +1,91 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    authentication::{
+        disable_totp, enable_totp, generate_recovery_codes, use_recovery_code, verify_totp, UserId,
+    },
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+#[tracing::instrument(name = "Enable two-factor authentication", skip_all, fields(user_id = %&*user_id))]
+pub async fn enable_two_factor(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+
+    if !verify_totp(*user_id, &form.code, &pool)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("Invalid authentication code.").send();
+        return Ok(see_other("/admin/two-factor"));
+    }
+
+    let recovery_codes = generate_recovery_codes();
+    enable_totp(*user_id, &recovery_codes, &pool)
+        .await
+        .map_err(e500)?;
+
+    // Recovery codes are stored hashed: this is the only time they are shown.
+    let mut codes_html = String::new();
+    for code in &recovery_codes {
+        writeln!(codes_html, "<li><code>{code}</code></li>").unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Two-factor authentication</title>
+                </head>
+                <body>
+                    <p>Two-factor authentication has been enabled.</p>
+                    <p>Store these recovery codes somewhere safe.
+                    Each of them can be used once if you lose access to your authenticator app.
+                    They will not be shown again.</p>
+                    <ul>
+                    {codes_html}
+                    </ul>
+                    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+                </body>
+            </html>
+            "#,
+        )))
+}
+
+#[tracing::instrument(name = "Disable two-factor authentication", skip_all, fields(user_id = %&*user_id))]
+pub async fn disable_two_factor(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+
+    let is_valid = verify_totp(*user_id, &form.code, &pool)
+        .await
+        .map_err(e500)?
+        || use_recovery_code(*user_id, &form.code, &pool)
+            .await
+            .map_err(e500)?;
+    if !is_valid {
+        FlashMessage::error("Invalid authentication code.").send();
+        return Ok(see_other("/admin/two-factor"));
+    }
+
+    disable_totp(*user_id, &pool).await.map_err(e500)?;
+    FlashMessage::info("Two-factor authentication has been disabled.").send();
+    Ok(see_other("/admin/two-factor"))
+}
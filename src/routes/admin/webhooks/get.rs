@@ -0,0 +1,81 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    authentication::csrf_token, session_state::TypedSession, utils::e500,
+    webhooks::list_webhook_endpoints,
+};
+
+pub async fn webhooks_list(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let endpoints = list_webhook_endpoints(&pool).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for endpoint in endpoints {
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>
+                    <form action="/admin/webhooks/delete" method="post">
+                        <input hidden type="text" name="endpoint_id" value="{}">
+                        <input hidden type="text" name="csrf_token" value="{}" />
+                        <button type="submit">Delete</button>
+                    </form>
+                </td>
+            </tr>"#,
+            htmlescape::encode_minimal(&endpoint.url),
+            endpoint.created_at.format("%Y-%m-%d %H:%M UTC"),
+            endpoint.n_pending,
+            endpoint.endpoint_id,
+            csrf_token,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Webhooks</title>
+                </head>
+                <body>
+                    {msg_html}
+                    <table>
+                        <tr>
+                            <th>URL</th>
+                            <th>Created</th>
+                            <th>Pending events</th>
+                            <th></th>
+                        </tr>
+                        {rows_html}
+                    </table>
+                    <h2>New endpoint</h2>
+                    <form action="/admin/webhooks" method="post">
+                        <label>URL
+                            <input type="text" placeholder="https://crm.example.com/hooks" name="url">
+                        </label>
+                        <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                        <button type="submit">Add endpoint</button>
+                    </form>
+                    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+                </body>
+            </html>
+            "#,
+        )))
+}
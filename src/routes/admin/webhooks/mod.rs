@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::webhooks_list;
+pub use post::{create_webhook_endpoint, delete_webhook_endpoint};
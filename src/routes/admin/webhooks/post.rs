@@ -0,0 +1,75 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    utils::{e500, see_other},
+    webhooks::{self, parse_endpoint_url},
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    url: String,
+}
+
+#[tracing::instrument(name = "Add a webhook endpoint", skip_all, fields(url = %form.url))]
+pub async fn create_webhook_endpoint(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let url = match parse_endpoint_url(&form.url) {
+        Ok(url) => url,
+        Err(e) => {
+            // Flash messages are rendered as HTML and the error quotes the input.
+            FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+            return Ok(see_other("/admin/webhooks"));
+        }
+    };
+
+    let (_, secret) = webhooks::create_webhook_endpoint(&url, &pool)
+        .await
+        .map_err(e500)?;
+
+    // The secret is needed to sign the events: it cannot be stored hashed,
+    // but there is no reason to show it more than once either.
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Webhooks</title>
+                </head>
+                <body>
+                    <p>Events will be sent to <b>{url}</b>.</p>
+                    <p>Copy the signing secret now: it will not be shown again.</p>
+                    <p><code>{secret}</code></p>
+                    <p>Check the <code>Webhook-Signature</code> header of every request against it.</p>
+                    <p><a href="/admin/webhooks">&lt;- Back</a></p>
+                </body>
+            </html>
+            "#,
+            url = htmlescape::encode_minimal(url.as_str()),
+            secret = secret.expose_secret(),
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteFormData {
+    endpoint_id: Uuid,
+}
+
+#[tracing::instrument(name = "Delete a webhook endpoint", skip(form, pool))]
+pub async fn delete_webhook_endpoint(
+    form: web::Form<DeleteFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    webhooks::delete_webhook_endpoint(form.0.endpoint_id, &pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("The webhook endpoint has been deleted.").send();
+    Ok(see_other("/admin/webhooks"))
+}
@@ -0,0 +1,78 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    authentication::csrf_token, session_state::TypedSession, utils::e500,
+    welcome_email::get_welcome_email,
+};
+
+pub async fn welcome_email_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let welcome_email = get_welcome_email(pool.get_ref()).await.map_err(e500)?;
+    let (enabled, subject, html_content, text_content, updated_html) = match &welcome_email {
+        Some(w) => (
+            w.enabled,
+            htmlescape::encode_attribute(&w.subject),
+            htmlescape::encode_minimal(&w.html_content),
+            htmlescape::encode_minimal(&w.text_content),
+            format!(
+                "<p>Last updated: {}</p>",
+                w.updated_at.format("%Y-%m-%d %H:%M UTC")
+            ),
+        ),
+        None => Default::default(),
+    };
+    let checked = if enabled { " checked" } else { "" };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Welcome email</title>
+                </head>
+                <body>
+                    {msg_html}
+                    <h1>Welcome email</h1>
+                    {updated_html}
+                    <form action="/admin/welcome-email" method="post">
+                        <label>
+                            <input type="checkbox" name="enabled" value="on"{checked}>
+                            Send it to new subscribers once they confirm their subscription
+                        </label>
+                        <br>
+                        <label>Subject:<br>
+                            <input type="text" name="subject" value="{subject}">
+                        </label>
+                        <br>
+                        <label>HTML content:<br>
+                            <textarea name="html_content" rows="20" cols="50">{html_content}</textarea>
+                        </label>
+                        <br>
+                        <label>Plain text content:<br>
+                            <textarea name="text_content" rows="20" cols="50">{text_content}</textarea>
+                        </label>
+                        <p>The content can use the same merge tags as issues, e.g.
+                        <code>{{{{ name }}}}</code>. It is wrapped in the default email template.</p>
+                        <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                        <button type="submit">Save</button>
+                    </form>
+                    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+                </body>
+            </html>
+            "#,
+        )))
+}
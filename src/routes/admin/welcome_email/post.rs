@@ -0,0 +1,62 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use crate::{
+    email_html::prepare_html,
+    merge_tags::validate_content,
+    utils::{e500, see_other},
+    welcome_email::save_welcome_email,
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    /// Unchecked boxes are not submitted at all.
+    #[serde(default)]
+    enabled: Option<String>,
+    subject: String,
+    html_content: String,
+    text_content: String,
+}
+
+impl FormData {
+    /// The message to show when the form cannot be saved as is. A disabled
+    /// welcome email can be incomplete, but not broken.
+    fn validate(&self) -> Result<(), String> {
+        if self.enabled.is_some()
+            && [&self.subject, &self.html_content, &self.text_content]
+                .iter()
+                .any(|field| field.trim().is_empty())
+        {
+            return Err("The welcome email needs a subject, HTML and plain text content.".into());
+        }
+        validate_content(&self.html_content, &self.text_content)
+    }
+}
+
+#[tracing::instrument(name = "Update the welcome email", skip(form, pool))]
+pub async fn update_welcome_email(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Err(e) = form.validate() {
+        FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+        return Ok(see_other("/admin/welcome-email"));
+    }
+    // Sanitized and with its CSS inlined, like the HTML of issues.
+    let html = prepare_html(&form.html_content);
+    save_welcome_email(
+        form.enabled.is_some(),
+        form.subject.trim(),
+        &html.html,
+        &form.text_content,
+        &pool,
+    )
+    .await
+    .map_err(e500)?;
+    FlashMessage::info("The welcome email has been saved.").send();
+    for warning in &html.warnings {
+        FlashMessage::warning(htmlescape::encode_minimal(warning)).send();
+    }
+    Ok(see_other("/admin/welcome-email"))
+}
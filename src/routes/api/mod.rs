@@ -0,0 +1,7 @@
+mod newsletters;
+mod problem;
+mod subscribers;
+
+pub use newsletters::*;
+pub use problem::*;
+pub use subscribers::*;
@@ -0,0 +1,55 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{fetch_newsletter_issue, NewsletterIssue, NewsletterIssueRow};
+use crate::routes::ApiError;
+
+#[derive(serde::Serialize)]
+struct NewsletterIssues {
+    newsletter_issues: Vec<NewsletterIssue>,
+}
+
+/// Every newsletter issue, most recent first.
+#[tracing::instrument(name = "List newsletter issues", skip_all)]
+pub async fn list_newsletter_issues(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    let rows = sqlx::query_as!(
+        NewsletterIssueRow,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            published_at,
+            n_recipients,
+            (
+                SELECT COUNT(*)
+                FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "n_pending!"
+        FROM newsletter_issues i
+        ORDER BY published_at DESC
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to fetch newsletter issues.")?;
+
+    Ok(HttpResponse::Ok().json(NewsletterIssues {
+        newsletter_issues: rows.into_iter().map(Into::into).collect(),
+    }))
+}
+
+#[tracing::instrument(name = "Get a newsletter issue", skip(pool))]
+pub async fn get_newsletter_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let issue = fetch_newsletter_issue(pool.get_ref(), newsletter_issue_id.into_inner())
+        .await
+        .context("Failed to fetch the newsletter issue.")?
+        .ok_or_else(|| ApiError::NotFound("There is no newsletter issue with this id.".into()))?;
+    Ok(HttpResponse::Ok().json(issue))
+}
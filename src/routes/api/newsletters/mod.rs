@@ -0,0 +1,105 @@
+mod get;
+mod post;
+
+pub use get::{get_newsletter_issue, list_newsletter_issues};
+pub use post::create_newsletter_issue;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+/// A newsletter issue, as represented by the JSON API.
+#[derive(serde::Serialize)]
+pub struct NewsletterIssue {
+    newsletter_issue_id: Uuid,
+    title: String,
+    content: Content,
+    published_at: DateTime<Utc>,
+    delivery: Delivery,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Content {
+    text: String,
+    html: String,
+}
+
+#[derive(serde::Serialize)]
+struct Delivery {
+    status: DeliveryStatus,
+    /// The number of confirmed subscribers when the issue was published.
+    recipients: i32,
+    /// The number of recipients the issue still has to be sent to.
+    pending: i64,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum DeliveryStatus {
+    InProgress,
+    Completed,
+}
+
+struct NewsletterIssueRow {
+    newsletter_issue_id: Uuid,
+    title: String,
+    text_content: String,
+    html_content: String,
+    published_at: DateTime<Utc>,
+    n_recipients: i32,
+    n_pending: i64,
+}
+
+impl From<NewsletterIssueRow> for NewsletterIssue {
+    fn from(row: NewsletterIssueRow) -> Self {
+        let status = if row.n_pending > 0 {
+            DeliveryStatus::InProgress
+        } else {
+            DeliveryStatus::Completed
+        };
+        Self {
+            newsletter_issue_id: row.newsletter_issue_id,
+            title: row.title,
+            content: Content {
+                text: row.text_content,
+                html: row.html_content,
+            },
+            published_at: row.published_at,
+            delivery: Delivery {
+                status,
+                recipients: row.n_recipients,
+                pending: row.n_pending,
+            },
+        }
+    }
+}
+
+#[tracing::instrument(skip(executor))]
+async fn fetch_newsletter_issue(
+    executor: impl PgExecutor<'_>,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<NewsletterIssue>, sqlx::Error> {
+    let row = sqlx::query_as!(
+        NewsletterIssueRow,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            published_at,
+            n_recipients,
+            (
+                SELECT COUNT(*)
+                FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "n_pending!"
+        FROM newsletter_issues i
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(executor)
+    .await?;
+    Ok(row.map(Into::into))
+}
@@ -0,0 +1,107 @@
+use actix_web::{http::header::LOCATION, web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+
+use super::{fetch_newsletter_issue, Content};
+use crate::{
+    authentication::UserId,
+    email_html::prepare_html,
+    idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
+    merge_tags::validate_content,
+    routes::{enqueue_delivery_tasks, insert_newsletter_issue, ApiError},
+};
+
+#[derive(serde::Deserialize)]
+pub struct BodyData {
+    title: String,
+    content: Content,
+}
+
+/// Publish a newsletter issue.
+/// Retries are safe as long as they carry the same `Idempotency-Key` header.
+#[tracing::instrument(name = "Publish a newsletter issue through the API", skip_all, fields(user_id = %&*user_id))]
+pub async fn create_newsletter_issue(
+    body: web::Json<BodyData>,
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = user_id.into_inner();
+    let BodyData { title, mut content } = body.0;
+    if title.trim().is_empty() {
+        return Err(ApiError::ValidationError(
+            "The title of the issue cannot be empty.".into(),
+        ));
+    }
+    if content.text.trim().is_empty() || content.html.trim().is_empty() {
+        return Err(ApiError::ValidationError(
+            "The issue needs both a text and an HTML content.".into(),
+        ));
+    }
+    // Stored as it will be sent. The warnings are only shown in the admin
+    // form, whose preview lets authors act on them.
+    content.html = prepare_html(&content.html).html;
+    validate_content(&content.html, &content.text).map_err(ApiError::ValidationError)?;
+    let idempotency_key = idempotency_key(&request)?;
+
+    let mut transaction = match &idempotency_key {
+        Some(idempotency_key) => match try_processing(&pool, idempotency_key, *user_id).await? {
+            NextAction::StartProcessing(t) => t,
+            NextAction::ReturnSavedResponse(saved_response) => return Ok(saved_response),
+        },
+        None => pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool.")?,
+    };
+
+    let issue_id = insert_newsletter_issue(
+        &mut transaction,
+        &title,
+        &content.text,
+        &content.html,
+        None,
+        None,
+        true,
+    )
+    .await
+    .context("Failed to store newsletter issue details.")?;
+    enqueue_delivery_tasks(&mut transaction, issue_id)
+        .await
+        .context("Failed to enqueue delivery tasks.")?;
+    let issue = fetch_newsletter_issue(&mut *transaction, issue_id)
+        .await
+        .context("Failed to fetch the new newsletter issue.")?
+        .context("The new newsletter issue is missing.")?;
+
+    let response = HttpResponse::Accepted()
+        .insert_header((LOCATION, format!("/api/v1/newsletters/{issue_id}")))
+        .json(issue);
+    let response = match idempotency_key {
+        Some(idempotency_key) => {
+            save_response(transaction, &idempotency_key, *user_id, response).await?
+        }
+        None => {
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit SQL transaction to store a newsletter issue.")?;
+            response
+        }
+    };
+    Ok(response)
+}
+
+fn idempotency_key(request: &HttpRequest) -> Result<Option<IdempotencyKey>, ApiError> {
+    let Some(value) = request.headers().get("Idempotency-Key") else {
+        return Ok(None);
+    };
+    let value = value
+        .to_str()
+        .map_err(|_| ApiError::ValidationError("The idempotency key must be ASCII.".into()))?;
+    let key = value
+        .to_owned()
+        .try_into()
+        .map_err(|e: anyhow::Error| ApiError::ValidationError(e.to_string()))?;
+    Ok(Some(key))
+}
@@ -0,0 +1,128 @@
+use actix_web::{
+    error::{JsonPayloadError, PathError, QueryPayloadError},
+    http::{header::WWW_AUTHENTICATE, StatusCode},
+    HttpRequest, HttpResponse, ResponseError,
+};
+
+use crate::{openapi::DocumentedError, routes::error_chain_fmt};
+
+/// The errors of the JSON API, reported as RFC 7807 problem documents.
+#[derive(thiserror::Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    UnprocessableEntity(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// We do not document problem types yet: `about:blank` tells clients to
+/// rely on the status code, with `title` being its reason phrase.
+#[derive(serde::Serialize)]
+struct ProblemDocument {
+    #[serde(rename = "type")]
+    type_: &'static str,
+    title: &'static str,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::UnprocessableEntity(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        // The details of unexpected errors are for our logs only.
+        let detail = match self {
+            ApiError::UnexpectedError(_) => None,
+            e => Some(e.to_string()),
+        };
+        let mut response = HttpResponse::build(status);
+        if let ApiError::Unauthorized(_) = self {
+            response.insert_header((WWW_AUTHENTICATE, "Bearer"));
+        }
+        response
+            .content_type("application/problem+json")
+            .json(ProblemDocument {
+                type_: "about:blank",
+                title: status.canonical_reason().unwrap_or("Unknown error"),
+                status: status.as_u16(),
+                detail,
+            })
+    }
+}
+
+impl DocumentedError for ApiError {
+    fn examples() -> Vec<(Self, &'static str)> {
+        vec![
+            (
+                ApiError::ValidationError(String::new()),
+                "The request is malformed or invalid.",
+            ),
+            (
+                ApiError::Unauthorized(String::new()),
+                "The API token is missing or invalid.",
+            ),
+            (
+                ApiError::Forbidden(String::new()),
+                "The API token lacks the scope the endpoint requires.",
+            ),
+            (
+                ApiError::NotFound(String::new()),
+                "The resource does not exist.",
+            ),
+            (
+                ApiError::Conflict(String::new()),
+                "The request conflicts with an existing resource.",
+            ),
+            (
+                ApiError::UnprocessableEntity(String::new()),
+                "The request is valid, but the change it asks for is not allowed.",
+            ),
+            (
+                ApiError::UnexpectedError(anyhow::anyhow!("")),
+                "Something went wrong on our side.",
+            ),
+        ]
+    }
+}
+
+/// Report malformed JSON bodies as problem documents too.
+pub fn json_error_handler(e: JsonPayloadError, _: &HttpRequest) -> actix_web::Error {
+    ApiError::ValidationError(e.to_string()).into()
+}
+
+/// A path segment that does not parse, e.g. a malformed id, matches no resource.
+pub fn path_error_handler(_: PathError, _: &HttpRequest) -> actix_web::Error {
+    ApiError::NotFound("The resource does not exist.".into()).into()
+}
+
+pub fn query_error_handler(e: QueryPayloadError, _: &HttpRequest) -> actix_web::Error {
+    ApiError::ValidationError(e.to_string()).into()
+}
@@ -0,0 +1,29 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::subscriber_not_found;
+use crate::routes::{remove_subscriber, ApiError};
+
+#[tracing::instrument(name = "Delete a subscriber through the API", skip(pool))]
+pub async fn delete_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let subscriber_id = subscriber_id.into_inner();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    remove_subscriber(&mut transaction, subscriber_id)
+        .await
+        .context("Failed to delete the subscriber.")?
+        .ok_or_else(subscriber_not_found)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete a subscriber.")?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,126 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{fetch_subscriber, subscriber_not_found, Subscriber, SubscriberRow};
+use crate::routes::ApiError;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    limit: Option<i64>,
+    cursor: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct SubscriberPage {
+    subscribers: Vec<Subscriber>,
+    /// Pass it back as `cursor` to get the next page: absent on the last one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+/// Where a page starts: right after the last subscriber of the previous one,
+/// in `(subscribed_at, id)` order. Unlike offsets, it does not skip or repeat
+/// subscribers when others are added or deleted in between.
+struct Cursor {
+    subscribed_at: DateTime<Utc>,
+    id: Uuid,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        let raw = format!("{}:{}", self.subscribed_at.timestamp_micros(), self.id);
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    fn decode(s: &str) -> Option<Self> {
+        let raw = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(s)
+            .ok()?;
+        let raw = String::from_utf8(raw).ok()?;
+        let (micros, id) = raw.split_once(':')?;
+        Some(Self {
+            subscribed_at: DateTime::from_timestamp_micros(micros.parse().ok()?)?,
+            id: Uuid::parse_str(id).ok()?,
+        })
+    }
+}
+
+#[tracing::instrument(name = "List subscribers", skip_all)]
+pub async fn list_subscribers(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let limit = parameters.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(ApiError::ValidationError(format!(
+            "The limit must be between 1 and {MAX_PAGE_SIZE}."
+        )));
+    }
+    let cursor = parameters
+        .cursor
+        .as_deref()
+        .map(|cursor| {
+            Cursor::decode(cursor)
+                .ok_or_else(|| ApiError::ValidationError("The cursor is invalid.".into()))
+        })
+        .transpose()?;
+
+    // Fetch one extra row to find out whether there is a next page.
+    let mut rows = sqlx::query_as!(
+        SubscriberRow,
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE $1::timestamptz IS NULL OR (subscribed_at, id) > ($1, $2)
+        ORDER BY subscribed_at, id
+        LIMIT $3
+        "#,
+        cursor.as_ref().map(|c| c.subscribed_at),
+        cursor.as_ref().map(|c| c.id),
+        limit + 1
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to fetch subscribers.")?;
+
+    let next_cursor = if rows.len() as i64 > limit {
+        rows.truncate(limit as usize);
+        rows.last().map(|row| {
+            Cursor {
+                subscribed_at: row.subscribed_at,
+                id: row.id,
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+    let subscribers = rows
+        .into_iter()
+        .map(Subscriber::try_from)
+        .collect::<Result<_, _>>()?;
+
+    Ok(HttpResponse::Ok().json(SubscriberPage {
+        subscribers,
+        next_cursor,
+    }))
+}
+
+#[tracing::instrument(name = "Get a subscriber", skip(pool))]
+pub async fn get_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let subscriber = fetch_subscriber(pool.get_ref(), subscriber_id.into_inner())
+        .await
+        .context("Failed to fetch the subscriber.")?
+        .ok_or_else(subscriber_not_found)?;
+    Ok(HttpResponse::Ok().json(subscriber))
+}
@@ -0,0 +1,111 @@
+mod delete;
+mod get;
+mod patch;
+mod post;
+
+pub use delete::delete_subscriber;
+pub use get::{get_subscriber, list_subscribers};
+pub use patch::update_subscriber;
+pub use post::create_subscriber;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::routes::ApiError;
+
+/// A subscriber, as represented by the JSON API.
+#[derive(serde::Serialize)]
+pub struct Subscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: SubscriptionStatus,
+    subscribed_at: DateTime<Utc>,
+}
+
+/// The values of `subscriptions.status`.
+#[derive(Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionStatus {
+    PendingConfirmation,
+    Confirmed,
+    /// The email provider reported a hard bounce or a spam complaint.
+    Suppressed,
+}
+
+impl SubscriptionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubscriptionStatus::PendingConfirmation => "pending_confirmation",
+            SubscriptionStatus::Confirmed => "confirmed",
+            SubscriptionStatus::Suppressed => "suppressed",
+        }
+    }
+
+    /// The transitions clients of the API may ask for. Suppression follows
+    /// bounces and complaints reported by the email provider: the API can
+    /// neither set it nor lift it, which would bypass the suppression list.
+    pub fn can_become(&self, status: SubscriptionStatus) -> bool {
+        *self == status
+            || (*self != SubscriptionStatus::Suppressed && status != SubscriptionStatus::Suppressed)
+    }
+}
+
+impl TryFrom<String> for SubscriptionStatus {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "pending_confirmation" => Ok(Self::PendingConfirmation),
+            "confirmed" => Ok(Self::Confirmed),
+            "suppressed" => Ok(Self::Suppressed),
+            other => anyhow::bail!("Unknown subscription status: {other}."),
+        }
+    }
+}
+
+struct SubscriberRow {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+impl TryFrom<SubscriberRow> for Subscriber {
+    type Error = anyhow::Error;
+
+    fn try_from(row: SubscriberRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.id,
+            email: row.email,
+            name: row.name,
+            status: row.status.try_into()?,
+            subscribed_at: row.subscribed_at,
+        })
+    }
+}
+
+#[tracing::instrument(skip(executor))]
+async fn fetch_subscriber(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+) -> Result<Option<Subscriber>, anyhow::Error> {
+    let row = sqlx::query_as!(
+        SubscriberRow,
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(executor)
+    .await?;
+    row.map(Subscriber::try_from).transpose()
+}
+
+fn subscriber_not_found() -> ApiError {
+    ApiError::NotFound("There is no subscriber with this id.".into())
+}
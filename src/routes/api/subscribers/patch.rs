@@ -0,0 +1,99 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{fetch_subscriber, subscriber_not_found, SubscriptionStatus};
+use crate::{
+    domain::SubscriberName,
+    routes::ApiError,
+    webhooks::{enqueue_webhook_event, WebhookEvent},
+    welcome_email::enqueue_welcome_email,
+};
+
+/// Fields that are left out are not changed.
+#[derive(serde::Deserialize)]
+pub struct BodyData {
+    name: Option<String>,
+    status: Option<SubscriptionStatus>,
+}
+
+#[tracing::instrument(name = "Update a subscriber through the API", skip(body, pool))]
+pub async fn update_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    body: web::Json<BodyData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let subscriber_id = subscriber_id.into_inner();
+    let BodyData { name, status } = body.0;
+    let name = name
+        .map(SubscriberName::parse)
+        .transpose()
+        .map_err(ApiError::ValidationError)?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let previous_status: SubscriptionStatus = sqlx::query_scalar!(
+        r#"SELECT status FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        subscriber_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to fetch the subscriber to update.")?
+    .ok_or_else(subscriber_not_found)?
+    .try_into()?;
+    if let Some(status) = status {
+        if !previous_status.can_become(status) {
+            return Err(ApiError::UnprocessableEntity(format!(
+                "The status of a subscriber cannot be changed from {} to {}.",
+                previous_status.as_str(),
+                status.as_str()
+            )));
+        }
+    }
+
+    let updated = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET
+            name = COALESCE($2, name),
+            status = COALESCE($3, status)
+        WHERE id = $1
+        RETURNING email, status
+        "#,
+        subscriber_id,
+        name.as_ref().map(|name| name.as_ref()),
+        status.map(|status| status.as_str()),
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .context("Failed to update the subscriber.")?;
+    if previous_status == SubscriptionStatus::PendingConfirmation
+        && updated.status == SubscriptionStatus::Confirmed.as_str()
+    {
+        enqueue_webhook_event(
+            &mut transaction,
+            &WebhookEvent::SubscriberConfirmed {
+                subscriber_id,
+                email: updated.email.clone(),
+            },
+        )
+        .await
+        .context("Failed to record the confirmation of the subscriber.")?;
+        enqueue_welcome_email(&mut transaction, &updated.email)
+            .await
+            .context("Failed to enqueue the welcome email.")?;
+    }
+    let subscriber = fetch_subscriber(&mut *transaction, subscriber_id)
+        .await
+        .context("Failed to fetch the updated subscriber.")?
+        .ok_or_else(subscriber_not_found)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update a subscriber.")?;
+
+    Ok(HttpResponse::Ok().json(subscriber))
+}
@@ -0,0 +1,123 @@
+use actix_web::{http::header::LOCATION, web, HttpResponse};
+use anyhow::Context;
+use sqlx::{Executor, PgPool};
+
+use super::fetch_subscriber;
+use crate::{
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    email_client::EmailClient,
+    routes::{
+        generate_subscription_token, insert_subscriber, send_confirmation_email, store_token,
+        ApiError,
+    },
+    startup::ApplicationBaseUrl,
+    tracking::TrackingLinks,
+    webhooks::{enqueue_webhook_event, WebhookEvent},
+    welcome_email::enqueue_welcome_email,
+};
+
+#[derive(serde::Deserialize)]
+pub struct BodyData {
+    email: String,
+    name: String,
+    /// Skip the confirmation email, e.g. for subscribers who opted in elsewhere.
+    #[serde(default)]
+    confirmed: bool,
+}
+
+impl TryFrom<&BodyData> for NewSubscriber {
+    type Error = String;
+
+    fn try_from(value: &BodyData) -> Result<NewSubscriber, String> {
+        let name = SubscriberName::parse(value.name.clone())?;
+        let email = SubscriberEmail::parse(value.email.clone())?;
+        Ok(NewSubscriber { email, name })
+    }
+}
+
+#[tracing::instrument(name = "Create a subscriber through the API", skip_all, fields(
+    subscriber_email = %body.email,
+    subscriber_name = %body.name
+))]
+pub async fn create_subscriber(
+    body: web::Json<BodyData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    tracking_links: web::Data<TrackingLinks>,
+) -> Result<HttpResponse, ApiError> {
+    let new_subscriber: NewSubscriber = (&body.0).try_into().map_err(ApiError::ValidationError)?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let subscriber_id = match insert_subscriber(&mut transaction, &new_subscriber).await {
+        Ok(subscriber_id) => subscriber_id,
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            return Err(ApiError::Conflict(format!(
+                "{} is already subscribed.",
+                new_subscriber.email
+            )));
+        }
+        Err(e) => {
+            return Err(anyhow::Error::new(e)
+                .context("Failed to insert new subscriber in the database.")
+                .into())
+        }
+    };
+
+    let subscription_token = if body.confirmed {
+        transaction
+            .execute(sqlx::query!(
+                r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
+                subscriber_id
+            ))
+            .await
+            .context("Failed to confirm the new subscriber.")?;
+        enqueue_webhook_event(
+            &mut transaction,
+            &WebhookEvent::SubscriberConfirmed {
+                subscriber_id,
+                email: new_subscriber.email.as_ref().to_owned(),
+            },
+        )
+        .await
+        .context("Failed to record the confirmation of the new subscriber.")?;
+        enqueue_welcome_email(&mut transaction, new_subscriber.email.as_ref())
+            .await
+            .context("Failed to enqueue the welcome email.")?;
+        None
+    } else {
+        let subscription_token = generate_subscription_token();
+        store_token(&mut transaction, subscriber_id, &subscription_token)
+            .await
+            .context("Failed to store the confirmation token for a new subscriber.")?;
+        Some(subscription_token)
+    };
+    let subscriber = fetch_subscriber(&mut *transaction, subscriber_id)
+        .await
+        .context("Failed to fetch the new subscriber.")?
+        .context("The new subscriber is missing.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a new subscriber.")?;
+
+    if let Some(subscription_token) = subscription_token {
+        send_confirmation_email(
+            &pool,
+            &email_client,
+            &tracking_links,
+            subscriber_id,
+            new_subscriber,
+            &base_url.0,
+            &subscription_token,
+        )
+        .await
+        .context("Failed to send a confirmation email.")?;
+    }
+
+    Ok(HttpResponse::Created()
+        .insert_header((LOCATION, format!("/api/v1/subscribers/{subscriber_id}")))
+        .json(subscriber))
+}
@@ -0,0 +1,193 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    email_html::sanitize_fragment,
+    merge_tags::{render_content, MergeTags},
+    startup::ApplicationBaseUrl,
+    utils::{e404, e500},
+};
+
+const PAGE_SIZE: i64 = 20;
+
+#[derive(serde::Deserialize)]
+pub struct ArchiveParameters {
+    /// Starts at 1, with the most recent issues.
+    page: Option<u32>,
+}
+
+struct ArchivedIssue {
+    title: String,
+    slug: String,
+    published_at: DateTime<Utc>,
+}
+
+/// The issues in the public archive, most recent first.
+#[tracing::instrument(name = "List archived issues", skip_all)]
+pub async fn archive(
+    parameters: web::Query<ArchiveParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let page = parameters.page.unwrap_or(1);
+    if page == 0 {
+        return Err(e404("Pages start at 1."));
+    }
+    // Fetch one extra row to find out whether there is an older page.
+    let mut issues = sqlx::query_as!(
+        ArchivedIssue,
+        r#"
+        SELECT title, slug, published_at
+        FROM newsletter_issues
+        WHERE in_archive
+        ORDER BY published_at DESC, newsletter_issue_id
+        LIMIT $1 OFFSET $2
+        "#,
+        PAGE_SIZE + 1,
+        (i64::from(page) - 1) * PAGE_SIZE
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to fetch the archived issues.")
+    .map_err(e500)?;
+    if issues.is_empty() && page > 1 {
+        return Err(e404("There is no such page in the archive."));
+    }
+    let has_older = issues.len() as i64 > PAGE_SIZE;
+    issues.truncate(PAGE_SIZE as usize);
+
+    let mut issues_html = String::new();
+    for issue in &issues {
+        writeln!(
+            issues_html,
+            r#"<li><a href="/archive/{}">{}</a> ({})</li>"#,
+            issue.slug,
+            htmlescape::encode_minimal(&issue.title),
+            issue.published_at.format("%Y-%m-%d"),
+        )
+        .unwrap();
+    }
+    if issues.is_empty() {
+        issues_html.push_str("<li>No issue has been published yet.</li>");
+    }
+    let mut pages_html = String::new();
+    if page > 1 {
+        write!(
+            pages_html,
+            r#"<a href="/archive?page={}">&lt;- Newer issues</a> "#,
+            page - 1
+        )
+        .unwrap();
+    }
+    if has_older {
+        write!(
+            pages_html,
+            r#"<a href="/archive?page={}">Older issues -&gt;</a>"#,
+            page + 1
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Newsletter archive</title>
+                    <link rel="alternate" type="application/rss+xml" href="/feed.rss">
+                    <link rel="alternate" type="application/atom+xml" href="/feed.atom">
+                </head>
+                <body>
+                    <h1>Newsletter archive</h1>
+                    <ul>
+                        {issues_html}
+                    </ul>
+                    <p>{pages_html}</p>
+                    <p><a href="/">Subscribe to the newsletter</a></p>
+                </body>
+            </html>
+            "#,
+        )))
+}
+
+struct ArchivedIssueContent {
+    title: String,
+    text_content: String,
+    html_content: String,
+    published_at: DateTime<Utc>,
+}
+
+/// An issue of the public archive, as a web page. Its merge tags are filled
+/// in with values that are not specific to any subscriber.
+#[tracing::instrument(name = "Get an archived issue", skip(pool, base_url))]
+pub async fn archived_issue(
+    slug: web::Path<String>,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = sqlx::query_as!(
+        ArchivedIssueContent,
+        r#"
+        SELECT title, text_content, html_content, published_at
+        FROM newsletter_issues
+        WHERE slug = $1 AND in_archive
+        "#,
+        slug.as_str()
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to fetch the archived issue.")
+    .map_err(e500)?
+    .ok_or_else(|| e404("There is no issue with this address in the archive."))?;
+    let content = public_html(&issue.html_content, &issue.text_content, &base_url.0);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>{title}</title>
+                </head>
+                <body>
+                    <h1>{title}</h1>
+                    <p>Published on {published_on}</p>
+                    <article>
+                    {content}
+                    </article>
+                    <p><a href="/">Subscribe to the newsletter</a></p>
+                    <p><a href="/archive">&lt;- All issues</a></p>
+                </body>
+            </html>
+            "#,
+            title = htmlescape::encode_minimal(&issue.title),
+            published_on = issue.published_at.format("%Y-%m-%d"),
+        )))
+}
+
+/// The HTML content of an issue for readers who are not subscribers, safe to
+/// embed in our pages and feeds.
+/// Issues published before merge tags were introduced may contain text that
+/// does not render as a template: they are shown as written.
+pub(super) fn public_html(html_content: &str, text_content: &str, base_url: &str) -> String {
+    match render_content(
+        html_content,
+        text_content,
+        None,
+        &MergeTags::public(base_url),
+    ) {
+        Ok(content) => sanitize_fragment(&content.html),
+        Err(e) => {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                "Failed to render the merge tags of an issue, showing it as written."
+            );
+            sanitize_fragment(html_content)
+        }
+    }
+}
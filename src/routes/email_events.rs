@@ -0,0 +1,278 @@
+use actix_web::{
+    http::{
+        header::{self, HeaderMap, HeaderValue},
+        StatusCode,
+    },
+    web, HttpRequest, HttpResponse, ResponseError,
+};
+use anyhow::Context;
+use base64::Engine;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::{
+    authentication::constant_time_eq,
+    configuration::EmailEventsSettings,
+    openapi::DocumentedError,
+    routes::error_chain_fmt,
+    webhooks::{enqueue_webhook_event, WebhookEvent},
+};
+
+/// The subset of Postmark's bounce and spam-complaint webhooks we act on.
+/// Other record types (deliveries, opens, ...) are acknowledged and ignored.
+#[derive(serde::Deserialize, Debug)]
+#[serde(tag = "RecordType")]
+pub enum EmailEvent {
+    Bounce {
+        #[serde(rename = "Type")]
+        bounce_type: String,
+        #[serde(rename = "Email")]
+        email: String,
+    },
+    SpamComplaint {
+        #[serde(rename = "Email")]
+        email: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// What an event means for the address it is about.
+#[derive(PartialEq, Eq, Debug)]
+enum Deliverability {
+    /// We must stop sending to the address.
+    Suppressed,
+    /// Delivery failed for a reason that may go away, e.g. a full mailbox.
+    SoftBounce,
+    Unaffected,
+}
+
+impl EmailEvent {
+    fn deliverability(&self) -> Deliverability {
+        match self {
+            Self::Bounce { bounce_type, .. } => match bounce_type.as_str() {
+                "HardBounce" | "BadEmailAddress" | "ManuallyDeactivated" => {
+                    Deliverability::Suppressed
+                }
+                "SoftBounce" | "Transient" | "DnsError" => Deliverability::SoftBounce,
+                _ => Deliverability::Unaffected,
+            },
+            Self::SpamComplaint { .. } => Deliverability::Suppressed,
+            Self::Other => Deliverability::Unaffected,
+        }
+    }
+
+    fn email(&self) -> Option<&str> {
+        match self {
+            Self::Bounce { email, .. } | Self::SpamComplaint { email } => Some(email),
+            Self::Other => None,
+        }
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum EmailEventError {
+    #[error("Invalid credentials.")]
+    AuthError(#[source] anyhow::Error),
+    #[error("{0}")]
+    ValidationError(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for EmailEventError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for EmailEventError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::AuthError(_) => StatusCode::UNAUTHORIZED,
+            Self::ValidationError(_) => StatusCode::BAD_REQUEST,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code()).finish();
+        if let Self::AuthError(_) = self {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static(r#"Basic realm="email-events""#),
+            );
+        }
+        response
+    }
+}
+
+impl DocumentedError for EmailEventError {
+    fn examples() -> Vec<(Self, &'static str)> {
+        vec![
+            (
+                EmailEventError::AuthError(anyhow::anyhow!("")),
+                "The Basic credentials are missing or do not match the configured ones.",
+            ),
+            (
+                EmailEventError::ValidationError(String::new()),
+                "The body is not a JSON event.",
+            ),
+            (
+                EmailEventError::UnexpectedError(anyhow::anyhow!("")),
+                "The event could not be processed. The provider will retry it.",
+            ),
+        ]
+    }
+}
+
+/// Receive the bounce and spam-complaint webhooks of the email provider.
+/// Postmark retries anything but a 200, so events about unknown addresses
+/// are acknowledged.
+#[tracing::instrument(name = "Receive an email event", skip_all, fields(email = tracing::field::Empty))]
+pub async fn receive_email_event(
+    request: HttpRequest,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+    settings: web::Data<EmailEventsSettings>,
+) -> Result<HttpResponse, EmailEventError> {
+    check_credentials(request.headers(), &settings).map_err(EmailEventError::AuthError)?;
+    let event: EmailEvent = serde_json::from_slice(&body)
+        .map_err(|e| EmailEventError::ValidationError(format!("Invalid email event: {e}")))?;
+    let Some(email) = event.email() else {
+        return Ok(HttpResponse::Ok().finish());
+    };
+    tracing::Span::current().record("email", tracing::field::display(email));
+
+    match event.deliverability() {
+        Deliverability::Suppressed => suppress_subscriber(&pool, email).await?,
+        Deliverability::SoftBounce => record_soft_bounce(&pool, email).await?,
+        Deliverability::Unaffected => {}
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+fn check_credentials(
+    headers: &HeaderMap,
+    settings: &EmailEventsSettings,
+) -> Result<(), anyhow::Error> {
+    let header_value = headers
+        .get("Authorization")
+        .context("The 'Authorization' header was missing.")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string.")?;
+    let base64encoded_segment = header_value
+        .strip_prefix("Basic ")
+        .context("The authorization scheme was not 'Basic'.")?;
+    let decoded_bytes = base64::engine::general_purpose::STANDARD
+        .decode(base64encoded_segment)
+        .context("Failed to base64-decode 'Basic' credentials.")?;
+    let decoded_credentials = String::from_utf8(decoded_bytes)
+        .context("The decoded credential string is not valid UTF8.")?;
+    let (username, password) = decoded_credentials
+        .split_once(':')
+        .context("A password must be provided in the 'Basic' auth.")?;
+
+    // Evaluate both comparisons, so the response time does not tell which failed.
+    let username_matches = constant_time_eq(username, &settings.username);
+    let password_matches = constant_time_eq(password, settings.password.expose_secret());
+    if !(username_matches && password_matches) {
+        anyhow::bail!("Invalid username or password.");
+    }
+    Ok(())
+}
+
+/// Stop sending to the address and notify the webhook endpoints,
+/// unless it was already suppressed.
+#[tracing::instrument(name = "Suppress subscriber", skip(pool))]
+async fn suppress_subscriber(pool: &PgPool, email: &str) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let suppressed = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'suppressed'
+        WHERE lower(email) = lower($1) AND status <> 'suppressed'
+        RETURNING id, email
+        "#,
+        email
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to suppress the subscriber.")?;
+    if let Some(suppressed) = suppressed {
+        enqueue_webhook_event(
+            &mut transaction,
+            &WebhookEvent::SubscriberBounced {
+                subscriber_id: suppressed.id,
+                email: suppressed.email,
+            },
+        )
+        .await
+        .context("Failed to record the suppression of the subscriber.")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to suppress a subscriber.")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Record soft bounce", skip(pool))]
+async fn record_soft_bounce(pool: &PgPool, email: &str) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions SET n_soft_bounces = n_soft_bounces + 1
+        WHERE lower(email) = lower($1)
+        "#,
+        email
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record a soft bounce.")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Deliverability, EmailEvent};
+
+    fn parse(json: &str) -> EmailEvent {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn hard_bounces_and_complaints_suppress_the_address() {
+        let hard_bounce = parse(
+            r#"{"RecordType": "Bounce", "Type": "HardBounce", "TypeCode": 1, "Email": "a@example.com"}"#,
+        );
+        assert_eq!(hard_bounce.deliverability(), Deliverability::Suppressed);
+        assert_eq!(hard_bounce.email(), Some("a@example.com"));
+
+        let complaint = parse(
+            r#"{"RecordType": "SpamComplaint", "Type": "SpamComplaint", "Email": "a@example.com"}"#,
+        );
+        assert_eq!(complaint.deliverability(), Deliverability::Suppressed);
+    }
+
+    #[test]
+    fn soft_bounces_are_told_apart() {
+        let soft_bounce = parse(
+            r#"{"RecordType": "Bounce", "Type": "SoftBounce", "TypeCode": 4096, "Email": "a@example.com"}"#,
+        );
+        assert_eq!(soft_bounce.deliverability(), Deliverability::SoftBounce);
+
+        let auto_responder = parse(
+            r#"{"RecordType": "Bounce", "Type": "AutoResponder", "TypeCode": 1024, "Email": "a@example.com"}"#,
+        );
+        assert_eq!(auto_responder.deliverability(), Deliverability::Unaffected);
+    }
+
+    #[test]
+    fn other_record_types_are_ignored() {
+        let delivery = parse(r#"{"RecordType": "Delivery", "Recipient": "a@example.com"}"#);
+        assert_eq!(delivery.deliverability(), Deliverability::Unaffected);
+        assert_eq!(delivery.email(), None);
+    }
+}
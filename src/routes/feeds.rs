@@ -0,0 +1,219 @@
+use actix_web::{
+    http::header::{ETag, EntityTag, Header, HttpDate, IfModifiedSince, IfNoneMatch, LastModified},
+    web, HttpRequest, HttpResponse,
+};
+use anyhow::Context;
+use chrono::{DateTime, SecondsFormat, SubsecRound, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::fmt::Write;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use super::archive::public_html;
+use crate::{startup::ApplicationBaseUrl, utils::e500};
+
+/// Feed readers poll often: only the most recent issues are listed.
+const FEED_SIZE: i64 = 20;
+const FEED_TITLE: &str = "Newsletter";
+
+struct FeedItem {
+    newsletter_issue_id: Uuid,
+    title: String,
+    slug: String,
+    /// Rendered for readers who are not subscribers.
+    html: String,
+    published_at: DateTime<Utc>,
+}
+
+impl FeedItem {
+    /// Stable across title changes, unlike the address of the issue.
+    fn guid(&self) -> String {
+        format!("urn:uuid:{}", self.newsletter_issue_id)
+    }
+}
+
+/// The issues of the public archive, as an RSS 2.0 feed.
+#[tracing::instrument(name = "Get the RSS feed", skip_all)]
+pub async fn rss_feed(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let base_url = &base_url.0;
+    let items = fetch_feed_items(&pool, base_url).await.map_err(e500)?;
+    let mut items_xml = String::new();
+    for item in &items {
+        write!(
+            items_xml,
+            r#"
+    <item>
+      <title>{title}</title>
+      <link>{base_url}/archive/{slug}</link>
+      <guid isPermaLink="false">{guid}</guid>
+      <pubDate>{published_at}</pubDate>
+      <description>{html}</description>
+    </item>"#,
+            title = htmlescape::encode_minimal(&item.title),
+            slug = item.slug,
+            guid = item.guid(),
+            published_at = item.published_at.to_rfc2822(),
+            html = htmlescape::encode_minimal(&item.html),
+        )
+        .unwrap();
+    }
+    let last_build_date = match items.first() {
+        Some(item) => format!(
+            "\n    <lastBuildDate>{}</lastBuildDate>",
+            item.published_at.to_rfc2822()
+        ),
+        None => String::new(),
+    };
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">
+  <channel>
+    <title>{FEED_TITLE}</title>
+    <link>{base_url}/archive</link>
+    <description>The issues of the newsletter.</description>
+    <atom:link href="{base_url}/feed.rss" rel="self" type="application/rss+xml"/>{last_build_date}{items_xml}
+  </channel>
+</rss>
+"#
+    );
+    Ok(feed_response(
+        &request,
+        body,
+        "application/rss+xml; charset=utf-8",
+        items.first().map(|item| item.published_at),
+    ))
+}
+
+/// The issues of the public archive, as an Atom feed.
+#[tracing::instrument(name = "Get the Atom feed", skip_all)]
+pub async fn atom_feed(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let base_url = &base_url.0;
+    let items = fetch_feed_items(&pool, base_url).await.map_err(e500)?;
+    let mut entries_xml = String::new();
+    for item in &items {
+        let published_at = item.published_at.to_rfc3339_opts(SecondsFormat::Secs, true);
+        write!(
+            entries_xml,
+            r#"
+  <entry>
+    <title>{title}</title>
+    <link href="{base_url}/archive/{slug}"/>
+    <id>{guid}</id>
+    <published>{published_at}</published>
+    <updated>{published_at}</updated>
+    <content type="html">{html}</content>
+  </entry>"#,
+            title = htmlescape::encode_minimal(&item.title),
+            slug = item.slug,
+            guid = item.guid(),
+            html = htmlescape::encode_minimal(&item.html),
+        )
+        .unwrap();
+    }
+    // An empty feed has not been updated since the epoch.
+    let updated = items
+        .first()
+        .map(|item| item.published_at)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, true);
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>{FEED_TITLE}</title>
+  <id>{base_url}/feed.atom</id>
+  <link href="{base_url}/archive"/>
+  <link href="{base_url}/feed.atom" rel="self"/>
+  <updated>{updated}</updated>
+  <author><name>{FEED_TITLE}</name></author>{entries_xml}
+</feed>
+"#
+    );
+    Ok(feed_response(
+        &request,
+        body,
+        "application/atom+xml; charset=utf-8",
+        items.first().map(|item| item.published_at),
+    ))
+}
+
+/// The most recent issues of the public archive, most recent first.
+#[tracing::instrument(skip(pool))]
+async fn fetch_feed_items(pool: &PgPool, base_url: &str) -> Result<Vec<FeedItem>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, title, slug, html_content, text_content, published_at
+        FROM newsletter_issues
+        WHERE in_archive
+        ORDER BY published_at DESC, newsletter_issue_id
+        LIMIT $1
+        "#,
+        FEED_SIZE
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the issues of the feed.")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| FeedItem {
+            html: public_html(&row.html_content, &row.text_content, base_url),
+            newsletter_issue_id: row.newsletter_issue_id,
+            title: row.title,
+            slug: row.slug,
+            published_at: row.published_at,
+        })
+        .collect())
+}
+
+/// Answer with `304 Not Modified` when the reader already has this version
+/// of the feed. The ETag is derived from the feed itself: it also changes
+/// when an issue leaves the archive, which `last_modified` does not track.
+fn feed_response(
+    request: &HttpRequest,
+    body: String,
+    content_type: &str,
+    last_modified: Option<DateTime<Utc>>,
+) -> HttpResponse {
+    let digest = Sha256::digest(body.as_bytes());
+    let etag = EntityTag::new_strong(hex::encode(&digest[..16]));
+    // HTTP dates have no fractional seconds.
+    let last_modified = last_modified.map(|t| HttpDate::from(SystemTime::from(t.trunc_subsecs(0))));
+
+    // `If-None-Match` takes precedence, as the more precise of the two.
+    // It parses as an empty list when it is absent.
+    let not_modified = match IfNoneMatch::parse(request) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) if !tags.is_empty() => {
+            tags.iter().any(|tag| tag.weak_eq(&etag))
+        }
+        _ => match (IfModifiedSince::parse(request), last_modified) {
+            (Ok(IfModifiedSince(since)), Some(last_modified)) => {
+                SystemTime::from(last_modified) <= SystemTime::from(since)
+            }
+            _ => false,
+        },
+    };
+
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response.insert_header(ETag(etag));
+    if let Some(last_modified) = last_modified {
+        response.insert_header(LastModified(last_modified));
+    }
+    if not_modified {
+        response.finish()
+    } else {
+        response.content_type(content_type).body(body)
+    }
+}
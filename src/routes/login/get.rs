@@ -2,14 +2,24 @@ use actix_web::{http::header::ContentType, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
 use std::fmt::Write;
 
-pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+use crate::{
+    authentication::csrf_token,
+    session_state::TypedSession,
+    utils::{e500, see_other},
+};
+
+pub async fn login_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = csrf_token(&session).map_err(e500)?;
     let mut error_html = String::new();
 
     for m in flash_messages.iter() {
         writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
 
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
             r#"<!DOCTYPE html>
@@ -33,10 +43,60 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
                         <input type="text" placeholder="Enter Password" name="password"
                     /></label>
 
+                    <input hidden type="text" name="csrf_token" value="{csrf_token}" />
                     <button type="submit">Login</button>
                     </form>
+                    <p><a href="/password-reset">Forgot your password?</a></p>
+                </body>
+                </html>
+            "#
+        )))
+}
+
+pub async fn second_factor_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    // Nothing to verify: do not start a session for whoever wanders here.
+    if session.get_pending_second_factor().map_err(e500)?.is_none() {
+        return Ok(see_other("/login"));
+    }
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+    let mut error_html = String::new();
+
+    for m in flash_messages.iter() {
+        writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+                <html lang="en">
+                <head>
+                    <meta charset="UTF-8" />
+                    <meta name="viewport" content="width=device-width, initial-scale=1.0" />
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+                    <title>Two-factor authentication</title>
+                </head>
+                <body>
+                    {error_html}
+                    <form action="/login/two-factor" method="post">
+                    <label
+                        >Authentication code
+                        <input
+                            type="text"
+                            inputmode="numeric"
+                            autocomplete="one-time-code"
+                            placeholder="Enter the code from your app or a recovery code"
+                            name="code"
+                    /></label>
+
+                    <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                    <button type="submit">Verify</button>
+                    </form>
                 </body>
                 </html>
             "#
-        ))
+        )))
 }
@@ -1,12 +1,18 @@
 use crate::{
-    authentication::{validate_credentials, AuthError, Credentials},
-    routes::error_chain_fmt,
+    authentication::{
+        get_totp_settings, rotate_csrf_token, use_recovery_code, validate_credentials, verify_totp,
+        AuthError, Credentials, LoginThrottle, PasswordHashingPolicy, ThrottleDecision,
+    },
+    routes::{error_chain_fmt, get_username},
+    session_registry::SessionRegistry,
     session_state::TypedSession,
+    utils::{client_ip, see_other, user_agent},
 };
-use actix_web::{error::InternalError, http::header::LOCATION, web, HttpResponse};
+use actix_web::{error::InternalError, http::header::LOCATION, web, HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::Secret;
 use sqlx::PgPool;
+use uuid::Uuid;
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
@@ -14,29 +20,70 @@ pub struct FormData {
     password: Secret<String>,
 }
 
-#[tracing::instrument(name = "Login", skip(form, pool, session), fields(username = tracing::field::Empty, user_id = tracing::field::Empty))]
+#[tracing::instrument(name = "Login", skip(form, pool, session, session_registry, login_throttle, password_hashing_policy, request), fields(username = tracing::field::Empty, user_id = tracing::field::Empty))]
 pub async fn login(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     session: TypedSession,
+    session_registry: web::Data<SessionRegistry>,
+    login_throttle: web::Data<LoginThrottle>,
+    password_hashing_policy: web::Data<PasswordHashingPolicy>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
     let credentials = Credentials {
         username: form.0.username,
         password: form.0.password,
     };
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
-    match validate_credentials(credentials, &pool).await {
+    let username = credentials.username.clone();
+    tracing::Span::current().record("username", tracing::field::display(&username));
+    let client_ip = client_ip(&request);
+
+    match login_throttle
+        .register_attempt(&username, &client_ip)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+    {
+        ThrottleDecision::Allow(delay) => tokio::time::sleep(delay).await,
+        ThrottleDecision::Locked => return Err(login_redirect(LoginError::TooManyAttempts)),
+    }
+
+    match validate_credentials(credentials, &password_hashing_policy, &pool).await {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
-            session.renew();
-            session
-                .insert_user_id(user_id)
-                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
-            Ok(HttpResponse::SeeOther()
-                .insert_header((LOCATION, "/admin/dashboard"))
-                .finish())
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            let totp_settings = get_totp_settings(user_id, &pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            if totp_settings.enabled {
+                // The password is not enough: park the user until they
+                // provide their second factor. The failures are only
+                // cleared once it is verified.
+                let second_factor_failures = login_throttle
+                    .second_factor_failures(&username, &client_ip)
+                    .await
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                if second_factor_failures >= MAX_SECOND_FACTOR_FAILURES {
+                    return Err(login_redirect(LoginError::TooManyAttempts));
+                }
+                session.renew();
+                rotate_csrf_token(&session)
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                session
+                    .insert_pending_second_factor(user_id)
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+                return Ok(see_other("/login/two-factor"));
+            }
+
+            login_throttle
+                .clear(&username, &client_ip)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            start_session(&session, &session_registry, user_id, &request)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            Ok(see_other("/admin/dashboard"))
         }
         Err(e) => {
+            // The attempt has already been counted as a failure.
             let e = match e {
                 AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
@@ -47,6 +94,99 @@ pub async fn login(
     }
 }
 
+/// How many invalid codes an account can receive from a client before it is
+/// locked out, like after too many invalid passwords. Codes are short:
+/// without this limit, a stolen password would be enough to brute-force the
+/// second factor.
+const MAX_SECOND_FACTOR_FAILURES: u64 = 5;
+
+#[derive(serde::Deserialize)]
+pub struct SecondFactorFormData {
+    code: String,
+}
+
+#[tracing::instrument(name = "Verify second factor", skip(form, pool, session, session_registry, login_throttle, request), fields(user_id = tracing::field::Empty))]
+pub async fn verify_second_factor(
+    form: web::Form<SecondFactorFormData>,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+    session_registry: web::Data<SessionRegistry>,
+    login_throttle: web::Data<LoginThrottle>,
+    request: HttpRequest,
+) -> Result<HttpResponse, InternalError<LoginError>> {
+    let user_id = match session
+        .get_pending_second_factor()
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?
+    {
+        Some(user_id) => user_id,
+        None => {
+            return Err(login_redirect(LoginError::AuthError(anyhow::anyhow!(
+                "There is no login waiting for a second factor."
+            ))))
+        }
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+    let username = get_username(user_id, &pool)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+    let client_ip = client_ip(&request);
+
+    let failures = login_throttle
+        .register_second_factor_attempt(&username, &client_ip)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+    if failures >= MAX_SECOND_FACTOR_FAILURES {
+        session.remove_pending_second_factor();
+        return Err(login_redirect(LoginError::TooManyAttempts));
+    }
+
+    let is_valid = verify_totp(user_id, &form.code, &pool)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+        || use_recovery_code(user_id, &form.code, &pool)
+            .await
+            .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+    if !is_valid {
+        if failures + 1 >= MAX_SECOND_FACTOR_FAILURES {
+            session.remove_pending_second_factor();
+            return Err(login_redirect(LoginError::TooManyAttempts));
+        }
+        FlashMessage::error("Invalid authentication code.").send();
+        let e = LoginError::AuthError(anyhow::anyhow!("Invalid second factor."));
+        return Err(InternalError::from_response(
+            e,
+            see_other("/login/two-factor"),
+        ));
+    }
+
+    login_throttle
+        .clear(&username, &client_ip)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+    session.remove_pending_second_factor();
+    start_session(&session, &session_registry, user_id, &request)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+    Ok(see_other("/admin/dashboard"))
+}
+
+/// Attach the user to a fresh session and register it.
+async fn start_session(
+    session: &TypedSession,
+    session_registry: &SessionRegistry,
+    user_id: Uuid,
+    request: &HttpRequest,
+) -> Result<(), anyhow::Error> {
+    session.renew();
+    rotate_csrf_token(session)?;
+    session.insert_user_id(user_id)?;
+    let session_id = session_registry
+        .register(user_id, &client_ip(request), &user_agent(request))
+        .await?;
+    session.insert_session_id(session_id)?;
+    Ok(())
+}
+
 /// Redirect to the login page with an error message.
 fn login_redirect(e: LoginError) -> InternalError<LoginError> {
     // Send cookies in error level
@@ -63,6 +203,10 @@ fn login_redirect(e: LoginError) -> InternalError<LoginError> {
 pub enum LoginError {
     #[error("Authentication Failed.")]
     AuthError(#[source] anyhow::Error),
+    // Deliberately vague: it must not reveal whether the account
+    // or the client address has been locked out.
+    #[error("Too many failed login attempts. Please try again later.")]
+    TooManyAttempts,
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -1,13 +1,29 @@
 mod admin;
+mod api;
+mod archive;
+mod email_events;
+mod feeds;
 mod health_check;
 mod home;
 mod login;
+mod openapi;
+mod password_reset;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_manage;
+mod tracking;
 
 pub use admin::*;
+pub use api::*;
+pub use archive::*;
+pub use email_events::*;
+pub use feeds::*;
 pub use health_check::*;
 pub use home::*;
 pub use login::*;
+pub use openapi::*;
+pub use password_reset::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_manage::*;
+pub use tracking::*;
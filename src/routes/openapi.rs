@@ -0,0 +1,7 @@
+use actix_web::HttpResponse;
+
+use crate::openapi::openapi_document;
+
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(openapi_document())
+}
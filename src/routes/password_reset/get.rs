@@ -0,0 +1,111 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    authentication::{csrf_token, validate_reset_token},
+    session_state::TypedSession,
+    utils::{e500, see_other},
+};
+
+pub async fn password_reset_request_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Forgot password</title>
+                </head>
+                <body>
+                    {msg_html}
+                    <form action="/password-reset" method="post">
+                        <label>Email
+                            <input type="email" placeholder="Enter your email" name="email">
+                        </label>
+                        <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                        <button type="submit">Send reset link</button>
+                    </form>
+                    <p><a href="/login">&lt;- Back to login</a></p>
+                </body>
+            </html>
+            "#
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+pub async fn password_reset_form(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token = parameters.0.token;
+    if validate_reset_token(&token, &pool)
+        .await
+        .map_err(e500)?
+        .is_none()
+    {
+        FlashMessage::error("The password reset link is invalid or has expired.").send();
+        return Ok(see_other("/password-reset"));
+    }
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let token = htmlescape::encode_attribute(&token);
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Reset password</title>
+                </head>
+                <body>
+                    {msg_html}
+                    <form action="/password-reset/confirm" method="post">
+                        <label>New password
+                        <input
+                            type="password"
+                            placeholder="Enter new password"
+                            name="new_password"
+                        >
+                        </label>
+                        <br>
+                        <label>Confirm new password
+                        <input
+                            type="password"
+                            placeholder="Type the new password again"
+                            name="new_password_check"
+                        >
+                        </label>
+                        <br>
+                        <input hidden type="text" name="token" value="{token}" />
+                        <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                        <button type="submit">Reset password</button>
+                    </form>
+                </body>
+            </html>
+            "#
+        )))
+}
@@ -0,0 +1,172 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{
+        change_password, consume_reset_token, generate_reset_token, get_user_by_email,
+        store_reset_token, PasswordHashingPolicy, PasswordPolicy, RESET_TOKEN_TTL_MINUTES,
+    },
+    domain::SubscriberEmail,
+    email_client::EmailClient,
+    routes::get_username,
+    session_registry::SessionRegistry,
+    startup::ApplicationBaseUrl,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct RequestFormData {
+    email: String,
+}
+
+#[tracing::instrument(
+    name = "Request a password reset",
+    skip(form, pool, email_client, base_url)
+)]
+pub async fn request_password_reset(
+    form: web::Form<RequestFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // The outcome is never shown to the caller: the same message is displayed
+    // whether the email belongs to a user or not, to avoid leaking which
+    // addresses have an account.
+    if let Err(e) = send_reset_link(form.0.email, &pool, &email_client, &base_url.0).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send a password reset link."
+        );
+    }
+
+    FlashMessage::info(
+        "If an account is associated with that email address, \
+        you will receive a password reset link shortly.",
+    )
+    .send();
+    Ok(see_other("/password-reset"))
+}
+
+async fn send_reset_link(
+    email: String,
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+) -> Result<(), anyhow::Error> {
+    let (user_id, email) = match get_user_by_email(&email, pool)
+        .await
+        .context("Failed to look up the user by email.")?
+    {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+    let recipient = SubscriberEmail::parse(email).map_err(|e| anyhow::anyhow!(e))?;
+
+    let token = generate_reset_token();
+    store_reset_token(user_id, &token, pool).await?;
+
+    let reset_link = format!("{}/password-reset/confirm?token={}", base_url, token);
+    let plain_body = format!(
+        "Someone asked to reset your password.\n\
+        Visit {} to choose a new one. The link expires in {} minutes.\n\
+        If it was not you, you can safely ignore this email.",
+        reset_link, RESET_TOKEN_TTL_MINUTES
+    );
+    let html_body = format!(
+        "Someone asked to reset your password.<br />\
+        Click <a href=\"{}\">here</a> to choose a new one. \
+        The link expires in {} minutes.<br />\
+        If it was not you, you can safely ignore this email.",
+        reset_link, RESET_TOKEN_TTL_MINUTES
+    );
+
+    email_client
+        .send_email(&recipient, "Reset your password", &html_body, &plain_body)
+        .await
+        .context("Failed to send the password reset email.")?;
+
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetFormData {
+    token: String,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+#[tracing::instrument(
+    name = "Reset password",
+    skip(form, pool, password_hashing_policy, password_policy, session_registry)
+)]
+pub async fn reset_password(
+    form: web::Form<ResetFormData>,
+    pool: web::Data<PgPool>,
+    password_hashing_policy: web::Data<PasswordHashingPolicy>,
+    password_policy: web::Data<PasswordPolicy>,
+    session_registry: web::Data<SessionRegistry>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let ResetFormData {
+        token,
+        new_password,
+        new_password_check,
+    } = form.0;
+    let form_location = format!(
+        "/password-reset/confirm?token={}",
+        urlencoding::encode(&token)
+    );
+
+    if new_password.expose_secret() != new_password_check.expose_secret() {
+        FlashMessage::error(
+            "You entered two different new passwords - the field values must match.",
+        )
+        .send();
+        return Ok(see_other(&form_location));
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let user_id: Uuid = match consume_reset_token(&mut transaction, &token)
+        .await
+        .map_err(e500)?
+    {
+        Some(user_id) => user_id,
+        None => {
+            FlashMessage::error("The password reset link is invalid or has expired.").send();
+            return Ok(see_other("/password-reset"));
+        }
+    };
+    // Dropping the transaction on a violation keeps the link usable.
+    let username = get_username(user_id, &pool).await.map_err(e500)?;
+    if let Err(violation) = password_policy.check(&new_password, &username, None) {
+        FlashMessage::error(violation.to_string()).send();
+        return Ok(see_other(&form_location));
+    }
+    change_password(
+        user_id,
+        new_password,
+        &password_hashing_policy,
+        &mut *transaction,
+    )
+    .await
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to reset the password.")
+        .map_err(e500)?;
+
+    // Whoever was logged in with the old password must log in again.
+    session_registry.revoke_all(user_id).await.map_err(e500)?;
+
+    FlashMessage::info("Your password has been reset. You can now log in.").send();
+    Ok(see_other("/login"))
+}
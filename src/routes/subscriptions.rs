@@ -1,4 +1,8 @@
-use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use actix_web::{
+    error::UrlencodedError,
+    http::{header::ContentType, StatusCode},
+    web, HttpRequest, HttpResponse, ResponseError,
+};
 use anyhow::Context;
 use chrono::Utc;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
@@ -6,15 +10,26 @@ use sqlx::{Executor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
+    confirmation_email::get_confirmation_email,
     domain::{NewSubscriber, SubscriberEmail, SubscriberName},
     email_client::EmailClient,
+    email_templates::get_default_layout,
+    merge_tags::{render_confirmation_email, MergeTags},
+    openapi::DocumentedError,
+    redirect_allow_list::RedirectAllowList,
     startup::ApplicationBaseUrl,
+    suppression_list::is_suppressed,
+    tracking::TrackingLinks,
+    utils::see_other,
+    webhooks::{enqueue_webhook_event, WebhookEvent},
 };
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
     email: String,
     name: String,
+    /// Where to send the subscriber instead of showing our own page.
+    redirect_to: Option<String>,
 }
 
 // If you provide a TryFrom implementation, your
@@ -29,7 +44,7 @@ impl TryFrom<FormData> for NewSubscriber {
     }
 }
 
-#[tracing::instrument(name = "Adding a new subscriber.", skip(form, pool, email_client, base_url), fields(
+#[tracing::instrument(name = "Adding a new subscriber.", skip(form, pool, email_client, base_url, tracking_links, redirect_allow_list), fields(
     subscriber_email = %form.email,
     subscriber_name = %form.name
 ))]
@@ -38,10 +53,21 @@ pub async fn subscribe(
     pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
+    tracking_links: web::Data<TrackingLinks>,
+    redirect_allow_list: web::Data<RedirectAllowList>,
 ) -> Result<HttpResponse, SubscribeError> {
     // `web::Form` is a wrapper around `FormData`
     // `form.0` gives us access to the underlying `FormData
-    let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
+    let mut form = form.0;
+    // Checked before anything is stored, so that a bad link in a form
+    // does not leave subscribers behind every time it is used.
+    let redirect_to = form
+        .redirect_to
+        .take()
+        .map(|target| resolve_redirect(&redirect_allow_list, &target))
+        .transpose()
+        .map_err(SubscribeError::ValidationError)?;
+    let new_subscriber = form.try_into().map_err(SubscribeError::ValidationError)?;
     let mut transaction = pool
         .begin()
         .await
@@ -61,7 +87,10 @@ pub async fn subscribe(
         .await
         .context("Failed to commit SQL transaction to store a new subscriber.")?;
     send_confirmation_email(
+        &pool,
         &email_client,
+        &tracking_links,
+        subscriber_id,
         new_subscriber,
         &base_url.0,
         &subscrition_token,
@@ -69,14 +98,68 @@ pub async fn subscribe(
     .await
     .context("Failed to send a confirmation email.")?;
 
-    Ok(HttpResponse::Ok().finish())
+    Ok(match redirect_to {
+        Some(redirect_to) => see_other(&redirect_to),
+        None => subscription_page(
+            StatusCode::OK,
+            "Check your inbox",
+            "<p>Thanks for subscribing! We have sent you an email: \
+            click the link in it to confirm your subscription.</p>",
+        ),
+    })
+}
+
+/// The absolute URL to send a subscriber to, if `target` is on the allow-list.
+pub(crate) fn resolve_redirect(
+    redirect_allow_list: &RedirectAllowList,
+    target: &str,
+) -> Result<String, String> {
+    redirect_allow_list
+        .resolve(target)
+        .ok_or_else(|| format!("{target} is not a page of our site."))
+}
+
+/// The pages shown to subscribers, who land on them from a form or an email
+/// rather than from the admin UI. `message_html` must already be escaped.
+pub(crate) fn subscription_page(
+    status: StatusCode,
+    title: &str,
+    message_html: &str,
+) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+                    <title>{title} - Newsletter</title>
+                </head>
+                <body>
+                    <header><a href="/">Newsletter</a></header>
+                    <main>
+                        <h1>{title}</h1>
+                        {message_html}
+                    </main>
+                    <footer><a href="/archive">Read past issues</a></footer>
+                </body>
+            </html>
+            "#
+        ))
+}
+
+/// Forms that lack a field are answered with the same page as invalid ones.
+pub fn subscribe_form_error_handler(_: UrlencodedError, _: &HttpRequest) -> actix_web::Error {
+    SubscribeError::ValidationError("Both your name and your email address are required.".into())
+        .into()
 }
 
 #[tracing::instrument(
     name = "Store subscription token in the database",
     skip(subscrition_token, transaction)
 )]
-async fn store_token(
+pub(crate) async fn store_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
     subscrition_token: &str,
@@ -92,33 +175,68 @@ async fn store_token(
     Ok(())
 }
 
+/// Nothing is sent to addresses on the suppression list, but the caller
+/// is not told: whoever subscribed must not learn it is there.
+/// The email is written from the admin UI, and wrapped in the default email
+/// template if there is one.
 #[tracing::instrument(
     name = "Send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber, base_url, subscription_token)
+    skip(
+        pool,
+        email_client,
+        tracking_links,
+        new_subscriber,
+        base_url,
+        subscription_token
+    )
 )]
-async fn send_confirmation_email(
+pub(crate) async fn send_confirmation_email(
+    pool: &PgPool,
     email_client: &EmailClient,
+    tracking_links: &TrackingLinks,
+    subscriber_id: Uuid,
     new_subscriber: NewSubscriber,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), anyhow::Error> {
+    if is_suppressed(pool, &new_subscriber.email)
+        .await
+        .context("Failed to check the suppression list.")?
+    {
+        tracing::info!("Skipping the confirmation email: the address is suppressed.");
+        return Ok(());
+    }
+
     let confirmation_link = format!(
         "{}/subscriptions/confirm?subscription_token={}",
         base_url, subscription_token
     );
-    let plain_body = format!(
-        "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
-        confirmation_link
-    );
-    let html_body = format!(
-        "Welcome to our newsletter!<br />\
-        Click <a href=\"{}\">here</a> to confirm your subscription.",
-        confirmation_link
-    );
+    let confirmation_email = get_confirmation_email(pool).await?;
+    let merge_tags = MergeTags {
+        name: new_subscriber.name.as_ref().to_owned(),
+        email: new_subscriber.email.as_ref().to_owned(),
+        unsubscribe_url: tracking_links.unsubscribe_url(subscriber_id),
+        preferences_url: tracking_links.preferences_url(subscriber_id),
+    };
+    let layout = get_default_layout(pool).await?;
+    let content = render_confirmation_email(
+        &confirmation_email.html_content,
+        &confirmation_email.text_content,
+        layout.as_ref(),
+        &merge_tags,
+        &confirmation_link,
+    )
+    .context("Failed to render the confirmation email.")?;
 
     email_client
-        .send_email(&new_subscriber.email, "Welcome!", &html_body, &plain_body)
-        .await
+        .send_email(
+            &new_subscriber.email,
+            &confirmation_email.subject,
+            &content.html,
+            &content.text,
+        )
+        .await?;
+    Ok(())
 }
 
 #[tracing::instrument(
@@ -144,12 +262,54 @@ pub async fn insert_subscriber(
     transaction.execute(query).await?;
     // Using the `?` operator to return early
     // if the function failed, returning a sqlx::Error
+    enqueue_webhook_event(
+        transaction,
+        &WebhookEvent::SubscriberCreated {
+            subscriber_id,
+            email: new_subscriber.email.as_ref().to_owned(),
+            name: new_subscriber.name.as_ref().to_owned(),
+        },
+    )
+    .await?;
 
     Ok(subscriber_id)
 }
 
+/// Delete a subscriber and everything attached to them.
+/// Returns their email, or `None` if there was no such subscriber.
+#[tracing::instrument(name = "Removing a subscriber from the database", skip(transaction))]
+pub(crate) async fn remove_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+    let deleted = sqlx::query!(
+        r#"DELETE FROM subscriptions WHERE id = $1 RETURNING email"#,
+        subscriber_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    let Some(deleted) = deleted else {
+        return Ok(None);
+    };
+    enqueue_webhook_event(
+        transaction,
+        &WebhookEvent::SubscriberUnsubscribed {
+            subscriber_id,
+            email: deleted.email.clone(),
+        },
+    )
+    .await?;
+    Ok(Some(deleted.email))
+}
+
 /// Generate a random 25-characters-long case-sensitive subscription token
-fn generate_subscription_token() -> String {
+pub(crate) fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
@@ -180,6 +340,38 @@ impl ResponseError for SubscribeError {
             SubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let message_html = match self {
+            SubscribeError::ValidationError(e) => format!(
+                "<p>{}</p><p>Please go back and check the form.</p>",
+                htmlescape::encode_minimal(e)
+            ),
+            SubscribeError::UnexpectedError(_) => {
+                "<p>Something went wrong on our side. Please try again later.</p>".to_owned()
+            }
+        };
+        subscription_page(
+            self.status_code(),
+            "We could not sign you up",
+            &message_html,
+        )
+    }
+}
+
+impl DocumentedError for SubscribeError {
+    fn examples() -> Vec<(Self, &'static str)> {
+        vec![
+            (
+                SubscribeError::ValidationError(String::new()),
+                "The name, the email address or `redirect_to` is invalid.",
+            ),
+            (
+                SubscribeError::UnexpectedError(anyhow::anyhow!("")),
+                "The subscriber could not be stored or emailed.",
+            ),
+        ]
+    }
 }
 
 pub struct StoreTokenError(sqlx::Error);
@@ -1,4 +1,11 @@
-use crate::routes::error_chain_fmt;
+use crate::{
+    openapi::DocumentedError,
+    redirect_allow_list::RedirectAllowList,
+    routes::{error_chain_fmt, resolve_redirect, subscription_page},
+    utils::see_other,
+    webhooks::{enqueue_webhook_event, WebhookEvent},
+    welcome_email::enqueue_welcome_email,
+};
 use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
 use anyhow::Context;
 use sqlx::PgPool;
@@ -6,13 +13,25 @@ use uuid::Uuid;
 
 #[derive(serde::Deserialize)]
 pub struct Parameters {
-    subscription_token: String,
+    subscription_token: Option<String>,
+    /// Where to send the subscriber instead of showing our own page.
+    redirect_to: Option<String>,
+}
+
+/// Confirming twice is not an error: links get clicked more than once.
+enum Confirmation {
+    Confirmed,
+    AlreadyConfirmed,
 }
 
 #[derive(thiserror::Error)]
 pub enum ConfirmationError {
+    #[error("The confirmation link has no token.")]
+    MissingToken,
     #[error("There is no subscriber associated with the provided token.")]
     UnknownToken,
+    #[error("{0}")]
+    InvalidRedirect(String),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -26,43 +45,137 @@ impl std::fmt::Debug for ConfirmationError {
 impl ResponseError for ConfirmationError {
     fn status_code(&self) -> StatusCode {
         match self {
+            Self::MissingToken | Self::InvalidRedirect(_) => StatusCode::BAD_REQUEST,
             Self::UnknownToken => StatusCode::UNAUTHORIZED,
             Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let (title, message_html) = match self {
+            // Tokens are deleted when a subscriber is removed: an old link
+            // looks the same as a mistyped one.
+            Self::MissingToken | Self::UnknownToken => (
+                "This link is invalid or has expired",
+                "<p>Please copy the whole link from the confirmation email, \
+                or <a href=\"/\">subscribe again</a>.</p>"
+                    .to_owned(),
+            ),
+            Self::InvalidRedirect(e) => (
+                "This link is invalid",
+                format!("<p>{}</p>", htmlescape::encode_minimal(e)),
+            ),
+            Self::UnexpectedError(_) => (
+                "We could not confirm your subscription",
+                "<p>Something went wrong on our side. Please try the link again later.</p>"
+                    .to_owned(),
+            ),
+        };
+        subscription_page(self.status_code(), title, &message_html)
+    }
+}
+
+impl DocumentedError for ConfirmationError {
+    fn examples() -> Vec<(Self, &'static str)> {
+        vec![
+            (ConfirmationError::MissingToken, "The link has no token."),
+            (
+                ConfirmationError::InvalidRedirect(String::new()),
+                "`redirect_to` is not a page of our site.",
+            ),
+            (
+                ConfirmationError::UnknownToken,
+                "There is no subscriber associated with the provided token.",
+            ),
+            (
+                ConfirmationError::UnexpectedError(anyhow::anyhow!("")),
+                "The subscription could not be confirmed.",
+            ),
+        ]
+    }
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters))]
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(parameters, pool, redirect_allow_list)
+)]
 pub async fn confirm(
     parameters: web::Query<Parameters>,
     pool: web::Data<PgPool>,
+    redirect_allow_list: web::Data<RedirectAllowList>,
 ) -> Result<HttpResponse, ConfirmationError> {
-    let subscriber_id = get_subscriber_id_from_token(&pool, &parameters.subscription_token)
+    let Parameters {
+        subscription_token,
+        redirect_to,
+    } = parameters.0;
+    let subscription_token = subscription_token.ok_or(ConfirmationError::MissingToken)?;
+    let redirect_to = redirect_to
+        .map(|target| resolve_redirect(&redirect_allow_list, &target))
+        .transpose()
+        .map_err(ConfirmationError::InvalidRedirect)?;
+    let subscriber_id = get_subscriber_id_from_token(&pool, &subscription_token)
         .await
         .context("Failed to retrieve the subscriber id associated with the provided token.")?
         .ok_or(ConfirmationError::UnknownToken)?;
 
-    confirm_subscriber(&pool, subscriber_id)
+    let confirmation = confirm_subscriber(&pool, subscriber_id)
         .await
         .context("Failed to update the subscriber status to `confirmed`.")?;
 
-    Ok(HttpResponse::Ok().finish())
+    if let Some(redirect_to) = redirect_to {
+        return Ok(see_other(&redirect_to));
+    }
+    Ok(match confirmation {
+        Confirmation::Confirmed => subscription_page(
+            StatusCode::OK,
+            "Your subscription is confirmed",
+            "<p>Thanks! You will get the next issue of our newsletter.</p>",
+        ),
+        Confirmation::AlreadyConfirmed => subscription_page(
+            StatusCode::OK,
+            "You are already subscribed",
+            "<p>Your subscription was confirmed before: there is nothing else to do.</p>",
+        ),
+    })
 }
 
+/// Confirming twice is a no-op: the event and the welcome email are only
+/// sent the first time.
 #[tracing::instrument(name = "Mark subscriber as confirmed", skip(subscriber_id, pool))]
-async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
+async fn confirm_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Confirmation, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let confirmed = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'confirmed'
+        WHERE id = $1 AND status = 'pending_confirmation'
+        RETURNING email
+        "#,
         subscriber_id
     )
-    .execute(pool)
+    .fetch_optional(&mut *transaction)
     .await
     .map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
         e
     })?;
+    let Some(confirmed) = confirmed else {
+        return Ok(Confirmation::AlreadyConfirmed);
+    };
+    enqueue_webhook_event(
+        &mut transaction,
+        &WebhookEvent::SubscriberConfirmed {
+            subscriber_id,
+            email: confirmed.email.clone(),
+        },
+    )
+    .await?;
+    enqueue_welcome_email(&mut transaction, &confirmed.email).await?;
+    transaction.commit().await?;
 
-    Ok(())
+    Ok(Confirmation::Confirmed)
 }
 
 #[tracing::instrument(name = "Get subscriber_id from token", skip(subscription_token, pool))]
@@ -0,0 +1,160 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{
+    authentication::csrf_token,
+    session_state::TypedSession,
+    tracking::TrackingLinks,
+    utils::{e404, e500},
+};
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+struct Subscriber {
+    email: String,
+    name: String,
+}
+
+#[tracing::instrument(name = "Show the unsubscribe form", skip_all)]
+pub async fn unsubscribe_form(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    tracking_links: web::Data<TrackingLinks>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token = parameters.0.token;
+    let subscriber_id = tracking_links
+        .verify_subscriber_token(&token)
+        .ok_or_else(|| e404("This link is invalid."))?;
+    let msg_html = messages_html(&flash_messages);
+    let Some(subscriber) = get_subscriber(&pool, subscriber_id).await.map_err(e500)? else {
+        return Ok(not_subscribed_page(&msg_html));
+    };
+    let email = htmlescape::encode_minimal(&subscriber.email);
+    let token = htmlescape::encode_attribute(&token);
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Unsubscribe</title>
+                </head>
+                <body>
+                    {msg_html}
+                    <p>Do you want to stop receiving our newsletter at {email}?</p>
+                    <form action="/subscriptions/unsubscribe" method="post">
+                        <input hidden type="text" name="token" value="{token}" />
+                        <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                        <button type="submit">Unsubscribe</button>
+                    </form>
+                </body>
+            </html>
+            "#
+        )))
+}
+
+#[tracing::instrument(name = "Show the preferences form", skip_all)]
+pub async fn preferences_form(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    tracking_links: web::Data<TrackingLinks>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token = parameters.0.token;
+    let subscriber_id = tracking_links
+        .verify_subscriber_token(&token)
+        .ok_or_else(|| e404("This link is invalid."))?;
+    let msg_html = messages_html(&flash_messages);
+    let Some(subscriber) = get_subscriber(&pool, subscriber_id).await.map_err(e500)? else {
+        return Ok(not_subscribed_page(&msg_html));
+    };
+    let email = htmlescape::encode_minimal(&subscriber.email);
+    let name = htmlescape::encode_attribute(&subscriber.name);
+    let unsubscribe_url =
+        htmlescape::encode_attribute(&tracking_links.unsubscribe_url(subscriber_id));
+    let token = htmlescape::encode_attribute(&token);
+    let csrf_token = csrf_token(&session).map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Subscription preferences</title>
+                </head>
+                <body>
+                    {msg_html}
+                    <p>You receive our newsletter at {email}.</p>
+                    <form action="/subscriptions/preferences" method="post">
+                        <label>Name
+                            <input type="text" name="name" value="{name}">
+                        </label>
+                        <input hidden type="text" name="token" value="{token}" />
+                        <input hidden type="text" name="csrf_token" value="{csrf_token}" />
+                        <button type="submit">Save</button>
+                    </form>
+                    <p><a href="{unsubscribe_url}">Unsubscribe</a></p>
+                </body>
+            </html>
+            "#
+        )))
+}
+
+fn messages_html(flash_messages: &IncomingFlashMessages) -> String {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    msg_html
+}
+
+/// Links stay valid after unsubscribing, e.g. in older issues.
+fn not_subscribed_page(msg_html: &str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Not subscribed</title>
+                </head>
+                <body>
+                    {msg_html}
+                    <p>You are not subscribed to our newsletter.</p>
+                </body>
+            </html>
+            "#
+        ))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<Subscriber>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"SELECT email, name FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the subscriber.")?;
+    Ok(subscriber)
+}
@@ -0,0 +1,8 @@
+//! Pages reached from the links at the bottom of newsletter issues.
+//! Subscribers have no account: they are identified by the signed token
+//! of the link.
+mod get;
+mod post;
+
+pub use get::{preferences_form, unsubscribe_form};
+pub use post::{unsubscribe, update_preferences};
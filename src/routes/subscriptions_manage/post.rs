@@ -0,0 +1,97 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    domain::SubscriberName,
+    routes::remove_subscriber,
+    tracking::TrackingLinks,
+    utils::{e404, e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeFormData {
+    token: String,
+}
+
+#[tracing::instrument(name = "Unsubscribe", skip_all)]
+pub async fn unsubscribe(
+    form: web::Form<UnsubscribeFormData>,
+    pool: web::Data<PgPool>,
+    tracking_links: web::Data<TrackingLinks>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token = form.0.token;
+    let subscriber_id = tracking_links
+        .verify_subscriber_token(&token)
+        .ok_or_else(|| e404("This link is invalid."))?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let removed = remove_subscriber(&mut transaction, subscriber_id)
+        .await
+        .context("Failed to delete the subscriber.")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete a subscriber.")
+        .map_err(e500)?;
+
+    if let Some(email) = removed {
+        FlashMessage::info(format!(
+            "{} has been unsubscribed.",
+            htmlescape::encode_minimal(&email)
+        ))
+        .send();
+    }
+    Ok(see_other(&format!(
+        "/subscriptions/unsubscribe?token={}",
+        urlencoding::encode(&token)
+    )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesFormData {
+    token: String,
+    name: String,
+}
+
+#[tracing::instrument(name = "Update subscription preferences", skip_all)]
+pub async fn update_preferences(
+    form: web::Form<PreferencesFormData>,
+    pool: web::Data<PgPool>,
+    tracking_links: web::Data<TrackingLinks>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let PreferencesFormData { token, name } = form.0;
+    let subscriber_id = tracking_links
+        .verify_subscriber_token(&token)
+        .ok_or_else(|| e404("This link is invalid."))?;
+    let form_location = format!(
+        "/subscriptions/preferences?token={}",
+        urlencoding::encode(&token)
+    );
+
+    let name = match SubscriberName::parse(name) {
+        Ok(name) => name,
+        Err(e) => {
+            FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+            return Ok(see_other(&form_location));
+        }
+    };
+    sqlx::query!(
+        r#"UPDATE subscriptions SET name = $1 WHERE id = $2"#,
+        name.as_ref(),
+        subscriber_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to update the name of the subscriber.")
+    .map_err(e500)?;
+
+    FlashMessage::info("Your preferences have been saved.").send();
+    Ok(see_other(&form_location))
+}
@@ -0,0 +1,94 @@
+use actix_web::{
+    http::header::{CacheControl, CacheDirective, LOCATION},
+    web, HttpResponse,
+};
+use sqlx::PgPool;
+
+use crate::tracking::{Recipient, TrackingLinks, TRACKING_PIXEL};
+
+/// Serve the tracking pixel of a copy of an issue, recording that it was opened.
+/// Failing to record the open must not break the email: the pixel is served
+/// whatever happens, as long as the token is genuine.
+#[tracing::instrument(name = "Track an issue opening", skip_all)]
+pub async fn track_open(
+    token: web::Path<String>,
+    pool: web::Data<PgPool>,
+    tracking_links: web::Data<TrackingLinks>,
+) -> HttpResponse {
+    let Some(recipient) = tracking_links.verify_open_token(&token) else {
+        return HttpResponse::NotFound().finish();
+    };
+    if let Err(e) = record_open(&pool, recipient).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to record the opening of an issue."
+        );
+    }
+    HttpResponse::Ok()
+        .content_type("image/gif")
+        // Clients should ask again every time the email is opened.
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .body(TRACKING_PIXEL)
+}
+
+/// Redirect to the target of a link of an issue, recording that it was clicked.
+/// Only URLs signed by the server are followed, so the endpoint cannot be
+/// used to redirect to arbitrary websites.
+#[tracing::instrument(name = "Track a link click", skip_all)]
+pub async fn track_click(
+    token: web::Path<String>,
+    pool: web::Data<PgPool>,
+    tracking_links: web::Data<TrackingLinks>,
+) -> HttpResponse {
+    let Some((recipient, url)) = tracking_links.verify_click_token(&token) else {
+        return HttpResponse::NotFound().finish();
+    };
+    if let Err(e) = record_click(&pool, recipient, &url).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to record a link click."
+        );
+    }
+    HttpResponse::Found()
+        .insert_header((LOCATION, url))
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .finish()
+}
+
+/// Subscribers who were deleted since the issue was sent are not recorded.
+#[tracing::instrument(skip(pool))]
+async fn record_open(pool: &PgPool, recipient: Recipient) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issue_opens (newsletter_issue_id, subscriber_id, first_opened_at)
+        SELECT $1, id, now() FROM subscriptions WHERE id = $2
+        ON CONFLICT (newsletter_issue_id, subscriber_id)
+        DO UPDATE SET n_opens = newsletter_issue_opens.n_opens + 1
+        "#,
+        recipient.newsletter_issue_id,
+        recipient.subscriber_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(pool))]
+async fn record_click(pool: &PgPool, recipient: Recipient, url: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issue_clicks (newsletter_issue_id, subscriber_id, url, first_clicked_at)
+        SELECT $1, id, $3, now() FROM subscriptions WHERE id = $2
+        ON CONFLICT (newsletter_issue_id, subscriber_id, url)
+        DO UPDATE SET n_clicks = newsletter_issue_clicks.n_clicks + 1
+        "#,
+        recipient.newsletter_issue_id,
+        recipient.subscriber_id,
+        url
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
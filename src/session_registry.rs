@@ -0,0 +1,232 @@
+use chrono::{DateTime, Duration, Utc};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use uuid::Uuid;
+
+/// Server-side index of the sessions opened by each user.
+///
+/// Session state lives in Redis under keys that are only known to
+/// `actix-session`, so we cannot enumerate the sessions of a user from there.
+/// Every successful login registers a random session id under the user, and
+/// `reject_anonymous_users` refuses any session whose id is no longer listed.
+#[derive(Clone)]
+pub struct SessionRegistry {
+    redis: ConnectionManager,
+    timeouts: SessionTimeouts,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SessionTimeouts {
+    /// How long a session survives without being used.
+    pub idle: Duration,
+    /// How long a session survives after login, however active it is.
+    pub absolute: Duration,
+}
+
+pub enum SessionActivity {
+    Active,
+    /// The session went past one of its timeouts and must not be used anymore.
+    Expired,
+}
+
+/// What the user is shown to recognise one of their sessions.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct SessionMetadata {
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub ip: String,
+    pub user_agent: String,
+}
+
+pub struct ActiveSession {
+    pub session_id: Uuid,
+    pub metadata: SessionMetadata,
+}
+
+impl SessionRegistry {
+    pub fn new(redis: ConnectionManager, timeouts: SessionTimeouts) -> Self {
+        Self { redis, timeouts }
+    }
+
+    fn user_sessions_key(user_id: Uuid) -> String {
+        format!("user_sessions:{user_id}")
+    }
+
+    fn metadata_key(session_id: Uuid) -> String {
+        format!("session_metadata:{session_id}")
+    }
+
+    /// The metadata is kept until the end of the absolute lifetime of the
+    /// session, so that an idle session can still be told apart from an
+    /// unknown one.
+    async fn store_metadata(
+        &self,
+        session_id: Uuid,
+        metadata: &SessionMetadata,
+    ) -> Result<(), anyhow::Error> {
+        let remaining_lifetime = metadata.created_at + self.timeouts.absolute - Utc::now();
+        // Round up, so the metadata never expires before the session does.
+        let ttl_seconds = (remaining_lifetime.num_milliseconds().max(1) as u64).div_ceil(1000);
+        self.redis
+            .clone()
+            .set_ex::<_, _, ()>(
+                Self::metadata_key(session_id),
+                serde_json::to_string(metadata)?,
+                ttl_seconds,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Register a new session for the user and return its id.
+    #[tracing::instrument(name = "Register session", skip(self))]
+    pub async fn register(
+        &self,
+        user_id: Uuid,
+        ip: &str,
+        user_agent: &str,
+    ) -> Result<Uuid, anyhow::Error> {
+        let session_id = Uuid::new_v4();
+        let now = Utc::now();
+        self.store_metadata(
+            session_id,
+            &SessionMetadata {
+                created_at: now,
+                last_seen_at: now,
+                ip: ip.to_owned(),
+                user_agent: user_agent.to_owned(),
+            },
+        )
+        .await?;
+        self.redis
+            .clone()
+            .sadd::<_, _, ()>(Self::user_sessions_key(user_id), session_id.to_string())
+            .await?;
+        Ok(session_id)
+    }
+
+    #[tracing::instrument(name = "Check if session is active", skip(self))]
+    pub async fn is_active(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, anyhow::Error> {
+        let is_member = self
+            .redis
+            .clone()
+            .sismember::<_, _, bool>(Self::user_sessions_key(user_id), session_id.to_string())
+            .await?;
+        Ok(is_member)
+    }
+
+    /// Record that the session has just been used, from the given IP,
+    /// unless it has gone past one of its timeouts.
+    #[tracing::instrument(name = "Touch session", skip(self))]
+    pub async fn touch(
+        &self,
+        session_id: Uuid,
+        ip: &str,
+    ) -> Result<SessionActivity, anyhow::Error> {
+        let metadata: Option<String> = self
+            .redis
+            .clone()
+            .get(Self::metadata_key(session_id))
+            .await?;
+        let Some(metadata) = metadata else {
+            return Ok(SessionActivity::Expired);
+        };
+        let mut metadata: SessionMetadata = serde_json::from_str(&metadata)?;
+
+        let now = Utc::now();
+        if now - metadata.last_seen_at > self.timeouts.idle
+            || now - metadata.created_at > self.timeouts.absolute
+        {
+            return Ok(SessionActivity::Expired);
+        }
+
+        metadata.last_seen_at = now;
+        metadata.ip = ip.to_owned();
+        self.store_metadata(session_id, &metadata).await?;
+        Ok(SessionActivity::Active)
+    }
+
+    /// The sessions of the user, most recently used first.
+    /// Expired sessions are dropped from the index.
+    #[tracing::instrument(name = "List sessions", skip(self))]
+    pub async fn list(&self, user_id: Uuid) -> Result<Vec<ActiveSession>, anyhow::Error> {
+        let session_ids: Vec<String> = self
+            .redis
+            .clone()
+            .smembers(Self::user_sessions_key(user_id))
+            .await?;
+        let session_ids = session_ids
+            .iter()
+            .filter_map(|id| Uuid::parse_str(id).ok())
+            .collect::<Vec<_>>();
+        if session_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let metadata: Vec<Option<String>> = self
+            .redis
+            .clone()
+            .mget(
+                session_ids
+                    .iter()
+                    .map(|id| Self::metadata_key(*id))
+                    .collect::<Vec<_>>(),
+            )
+            .await?;
+
+        let now = Utc::now();
+        let mut sessions = Vec::with_capacity(session_ids.len());
+        for (session_id, metadata) in session_ids.into_iter().zip(metadata) {
+            let metadata: Option<SessionMetadata> =
+                metadata.map(|m| serde_json::from_str(&m)).transpose()?;
+            match metadata {
+                Some(metadata) if now - metadata.last_seen_at <= self.timeouts.idle => sessions
+                    .push(ActiveSession {
+                        session_id,
+                        metadata,
+                    }),
+                _ => self.revoke(user_id, session_id).await?,
+            }
+        }
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.metadata.last_seen_at));
+        Ok(sessions)
+    }
+
+    #[tracing::instrument(name = "Revoke session", skip(self))]
+    pub async fn revoke(&self, user_id: Uuid, session_id: Uuid) -> Result<(), anyhow::Error> {
+        redis::pipe()
+            .atomic()
+            .srem(Self::user_sessions_key(user_id), session_id.to_string())
+            .del(Self::metadata_key(session_id))
+            .query_async::<_, ()>(&mut self.redis.clone())
+            .await?;
+        Ok(())
+    }
+
+    /// Invalidate every session of the user.
+    /// They will be purged from the session store the next time they are used.
+    #[tracing::instrument(name = "Revoke all sessions", skip(self))]
+    pub async fn revoke_all(&self, user_id: Uuid) -> Result<(), anyhow::Error> {
+        self.revoke_all_except(user_id, None).await
+    }
+
+    /// Invalidate every session of the user but the given one,
+    /// e.g. to kick out whoever else might be using the account.
+    #[tracing::instrument(name = "Revoke other sessions", skip(self))]
+    pub async fn revoke_all_except(
+        &self,
+        user_id: Uuid,
+        kept_session_id: Option<Uuid>,
+    ) -> Result<(), anyhow::Error> {
+        let session_ids: Vec<String> = self
+            .redis
+            .clone()
+            .smembers(Self::user_sessions_key(user_id))
+            .await?;
+        for session_id in session_ids.iter().filter_map(|id| Uuid::parse_str(id).ok()) {
+            if Some(session_id) != kept_session_id {
+                self.revoke(user_id, session_id).await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,18 +1,31 @@
 use super::email_client::EmailClient;
-use super::routes::{confirm, health_check, subscribe};
-use crate::authentication::reject_anonymous_users;
-use crate::configuration::{DatabaseSettings, Settings};
-use crate::routes::{
-    admin_dashboard, change_password, change_password_form, home, log_out, login, login_form,
-    newsletter_form, publish_newsletter,
+use crate::authentication::{
+    reject_anonymous_users, reject_invalid_csrf_tokens, reject_requests_without_api_token,
+    LoginThrottle, PasswordHashingPolicy, PasswordPolicy,
 };
+use crate::configuration::{
+    DatabaseSettings, EmailEventsSettings, LoginThrottleSettings, PasswordHashingSettings,
+    PasswordPolicySettings, Settings,
+};
+use crate::openapi::{register_routes, RouteGroup};
+use crate::redirect_allow_list::RedirectAllowList;
+use crate::routes::{json_error_handler, path_error_handler, query_error_handler};
+use crate::session_registry::{SessionRegistry, SessionTimeouts};
+use crate::tracking::TrackingLinks;
+use crate::trusted_proxies::TrustedProxies;
+use actix_session::config::BrowserSession;
 use actix_session::storage::RedisSessionStore;
 use actix_session::SessionMiddleware;
-use actix_web::cookie::Key;
-use actix_web::{dev::Server, web, App, HttpServer};
+use actix_web::cookie::{time::Duration as CookieDuration, Key};
+use actix_web::{
+    dev::{Server, ServerHandle},
+    web, App, HttpServer,
+};
 use actix_web_flash_messages::storage::CookieMessageStore;
 use actix_web_flash_messages::FlashMessagesFramework;
 use actix_web_lab::middleware::from_fn;
+use anyhow::Context;
+use redis::aio::ConnectionManager;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
@@ -37,14 +50,26 @@ impl Application {
         );
         let listener = TcpListener::bind(address)?;
         let port = listener.local_addr().unwrap().port();
+        let session_timeouts = configuration.application.session_timeouts();
+        let email_events = configuration.email_events.context(
+            "The credentials of the email provider's webhooks are missing: \
+            set APP_EMAIL_EVENTS__USERNAME and APP_EMAIL_EVENTS__PASSWORD.",
+        )?;
         // Return the error if the server fails to start
         let server = run(
             listener,
             connection_pool,
             email_client,
             configuration.application.base_url,
+            configuration.application.redirect_allow_list,
+            configuration.application.trusted_proxies,
             configuration.application.hmac_secret,
             configuration.redis_uri,
+            session_timeouts,
+            configuration.login_throttle,
+            configuration.password_hashing,
+            configuration.password_policy,
+            email_events,
         )
         .await?;
 
@@ -55,6 +80,11 @@ impl Application {
         self.port
     }
 
+    /// A handle to stop the server from outside, e.g. at the end of a test.
+    pub fn handle(&self) -> ServerHandle {
+        self.server.handle()
+    }
+
     // this function only returns when the application is stopped.
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
         self.server.await
@@ -72,22 +102,49 @@ pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
 pub struct ApplicationBaseUrl(pub String);
 
 // This is no longer a binary entrypoint. Now you can use it as a library in other binaries or tests.
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
     listener: TcpListener,
     db_pool: PgPool,
     email_client: EmailClient,
     base_url: String,
+    redirect_allow_list: Vec<String>,
+    trusted_proxies: Vec<String>,
     hmac_secret: Secret<String>,
     redis_uri: Secret<String>,
+    session_timeouts: SessionTimeouts,
+    login_throttle: LoginThrottleSettings,
+    password_hashing: PasswordHashingSettings,
+    password_policy: PasswordPolicySettings,
+    email_events: EmailEventsSettings,
 ) -> Result<Server, anyhow::Error> {
     // Make connection an ARC
     let db_pool = web::Data::new(db_pool);
     let email_client = web::Data::new(email_client);
+    let tracking_links = web::Data::new(TrackingLinks::new(base_url.clone(), hmac_secret.clone()));
+    let redirect_allow_list =
+        web::Data::new(RedirectAllowList::new(&base_url, &redirect_allow_list)?);
+    let trusted_proxies = web::Data::new(TrustedProxies::new(&trusted_proxies)?);
     let base_url = web::Data::new(ApplicationBaseUrl(base_url));
     let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
+    let password_hashing_policy = web::Data::new(PasswordHashingPolicy::new(&password_hashing)?);
+    let password_policy = web::Data::new(PasswordPolicy::new(&password_policy)?);
+    let email_events = web::Data::new(email_events);
 
     // Middleware for Session
     let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+    // Timeouts are enforced by `reject_anonymous_users`. The session state is
+    // kept a little longer, so that it can tell the user their session has
+    // expired rather than silently asking them to log in.
+    let session_lifecycle = BrowserSession::default().state_ttl(CookieDuration::seconds(
+        (session_timeouts.absolute + session_timeouts.idle).num_seconds(),
+    ));
+
+    // Redis connection for the data we manage ourselves
+    let redis =
+        ConnectionManager::new(redis::Client::open(redis_uri.expose_secret().as_str())?).await?;
+    let session_registry = web::Data::new(SessionRegistry::new(redis.clone(), session_timeouts));
+    let login_throttle = web::Data::new(LoginThrottle::new(redis, login_throttle));
 
     // Middleware for Flash Messages
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
@@ -97,30 +154,61 @@ pub async fn run(
     let server = HttpServer::new(move || {
         App::new()
             .wrap(message_framework.clone())
-            .wrap(SessionMiddleware::new(
-                redis_store.clone(),
-                secret_key.clone(),
-            ))
+            .wrap(
+                SessionMiddleware::builder(redis_store.clone(), secret_key.clone())
+                    .session_lifecycle(session_lifecycle.clone())
+                    .build(),
+            )
             .wrap(TracingLogger::default())
-            .route("/", web::get().to(home))
-            .route("/login", web::post().to(login))
-            .route("/login", web::get().to(login_form))
-            .route("/health_check", web::get().to(health_check))
-            .route("/subscriptions", web::post().to(subscribe))
-            .route("/subscriptions/confirm", web::get().to(confirm))
+            // Scopes answer 404 for paths they do not know, rather than
+            // falling back to the routes registered after them: public
+            // routes such as `/subscriptions/confirm` come first.
+            .configure(register_routes(RouteGroup::Public))
+            .service(
+                web::scope(RouteGroup::Login.prefix())
+                    .wrap(from_fn(reject_invalid_csrf_tokens))
+                    .configure(register_routes(RouteGroup::Login)),
+            )
+            .service(
+                web::scope(RouteGroup::PasswordReset.prefix())
+                    .wrap(from_fn(reject_invalid_csrf_tokens))
+                    .configure(register_routes(RouteGroup::PasswordReset)),
+            )
             .service(
-                web::scope("/admin")
+                web::scope(RouteGroup::SubscriptionManagement.prefix())
+                    .wrap(from_fn(reject_invalid_csrf_tokens))
+                    .configure(register_routes(RouteGroup::SubscriptionManagement)),
+            )
+            .service(
+                web::scope(RouteGroup::Admin.prefix())
+                    .wrap(from_fn(reject_invalid_csrf_tokens))
                     .wrap(from_fn(reject_anonymous_users))
-                    .route("/dashboard", web::get().to(admin_dashboard))
-                    .route("/newsletters", web::get().to(newsletter_form))
-                    .route("/newsletters", web::post().to(publish_newsletter))
-                    .route("/password", web::get().to(change_password_form))
-                    .route("/password", web::post().to(change_password))
-                    .route("/logout", web::post().to(log_out)),
+                    // Newsletter issues and email templates easily go over
+                    // the default limit of 16 KB. The CSRF middleware reads
+                    // the body first, under the limit of raw payloads.
+                    .app_data(web::FormConfig::default().limit(1024 * 1024))
+                    .app_data(web::PayloadConfig::new(1024 * 1024))
+                    .configure(register_routes(RouteGroup::Admin)),
+            )
+            .service(
+                web::scope(RouteGroup::Api.prefix())
+                    .wrap(from_fn(reject_requests_without_api_token))
+                    .app_data(web::JsonConfig::default().error_handler(json_error_handler))
+                    .app_data(web::PathConfig::default().error_handler(path_error_handler))
+                    .app_data(web::QueryConfig::default().error_handler(query_error_handler))
+                    .configure(register_routes(RouteGroup::Api)),
             )
             .app_data(db_pool.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
+            .app_data(session_registry.clone())
+            .app_data(login_throttle.clone())
+            .app_data(password_hashing_policy.clone())
+            .app_data(password_policy.clone())
+            .app_data(email_events.clone())
+            .app_data(tracking_links.clone())
+            .app_data(redirect_allow_list.clone())
+            .app_data(trusted_proxies.clone())
     })
     .listen(listener)?
     .run();
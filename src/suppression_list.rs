@@ -0,0 +1,116 @@
+//! Addresses that must never be mailed: legal requests, complaints,
+//! role addresses... Every send path checks the list right before calling
+//! `EmailClient::send_email`, whatever the status of the subscriber.
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
+
+use crate::domain::SubscriberEmail;
+
+pub struct SuppressedEmail {
+    pub email: String,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Check the suppression list", skip(executor))]
+pub async fn is_suppressed(
+    executor: impl PgExecutor<'_>,
+    email: &SubscriberEmail,
+) -> Result<bool, sqlx::Error> {
+    let suppressed = sqlx::query!(
+        r#"SELECT EXISTS (SELECT 1 FROM suppressed_emails WHERE email = lower($1)) AS "suppressed!""#,
+        email.as_ref()
+    )
+    .fetch_one(executor)
+    .await?
+    .suppressed;
+    Ok(suppressed)
+}
+
+/// Add addresses to the list, returning how many were not on it yet.
+#[tracing::instrument(name = "Suppress emails", skip(emails, pool))]
+pub async fn suppress_emails(
+    emails: &[SubscriberEmail],
+    reason: Option<&str>,
+    pool: &PgPool,
+) -> Result<u64, anyhow::Error> {
+    let emails: Vec<String> = emails.iter().map(|e| e.as_ref().to_lowercase()).collect();
+    let n_added = sqlx::query!(
+        r#"
+        INSERT INTO suppressed_emails (email, reason, created_at)
+        SELECT DISTINCT email, $2, now() FROM UNNEST($1::text[]) AS email
+        ON CONFLICT (email) DO NOTHING
+        "#,
+        &emails,
+        reason
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add emails to the suppression list.")?
+    .rows_affected();
+    Ok(n_added)
+}
+
+/// Returns whether the address was on the list.
+#[tracing::instrument(name = "Remove email from the suppression list", skip(pool))]
+pub async fn unsuppress_email(email: &str, pool: &PgPool) -> Result<bool, anyhow::Error> {
+    let n_removed = sqlx::query!(
+        r#"DELETE FROM suppressed_emails WHERE email = lower($1)"#,
+        email
+    )
+    .execute(pool)
+    .await
+    .context("Failed to remove an email from the suppression list.")?
+    .rows_affected();
+    Ok(n_removed > 0)
+}
+
+#[tracing::instrument(name = "List suppressed emails", skip(pool))]
+pub async fn list_suppressed_emails(pool: &PgPool) -> Result<Vec<SuppressedEmail>, anyhow::Error> {
+    let emails = sqlx::query_as!(
+        SuppressedEmail,
+        r#"
+        SELECT email, reason, created_at
+        FROM suppressed_emails
+        ORDER BY created_at DESC, email
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the suppression list.")?;
+    Ok(emails)
+}
+
+/// Read a bulk import: addresses separated by new lines, commas or
+/// semicolons, as exported by most tools. Blank entries are skipped,
+/// invalid ones are returned separately.
+pub fn parse_email_list(input: &str) -> (Vec<SubscriberEmail>, Vec<String>) {
+    let mut valid = Vec::new();
+    let mut invalid = Vec::new();
+    for entry in input
+        .split(['\n', ',', ';'])
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        match SubscriberEmail::parse(entry.to_owned()) {
+            Ok(email) => valid.push(email),
+            Err(_) => invalid.push(entry.to_owned()),
+        }
+    }
+    (valid, invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_email_list;
+
+    #[test]
+    fn bulk_imports_accept_common_separators() {
+        let (valid, invalid) =
+            parse_email_list("a@example.com\r\nb@example.com, c@example.com;\n\nnot-an-email\n");
+        let valid: Vec<_> = valid.iter().map(|e| e.as_ref()).collect();
+        assert_eq!(valid, ["a@example.com", "b@example.com", "c@example.com"]);
+        assert_eq!(invalid, ["not-an-email"]);
+    }
+}
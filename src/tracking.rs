@@ -0,0 +1,329 @@
+//! Engagement tracking for newsletter issues.
+//!
+//! Every recipient gets their own copy of an issue, with URLs that identify
+//! them. The URLs are signed with the `hmac_secret` of the application,
+//! so they cannot be forged to inflate the statistics of an issue, nor to
+//! turn the click redirector into an open redirect. The same signatures
+//! protect the links that let subscribers manage their subscription
+//! without an account.
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// Bytes of the HMAC kept in the tokens.
+const SIGNATURE_LENGTH: usize = 16;
+
+/// A transparent 1x1 GIF.
+pub const TRACKING_PIXEL: &[u8] = b"GIF89a\x01\x00\x01\x00\x80\x00\x00\x00\x00\x00\xff\xff\xff\
+    !\xf9\x04\x01\x00\x00\x00\x00,\x00\x00\x00\x00\x01\x00\x01\x00\x00\x02\x02D\x01\x00;";
+
+/// Who a copy of an issue was sent to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Recipient {
+    pub newsletter_issue_id: Uuid,
+    pub subscriber_id: Uuid,
+}
+
+impl Recipient {
+    fn to_bytes(self) -> Vec<u8> {
+        [
+            self.newsletter_issue_id.as_bytes().as_slice(),
+            self.subscriber_id.as_bytes(),
+        ]
+        .concat()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(Self {
+            newsletter_issue_id: Uuid::from_slice(bytes.get(..16)?).ok()?,
+            subscriber_id: Uuid::from_slice(bytes.get(16..32)?).ok()?,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct TrackingLinks {
+    base_url: String,
+    hmac_secret: Secret<String>,
+}
+
+impl TrackingLinks {
+    pub fn new(base_url: String, hmac_secret: Secret<String>) -> Self {
+        Self {
+            base_url,
+            hmac_secret,
+        }
+    }
+
+    /// The URL of the tracking pixel of the copy sent to `recipient`.
+    pub fn open_pixel_url(&self, recipient: Recipient) -> String {
+        format!(
+            "{}/o/{}",
+            self.base_url,
+            self.sign("open", &recipient.to_bytes())
+        )
+    }
+
+    /// The recipient identified by the token of a tracking pixel URL.
+    pub fn verify_open_token(&self, token: &str) -> Option<Recipient> {
+        Recipient::from_bytes(&self.verify("open", token)?)
+    }
+
+    /// A URL that records the click of `recipient` before redirecting to `url`.
+    pub fn click_url(&self, recipient: Recipient, url: &str) -> String {
+        let payload = [recipient.to_bytes().as_slice(), url.as_bytes()].concat();
+        format!("{}/r/{}", self.base_url, self.sign("click", &payload))
+    }
+
+    /// The recipient and the destination of a click redirect.
+    pub fn verify_click_token(&self, token: &str) -> Option<(Recipient, String)> {
+        let payload = self.verify("click", token)?;
+        let recipient = Recipient::from_bytes(&payload)?;
+        let url = String::from_utf8(payload.get(32..)?.to_vec()).ok()?;
+        Some((recipient, url))
+    }
+
+    /// The page where a subscriber can stop receiving issues.
+    pub fn unsubscribe_url(&self, subscriber_id: Uuid) -> String {
+        format!(
+            "{}/subscriptions/unsubscribe?token={}",
+            self.base_url,
+            self.sign("subscriber", subscriber_id.as_bytes())
+        )
+    }
+
+    /// The page where a subscriber can update their details.
+    pub fn preferences_url(&self, subscriber_id: Uuid) -> String {
+        format!(
+            "{}/subscriptions/preferences?token={}",
+            self.base_url,
+            self.sign("subscriber", subscriber_id.as_bytes())
+        )
+    }
+
+    /// The subscriber identified by the token of an unsubscribe or
+    /// preferences URL.
+    pub fn verify_subscriber_token(&self, token: &str) -> Option<Uuid> {
+        Uuid::from_slice(&self.verify("subscriber", token)?).ok()
+    }
+
+    /// The copy of an issue sent to `recipient`: links go through the click
+    /// redirector and the tracking pixel is added at the end.
+    /// Links to our own pages, e.g. to unsubscribe, are not tracked.
+    pub fn personalise_html(&self, html: &str, recipient: Recipient) -> String {
+        let rewrite =
+            |url: &str| (!url.starts_with(&self.base_url)).then(|| self.click_url(recipient, url));
+        let html = match rewrite_links(html, rewrite) {
+            Ok(html) => html,
+            Err(e) => {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to rewrite the links of an issue. They will not be tracked."
+                );
+                html.to_owned()
+            }
+        };
+        add_open_pixel(&html, &self.open_pixel_url(recipient))
+    }
+
+    fn mac(&self, purpose: &str, payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.hmac_secret.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        // Tokens issued for one purpose must not be accepted for another.
+        mac.update(purpose.as_bytes());
+        mac.update(b":");
+        mac.update(payload);
+        mac
+    }
+
+    /// `{payload}.{signature}`, both base64url-encoded.
+    fn sign(&self, purpose: &str, payload: &[u8]) -> String {
+        let signature = self.mac(purpose, payload).finalize().into_bytes();
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(&signature[..SIGNATURE_LENGTH])
+        )
+    }
+
+    fn verify(&self, purpose: &str, token: &str) -> Option<Vec<u8>> {
+        let (payload, signature) = token.split_once('.')?;
+        let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        if signature.len() != SIGNATURE_LENGTH {
+            return None;
+        }
+        self.mac(purpose, &payload)
+            .verify_truncated_left(&signature)
+            .ok()?;
+        Some(payload)
+    }
+}
+
+/// Replace the target of every link to a web page with `rewrite(target)`,
+/// unless it returns `None`. Other links (`mailto:`, anchors, relative
+/// URLs...) are left untouched.
+pub fn rewrite_links(
+    html: &str,
+    rewrite: impl Fn(&str) -> Option<String>,
+) -> Result<String, lol_html::errors::RewritingError> {
+    lol_html::rewrite_str(
+        html,
+        lol_html::RewriteStrSettings {
+            element_content_handlers: vec![lol_html::element!("a[href]", |el| {
+                let Some(href) = el.get_attribute("href") else {
+                    return Ok(());
+                };
+                // Attribute values are returned with their entities, e.g. `&amp;`.
+                let href = htmlescape::decode_html(&href).unwrap_or(href);
+                if !is_web_url(href.trim()) {
+                    return Ok(());
+                }
+                if let Some(href) = rewrite(href.trim()) {
+                    el.set_attribute("href", &href)?;
+                }
+                Ok(())
+            })],
+            ..lol_html::RewriteStrSettings::new()
+        },
+    )
+}
+
+fn is_web_url(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// Add the tracking pixel at the end of the body of an HTML document,
+/// or at the very end if it has no `</body>` tag.
+pub fn add_open_pixel(html: &str, pixel_url: &str) -> String {
+    let pixel = format!(
+        r#"<img src="{}" width="1" height="1" alt="" style="border:0">"#,
+        htmlescape::encode_minimal(pixel_url)
+    );
+    match html.to_ascii_lowercase().rfind("</body>") {
+        Some(index) => format!("{}{}{}", &html[..index], pixel, &html[index..]),
+        None => format!("{html}{pixel}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_open_pixel, rewrite_links, Recipient, TrackingLinks};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn tracking_links(secret: &str) -> TrackingLinks {
+        TrackingLinks::new("https://example.com".into(), Secret::new(secret.into()))
+    }
+
+    fn recipient() -> Recipient {
+        Recipient {
+            newsletter_issue_id: Uuid::new_v4(),
+            subscriber_id: Uuid::new_v4(),
+        }
+    }
+
+    fn token(url: &str) -> &str {
+        url.rsplit('/').next().unwrap()
+    }
+
+    #[test]
+    fn open_tokens_identify_the_recipient() {
+        let links = tracking_links("secret");
+        let recipient = recipient();
+        let url = links.open_pixel_url(recipient);
+        assert!(url.starts_with("https://example.com/o/"));
+        assert_eq!(links.verify_open_token(token(&url)), Some(recipient));
+    }
+
+    #[test]
+    fn tampered_or_foreign_tokens_are_rejected() {
+        let links = tracking_links("secret");
+        let url = links.open_pixel_url(recipient());
+        let (payload, signature) = token(&url).split_once('.').unwrap();
+
+        let other_url = links.open_pixel_url(recipient());
+        let (other_payload, _) = token(&other_url).split_once('.').unwrap();
+        assert_eq!(
+            links.verify_open_token(&format!("{other_payload}.{signature}")),
+            None
+        );
+        assert_eq!(links.verify_open_token(payload), None);
+        assert_eq!(
+            tracking_links("another secret").verify_open_token(token(&url)),
+            None
+        );
+    }
+
+    #[test]
+    fn click_tokens_carry_the_destination() {
+        let links = tracking_links("secret");
+        let recipient = recipient();
+        let url = links.click_url(recipient, "https://example.org/a?b=c&d=é");
+        assert!(url.starts_with("https://example.com/r/"));
+        assert_eq!(
+            links.verify_click_token(token(&url)),
+            Some((recipient, "https://example.org/a?b=c&d=é".to_owned()))
+        );
+        // Open and click tokens are not interchangeable.
+        assert_eq!(links.verify_open_token(token(&url)), None);
+        let pixel_url = links.open_pixel_url(recipient);
+        assert_eq!(links.verify_click_token(token(&pixel_url)), None);
+    }
+
+    #[test]
+    fn subscriber_tokens_identify_the_subscriber() {
+        let links = tracking_links("secret");
+        let recipient = recipient();
+        let url = links.unsubscribe_url(recipient.subscriber_id);
+        let (_, subscriber_token) = url.split_once("?token=").unwrap();
+        assert_eq!(
+            links.verify_subscriber_token(subscriber_token),
+            Some(recipient.subscriber_id)
+        );
+        let pixel_url = links.open_pixel_url(recipient);
+        assert_eq!(links.verify_subscriber_token(token(&pixel_url)), None);
+    }
+
+    #[test]
+    fn links_to_our_own_pages_are_not_tracked() {
+        let links = tracking_links("secret");
+        let recipient = recipient();
+        let unsubscribe_url = links.unsubscribe_url(recipient.subscriber_id);
+        let html = format!(r#"<a href="{unsubscribe_url}">Unsubscribe</a>"#);
+        let personalised = links.personalise_html(&html, recipient);
+        assert!(personalised.starts_with(&html));
+    }
+
+    #[test]
+    fn only_links_to_web_pages_are_rewritten() {
+        let html = r##"<p><a href="https://example.org/?a=1&amp;b=2">Web</a>
+            <a href="mailto:ursula@example.com">Mail</a>
+            <a href="#top">Anchor</a>
+            <a name="top">No href</a></p>"##;
+        let rewritten =
+            rewrite_links(html, |url| Some(format!("https://t/{}", url.len()))).unwrap();
+        assert_eq!(
+            rewritten,
+            r##"<p><a href="https://t/28">Web</a>
+            <a href="mailto:ursula@example.com">Mail</a>
+            <a href="#top">Anchor</a>
+            <a name="top">No href</a></p>"##
+        );
+    }
+
+    #[test]
+    fn the_pixel_goes_at_the_end_of_the_body() {
+        assert_eq!(
+            add_open_pixel("<html><BODY><p>Hi</p></BODY></html>", "https://x/o/a.b"),
+            r#"<html><BODY><p>Hi</p><img src="https://x/o/a.b" width="1" height="1" alt="" style="border:0"></BODY></html>"#
+        );
+        assert_eq!(
+            add_open_pixel("<p>Hi</p>", "https://x/o/a.b"),
+            r#"<p>Hi</p><img src="https://x/o/a.b" width="1" height="1" alt="" style="border:0">"#
+        );
+    }
+}
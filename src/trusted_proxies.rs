@@ -0,0 +1,153 @@
+//! The reverse proxies whose `X-Forwarded-For` header we believe.
+//! The header is set by the client as much as by proxies: trusting it from
+//! anybody would let a client pick the IP its login attempts are counted
+//! against.
+use std::net::IpAddr;
+
+use anyhow::Context;
+
+pub struct TrustedProxies {
+    networks: Vec<IpNetwork>,
+}
+
+/// An IP address, or a CIDR range such as `10.0.0.0/8`.
+struct IpNetwork {
+    address: IpAddr,
+    prefix_length: u32,
+}
+
+impl IpNetwork {
+    fn parse(s: &str) -> Result<Self, anyhow::Error> {
+        let (address, prefix_length) = match s.split_once('/') {
+            Some((address, prefix_length)) => (address, Some(prefix_length)),
+            None => (s, None),
+        };
+        let address: IpAddr = address.trim().parse()?;
+        let max_prefix_length = if address.is_ipv4() { 32 } else { 128 };
+        let prefix_length = match prefix_length {
+            Some(prefix_length) => prefix_length.trim().parse()?,
+            None => max_prefix_length,
+        };
+        anyhow::ensure!(
+            prefix_length <= max_prefix_length,
+            "The prefix length is too long."
+        );
+        Ok(Self {
+            address,
+            prefix_length,
+        })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        // Clients of an IPv6 socket show up as IPv4-mapped addresses.
+        let ip = match ip {
+            IpAddr::V6(ip) => ip
+                .to_ipv4_mapped()
+                .map(IpAddr::V4)
+                .unwrap_or(IpAddr::V6(ip)),
+            ip => ip,
+        };
+        match (self.address, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_length).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_length).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl TrustedProxies {
+    pub fn new(networks: &[String]) -> Result<Self, anyhow::Error> {
+        let networks = networks
+            .iter()
+            .map(|network| {
+                IpNetwork::parse(network).with_context(|| {
+                    format!("The trusted proxy {network} is not an IP address or a CIDR range.")
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { networks })
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.networks.iter().any(|network| network.contains(ip))
+    }
+
+    /// The IP of the client, given the IP of the peer we are connected to and
+    /// the `X-Forwarded-For` header of the request.
+    /// Every proxy appends the address it got the request from to the header:
+    /// the client is the rightmost address that is not one of our proxies.
+    /// Anything to its left was set by the client itself.
+    pub fn client_ip(&self, peer_ip: Option<IpAddr>, forwarded_for: Option<&str>) -> String {
+        let Some(mut client_ip) = peer_ip else {
+            return "unknown".into();
+        };
+        if let Some(forwarded_for) = forwarded_for {
+            for hop in forwarded_for.rsplit(',') {
+                if !self.is_trusted(client_ip) {
+                    break;
+                }
+                match hop.trim().parse() {
+                    Ok(ip) => client_ip = ip,
+                    // Garbage: whoever wrote it is as far as we can go.
+                    Err(_) => break,
+                }
+            }
+        }
+        client_ip.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrustedProxies;
+    use std::net::IpAddr;
+
+    fn proxies() -> TrustedProxies {
+        TrustedProxies::new(&["10.0.0.0/8".into(), "192.168.1.1".into()]).unwrap()
+    }
+
+    fn ip(s: &str) -> Option<IpAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    #[test]
+    fn the_header_is_ignored_when_the_peer_is_not_a_proxy() {
+        assert_eq!(
+            proxies().client_ip(ip("203.0.113.7"), Some("198.51.100.1")),
+            "203.0.113.7"
+        );
+    }
+
+    #[test]
+    fn the_header_is_honoured_when_the_peer_is_a_proxy() {
+        assert_eq!(
+            proxies().client_ip(ip("10.1.2.3"), Some("198.51.100.1")),
+            "198.51.100.1"
+        );
+    }
+
+    #[test]
+    fn addresses_set_by_the_client_are_ignored() {
+        assert_eq!(
+            proxies().client_ip(ip("10.1.2.3"), Some("1.1.1.1, 198.51.100.1, 192.168.1.1")),
+            "198.51.100.1"
+        );
+    }
+
+    #[test]
+    fn the_peer_is_used_without_a_header() {
+        assert_eq!(proxies().client_ip(ip("10.1.2.3"), None), "10.1.2.3");
+    }
+
+    #[test]
+    fn invalid_proxies_are_rejected() {
+        assert!(TrustedProxies::new(&["10.0.0.0/33".into()]).is_err());
+        assert!(TrustedProxies::new(&["localhost".into()]).is_err());
+    }
+}
@@ -1,4 +1,9 @@
-use actix_web::{http::header::LOCATION, HttpResponse};
+use actix_web::{
+    http::header::{LOCATION, USER_AGENT},
+    web, HttpRequest, HttpResponse,
+};
+
+use crate::trusted_proxies::TrustedProxies;
 
 /// Return an opaque 500 while preserving the error root cause for logging
 pub fn e500<T>(e: T) -> actix_web::Error
@@ -22,3 +27,33 @@ where
 {
     actix_web::error::ErrorBadRequest(e)
 }
+
+pub fn e404<T>(e: T) -> actix_web::Error
+where
+    T: std::fmt::Debug + std::fmt::Display + 'static,
+{
+    actix_web::error::ErrorNotFound(e)
+}
+
+/// The IP address of the client.
+/// `X-Forwarded-For` is only honoured when the request comes from one of
+/// the configured trusted proxies.
+pub fn client_ip(request: &HttpRequest) -> String {
+    let trusted_proxies = request
+        .app_data::<web::Data<TrustedProxies>>()
+        .expect("The trusted proxies are not registered as application data.");
+    let forwarded_for = request
+        .headers()
+        .get("X-Forwarded-For")
+        .and_then(|value| value.to_str().ok());
+    trusted_proxies.client_ip(request.peer_addr().map(|addr| addr.ip()), forwarded_for)
+}
+
+pub fn user_agent(request: &HttpRequest) -> String {
+    request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("unknown")
+        .to_owned()
+}
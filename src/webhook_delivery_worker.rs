@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::Utc;
+use secrecy::Secret;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use tracing::{field::display, Span};
+use uuid::Uuid;
+
+use crate::configuration::{Settings, WebhookSettings};
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::startup::get_connection_pool;
+use crate::webhooks::signature_header;
+
+pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    let http_client = webhook_http_client(&configuration.webhooks);
+
+    worker_loop(connection_pool, http_client, configuration.webhooks).await
+}
+
+pub fn webhook_http_client(settings: &WebhookSettings) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(settings.timeout())
+        // An endpoint could otherwise send our signed payloads anywhere,
+        // including to services of our own network.
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to build the webhook HTTP client.")
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    http_client: reqwest::Client,
+    settings: WebhookSettings,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_execute_task(&pool, &http_client, &settings).await {
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+            Ok(ExecutionOutcome::EmptyQueue) => tokio::time::sleep(Duration::from_secs(10)).await,
+            Err(_) => tokio::time::sleep(Duration::from_secs(1)).await,
+        }
+    }
+}
+
+struct Task {
+    event_id: Uuid,
+    endpoint_id: Uuid,
+    n_attempts: i16,
+    url: String,
+    secret: String,
+    payload: String,
+}
+
+#[tracing::instrument(skip_all, fields(event_id = tracing::field::Empty, endpoint_id = tracing::field::Empty))]
+pub async fn try_execute_task(
+    pool: &PgPool,
+    http_client: &reqwest::Client,
+    settings: &WebhookSettings,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let Some((transaction, task)) = dequeue_task(pool).await? else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current()
+        .record("event_id", display(task.event_id))
+        .record("endpoint_id", display(task.endpoint_id));
+
+    let timestamp = Utc::now().timestamp();
+    let outcome = http_client
+        .post(&task.url)
+        .header("Content-Type", "application/json")
+        .header("Webhook-Id", task.event_id.to_string())
+        .header(
+            "Webhook-Signature",
+            signature_header(&Secret::new(task.secret.clone()), timestamp, &task.payload),
+        )
+        .body(task.payload.clone())
+        .send()
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|response| {
+            // Redirects are not followed: they are failures like any other.
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!(
+                    "The endpoint answered {}.",
+                    response.status()
+                ))
+            }
+        });
+
+    match outcome {
+        Ok(_) => delete_task(transaction, &task).await?,
+        Err(e) => {
+            let n_attempts = task.n_attempts as u32 + 1;
+            if n_attempts >= settings.max_attempts {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to deliver a webhook event {} times. Giving up.",
+                    n_attempts
+                );
+                delete_task(transaction, &task).await?;
+            } else {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to deliver a webhook event. It will be retried."
+                );
+                postpone_task(transaction, &task, settings.retry_delay(n_attempts)).await?;
+            }
+        }
+    }
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+type PgTransaction = Transaction<'static, Postgres>;
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_task(pool: &PgPool) -> Result<Option<(PgTransaction, Task)>, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let task = sqlx::query_as!(
+        Task,
+        r#"
+        SELECT o.event_id, o.endpoint_id, o.n_attempts, e.url, e.secret, ev.payload
+        FROM webhook_outbox o
+        JOIN webhook_endpoints e ON e.endpoint_id = o.endpoint_id
+        JOIN webhook_events ev ON ev.event_id = o.event_id
+        WHERE o.execute_after <= now()
+        ORDER BY o.execute_after
+        FOR UPDATE OF o
+        SKIP LOCKED
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+    Ok(task.map(|task| (transaction, task)))
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_task(mut transaction: PgTransaction, task: &Task) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM webhook_outbox
+        WHERE
+            event_id = $1 AND
+            endpoint_id = $2
+        "#,
+        task.event_id,
+        task.endpoint_id
+    );
+    transaction.execute(query).await?;
+    // The event is no longer needed once every endpoint is done with it.
+    // Locking it first makes sure that, of two workers completing its last
+    // deliveries at the same time, the second sees the work of the first.
+    let query = sqlx::query!(
+        r#"SELECT event_id FROM webhook_events WHERE event_id = $1 FOR UPDATE"#,
+        task.event_id
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM webhook_events
+        WHERE
+            event_id = $1 AND
+            NOT EXISTS (SELECT 1 FROM webhook_outbox WHERE event_id = $1)
+        "#,
+        task.event_id
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn postpone_task(
+    mut transaction: PgTransaction,
+    task: &Task,
+    delay: chrono::Duration,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE webhook_outbox
+        SET
+            n_attempts = n_attempts + 1,
+            execute_after = $3
+        WHERE
+            event_id = $1 AND
+            endpoint_id = $2
+        "#,
+        task.event_id,
+        task.endpoint_id,
+        Utc::now() + delay
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to postpone a webhook delivery.")?;
+    transaction.commit().await?;
+
+    Ok(())
+}
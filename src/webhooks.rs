@@ -0,0 +1,262 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// Something that happened, which the registered endpoints are notified of.
+#[derive(serde::Serialize, Debug)]
+#[serde(tag = "type", content = "data")]
+pub enum WebhookEvent {
+    #[serde(rename = "subscriber.created")]
+    SubscriberCreated {
+        subscriber_id: Uuid,
+        email: String,
+        name: String,
+    },
+    #[serde(rename = "subscriber.confirmed")]
+    SubscriberConfirmed { subscriber_id: Uuid, email: String },
+    #[serde(rename = "subscriber.unsubscribed")]
+    SubscriberUnsubscribed { subscriber_id: Uuid, email: String },
+    #[serde(rename = "subscriber.bounced")]
+    SubscriberBounced { subscriber_id: Uuid, email: String },
+    #[serde(rename = "newsletter_issue.delivered")]
+    NewsletterIssueDelivered {
+        newsletter_issue_id: Uuid,
+        title: String,
+        recipients: i32,
+    },
+}
+
+impl WebhookEvent {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            WebhookEvent::SubscriberCreated { .. } => "subscriber.created",
+            WebhookEvent::SubscriberConfirmed { .. } => "subscriber.confirmed",
+            WebhookEvent::SubscriberUnsubscribed { .. } => "subscriber.unsubscribed",
+            WebhookEvent::SubscriberBounced { .. } => "subscriber.bounced",
+            WebhookEvent::NewsletterIssueDelivered { .. } => "newsletter_issue.delivered",
+        }
+    }
+}
+
+/// The JSON body posted to the endpoints.
+#[derive(serde::Serialize)]
+struct Payload<'a> {
+    id: Uuid,
+    created_at: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+}
+
+/// Record the event in the outbox of every registered endpoint.
+/// It must be called in the transaction of the change that triggered the
+/// event: the event is then stored if and only if the change is.
+/// Nothing is stored when no endpoint is registered.
+#[tracing::instrument(name = "Enqueue webhook event", skip(transaction))]
+pub async fn enqueue_webhook_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    event: &WebhookEvent,
+) -> Result<(), sqlx::Error> {
+    let has_endpoints =
+        sqlx::query!(r#"SELECT EXISTS (SELECT 1 FROM webhook_endpoints) AS "exists!""#)
+            .fetch_one(&mut **transaction)
+            .await?
+            .exists;
+    if !has_endpoints {
+        return Ok(());
+    }
+    let event_id = Uuid::new_v4();
+    let created_at = Utc::now();
+    let payload = serde_json::to_string(&Payload {
+        id: event_id,
+        created_at,
+        event,
+    })
+    .expect("Webhook events are always serialisable.");
+
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            INSERT INTO webhook_events (event_id, event_type, payload, created_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            event_id,
+            event.event_type(),
+            payload,
+            created_at
+        ))
+        .await?;
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            INSERT INTO webhook_outbox (event_id, endpoint_id)
+            SELECT $1, endpoint_id
+            FROM webhook_endpoints
+            "#,
+            event_id
+        ))
+        .await?;
+    Ok(())
+}
+
+/// The value of the `Webhook-Signature` header: the HMAC-SHA256 of
+/// `{timestamp}.{body}`, keyed with the secret of the endpoint.
+/// Signing the timestamp lets receivers reject replayed requests.
+pub fn signature_header(secret: &Secret<String>, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+    format!(
+        "t={timestamp},v1={}",
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+pub struct WebhookEndpoint {
+    pub endpoint_id: Uuid,
+    pub url: String,
+    pub created_at: DateTime<Utc>,
+    /// Events that have not been delivered yet, including those being retried.
+    pub n_pending: i64,
+}
+
+/// Check that `url` is somewhere we can post events to.
+pub fn parse_endpoint_url(url: &str) -> Result<reqwest::Url, String> {
+    let parsed =
+        reqwest::Url::parse(url.trim()).map_err(|_| format!("{url} is not a valid URL."))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("{url} is not an HTTP URL."));
+    }
+    Ok(parsed)
+}
+
+/// Register an endpoint and return its id and signing secret.
+#[tracing::instrument(name = "Create webhook endpoint", skip(pool))]
+pub async fn create_webhook_endpoint(
+    url: &reqwest::Url,
+    pool: &PgPool,
+) -> Result<(Uuid, Secret<String>), anyhow::Error> {
+    let endpoint_id = Uuid::new_v4();
+    let secret = generate_secret();
+    sqlx::query!(
+        r#"
+        INSERT INTO webhook_endpoints (endpoint_id, url, secret, created_at)
+        VALUES ($1, $2, $3, now())
+        "#,
+        endpoint_id,
+        url.as_str(),
+        secret.expose_secret()
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the webhook endpoint.")?;
+    Ok((endpoint_id, secret))
+}
+
+#[tracing::instrument(name = "List webhook endpoints", skip(pool))]
+pub async fn list_webhook_endpoints(pool: &PgPool) -> Result<Vec<WebhookEndpoint>, anyhow::Error> {
+    let endpoints = sqlx::query_as!(
+        WebhookEndpoint,
+        r#"
+        SELECT
+            endpoint_id,
+            url,
+            created_at,
+            (
+                SELECT COUNT(*)
+                FROM webhook_outbox o
+                WHERE o.endpoint_id = e.endpoint_id
+            ) AS "n_pending!"
+        FROM webhook_endpoints e
+        ORDER BY created_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the webhook endpoints.")?;
+    Ok(endpoints)
+}
+
+/// Remove an endpoint, along with the events it has not received yet.
+#[tracing::instrument(name = "Delete webhook endpoint", skip(pool))]
+pub async fn delete_webhook_endpoint(
+    endpoint_id: Uuid,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM webhook_endpoints WHERE endpoint_id = $1"#,
+            endpoint_id
+        ))
+        .await
+        .context("Failed to delete the webhook endpoint.")?;
+    // Events no other endpoint is waiting for.
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            DELETE FROM webhook_events ev
+            WHERE NOT EXISTS (
+                SELECT 1 FROM webhook_outbox o WHERE o.event_id = ev.event_id
+            )
+            "#
+        ))
+        .await
+        .context("Failed to delete the events of the webhook endpoint.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the deletion of the webhook endpoint.")?;
+    Ok(())
+}
+
+fn generate_secret() -> Secret<String> {
+    let mut rng = thread_rng();
+    let random: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect();
+    Secret::new(format!("whsec_{random}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_endpoint_url, signature_header, WebhookEvent};
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    #[test]
+    fn events_are_tagged_with_their_type() {
+        let event = WebhookEvent::SubscriberConfirmed {
+            subscriber_id: uuid::Uuid::nil(),
+            email: "ursula@example.com".into(),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], event.event_type());
+        assert_eq!(json["data"]["email"], "ursula@example.com");
+    }
+
+    #[test]
+    fn signatures_match_a_known_hmac() {
+        // echo -n '1700000000.{}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            signature_header(&Secret::new("secret".into()), 1700000000, "{}"),
+            "t=1700000000,v1=b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+        );
+    }
+
+    #[test]
+    fn only_http_urls_are_accepted() {
+        assert_ok!(parse_endpoint_url("https://crm.example.com/hooks"));
+        assert_ok!(parse_endpoint_url("http://localhost:8080"));
+        assert_err!(parse_endpoint_url("ftp://example.com"));
+        assert_err!(parse_endpoint_url("not a url"));
+    }
+}
@@ -0,0 +1,86 @@
+//! The email sent to subscribers once they confirm their subscription,
+//! written from the admin UI. It goes through the delivery queue, like
+//! newsletter issues: confirming stays fast, and failures are retried.
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+
+pub struct WelcomeEmail {
+    /// Disabled welcome emails are kept, but never sent.
+    pub enabled: bool,
+    pub subject: String,
+    /// Templates with merge tags, like the content of issues.
+    pub html_content: String,
+    pub text_content: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// `None` until the welcome email is saved for the first time.
+#[tracing::instrument(name = "Get the welcome email", skip(executor))]
+pub async fn get_welcome_email(
+    executor: impl PgExecutor<'_>,
+) -> Result<Option<WelcomeEmail>, anyhow::Error> {
+    let welcome_email = sqlx::query_as!(
+        WelcomeEmail,
+        r#"
+        SELECT enabled, subject, html_content, text_content, updated_at
+        FROM welcome_email
+        "#
+    )
+    .fetch_optional(executor)
+    .await
+    .context("Failed to fetch the welcome email.")?;
+    Ok(welcome_email)
+}
+
+#[tracing::instrument(
+    name = "Save the welcome email",
+    skip(html_content, text_content, pool)
+)]
+pub async fn save_welcome_email(
+    enabled: bool,
+    subject: &str,
+    html_content: &str,
+    text_content: &str,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO welcome_email (enabled, subject, html_content, text_content, updated_at)
+        VALUES ($1, $2, $3, $4, now())
+        ON CONFLICT (id) DO UPDATE SET
+            enabled = EXCLUDED.enabled,
+            subject = EXCLUDED.subject,
+            html_content = EXCLUDED.html_content,
+            text_content = EXCLUDED.text_content,
+            updated_at = EXCLUDED.updated_at
+        "#,
+        enabled,
+        subject,
+        html_content,
+        text_content
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the welcome email.")?;
+    Ok(())
+}
+
+/// Queue the welcome email for a subscriber who just confirmed, if it is
+/// enabled. The content is read when the email is sent.
+#[tracing::instrument(name = "Enqueue the welcome email", skip(transaction))]
+pub async fn enqueue_welcome_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_email: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (subscriber_email)
+        SELECT $1 FROM welcome_email WHERE enabled
+        "#,
+        subscriber_email
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,232 @@
+use uuid::Uuid;
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{client_without_session, create_confirmed_subscriber, spawn_app, TestApp};
+
+fn issue_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    })
+}
+
+async fn post_newsletter(
+    app: &TestApp,
+    token: &str,
+    body: &serde_json::Value,
+    idempotency_key: Option<&str>,
+) -> reqwest::Response {
+    let mut request = client_without_session()
+        .post(format!("{}/api/v1/newsletters", &app.address))
+        .bearer_auth(token)
+        .json(body);
+    if let Some(idempotency_key) = idempotency_key {
+        request = request.header("Idempotency-Key", idempotency_key);
+    }
+    request.send().await.expect("Failed to execute request")
+}
+
+async fn get_newsletter(app: &TestApp, token: &str, path: &str) -> reqwest::Response {
+    client_without_session()
+        .get(format!("{}/api/v1/newsletters{}", &app.address, path))
+        .bearer_auth(token)
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+async fn assert_is_problem(response: reqwest::Response, status: u16) -> serde_json::Value {
+    assert_eq!(response.status().as_u16(), status);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["status"], status);
+    assert_eq!(problem["type"], "about:blank");
+    problem
+}
+
+#[tokio::test]
+async fn the_api_requires_an_api_token() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - A browser session is not enough
+    let response = app
+        .api_client
+        .get(format!("{}/api/v1/newsletters", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+    let problem = assert_is_problem(response, 401).await;
+    assert_eq!(problem["detail"], "An API token is required.");
+}
+
+#[tokio::test]
+async fn reading_newsletters_requires_the_read_scope() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+
+    // Act
+    let response = get_newsletter(&app, &token, "").await;
+
+    // Assert
+    let problem = assert_is_problem(response, 403).await;
+    assert_eq!(
+        problem["detail"],
+        "The API token is missing the `newsletters:read` scope."
+    );
+}
+
+#[tokio::test]
+async fn a_published_issue_reports_its_delivery_status() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let token = app
+        .create_api_token(&["newsletters:publish", "newsletters:read"])
+        .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Publish
+    let response = post_newsletter(&app, &token, &issue_body(), None).await;
+    assert_eq!(response.status().as_u16(), 202);
+    let location = response.headers()["Location"].to_str().unwrap().to_owned();
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(issue["title"], "Newsletter title");
+    assert!(chrono::DateTime::parse_from_rfc3339(issue["published_at"].as_str().unwrap()).is_ok());
+    assert_eq!(issue["delivery"]["status"], "in_progress");
+    assert_eq!(issue["delivery"]["recipients"], 1);
+    assert_eq!(issue["delivery"]["pending"], 1);
+    let issue_id = issue["newsletter_issue_id"].as_str().unwrap();
+    assert_eq!(location, format!("/api/v1/newsletters/{issue_id}"));
+
+    // Act - Part 2 - Deliver and fetch the issue again
+    app.dispatch_all_pending_emails().await;
+    let response = get_newsletter(&app, &token, &format!("/{issue_id}")).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(issue["content"]["html"], "<p>Newsletter body as HTML</p>");
+    assert_eq!(issue["delivery"]["status"], "completed");
+    assert_eq!(issue["delivery"]["pending"], 0);
+
+    let response = get_newsletter(&app, &token, "").await;
+    let issues: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(issues["newsletter_issues"].as_array().unwrap().len(), 1);
+    assert_eq!(
+        issues["newsletter_issues"][0]["newsletter_issue_id"],
+        issue_id
+    );
+}
+
+#[tokio::test]
+async fn publishing_is_idempotent_with_an_idempotency_key() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    // Act - Submit the same issue twice
+    let first = post_newsletter(&app, &token, &issue_body(), Some(&idempotency_key)).await;
+    let second = post_newsletter(&app, &token, &issue_body(), Some(&idempotency_key)).await;
+
+    // Assert
+    assert_eq!(first.status().as_u16(), 202);
+    assert_eq!(second.status().as_u16(), 202);
+    assert_eq!(
+        first.json::<serde_json::Value>().await.unwrap(),
+        second.json::<serde_json::Value>().await.unwrap()
+    );
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that we have sent the newsletter email **once**
+}
+
+#[tokio::test]
+async fn invalid_issues_are_rejected_with_a_problem_document() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+    let test_cases = vec![
+        (
+            serde_json::json!({
+                "title": "",
+                "content": { "text": "text", "html": "<p>html</p>" }
+            }),
+            "empty title",
+        ),
+        (
+            serde_json::json!({
+                "title": "Newsletter title",
+                "content": { "text": "", "html": "<p>html</p>" }
+            }),
+            "empty text content",
+        ),
+        (
+            serde_json::json!({
+                "title": "Newsletter title",
+                "content": { "text": "text", "html": "<p>{% if name %}html</p>" }
+            }),
+            "a broken template",
+        ),
+        (
+            serde_json::json!({ "title": "Newsletter title" }),
+            "missing content",
+        ),
+    ];
+
+    for (body, description) in test_cases {
+        // Act
+        let response = post_newsletter(&app, &token, &body, None).await;
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request when the payload had {}.",
+            description
+        );
+        assert_is_problem(response, 400).await;
+    }
+}
+
+#[tokio::test]
+async fn an_unknown_issue_is_not_found() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:read"]).await;
+
+    for path in [format!("/{}", Uuid::new_v4()), "/not-an-id".to_string()] {
+        // Act
+        let response = get_newsletter(&app, &token, &path).await;
+
+        // Assert
+        assert_is_problem(response, 404).await;
+    }
+}
@@ -0,0 +1,375 @@
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{client_without_session, spawn_app, TestApp};
+
+const ALL_SCOPES: [&str; 2] = ["subscribers:read", "subscribers:write"];
+
+async fn post_subscriber(
+    app: &TestApp,
+    token: &str,
+    body: &serde_json::Value,
+) -> reqwest::Response {
+    client_without_session()
+        .post(format!("{}/api/v1/subscribers", &app.address))
+        .bearer_auth(token)
+        .json(body)
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+async fn get_subscribers(app: &TestApp, token: &str, path_and_query: &str) -> reqwest::Response {
+    client_without_session()
+        .get(format!(
+            "{}/api/v1/subscribers{}",
+            &app.address, path_and_query
+        ))
+        .bearer_auth(token)
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+async fn create_subscriber(app: &TestApp, token: &str, email: &str) -> serde_json::Value {
+    let response = post_subscriber(
+        app,
+        token,
+        &serde_json::json!({ "email": email, "name": "le guin", "confirmed": true }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 201);
+    response.json().await.unwrap()
+}
+
+async fn assert_is_problem(response: reqwest::Response, status: u16) -> serde_json::Value {
+    assert_eq!(response.status().as_u16(), status);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    response.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn a_subscriber_created_through_the_api_must_confirm_by_default() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&ALL_SCOPES).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = post_subscriber(
+        &app,
+        &token,
+        &serde_json::json!({ "email": "ursula_le_guin@gmail.com", "name": "le guin" }),
+    )
+    .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 201);
+    let location = response.headers()["Location"].to_str().unwrap().to_owned();
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscriber["status"], "pending_confirmation");
+    assert_eq!(
+        location,
+        format!("/api/v1/subscribers/{}", subscriber["id"].as_str().unwrap())
+    );
+}
+
+#[tokio::test]
+async fn a_pre_confirmed_subscriber_gets_no_confirmation_email() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&ALL_SCOPES).await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let subscriber = create_subscriber(&app, &token, "ursula_le_guin@gmail.com").await;
+
+    // Assert
+    assert_eq!(subscriber["status"], "confirmed");
+    let saved = sqlx::query!("SELECT email, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn invalid_subscribers_are_rejected_with_a_problem_document() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&ALL_SCOPES).await;
+    let test_cases = vec![
+        (
+            serde_json::json!({ "email": "ursula_le_guin@gmail.com", "name": "" }),
+            "empty name",
+        ),
+        (
+            serde_json::json!({ "email": "definitely-not-an-email", "name": "Ursula" }),
+            "invalid email",
+        ),
+        (serde_json::json!({ "name": "Ursula" }), "missing email"),
+    ];
+
+    for (body, description) in test_cases {
+        // Act
+        let response = post_subscriber(&app, &token, &body).await;
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not return a 400 Bad Request when the payload had {}.",
+            description
+        );
+        let problem = assert_is_problem(response, 400).await;
+        assert_eq!(problem["status"], 400);
+    }
+}
+
+#[tokio::test]
+async fn an_email_can_only_be_subscribed_once() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&ALL_SCOPES).await;
+    create_subscriber(&app, &token, "ursula_le_guin@gmail.com").await;
+
+    // Act
+    let response = post_subscriber(
+        &app,
+        &token,
+        &serde_json::json!({ "email": "ursula_le_guin@gmail.com", "name": "Ursula", "confirmed": true }),
+    )
+    .await;
+
+    // Assert
+    let problem = assert_is_problem(response, 409).await;
+    assert_eq!(
+        problem["detail"],
+        "ursula_le_guin@gmail.com is already subscribed."
+    );
+}
+
+#[tokio::test]
+async fn subscribers_are_listed_page_by_page() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&ALL_SCOPES).await;
+    let mut created = Vec::new();
+    for i in 0..5 {
+        let subscriber = create_subscriber(&app, &token, &format!("reader{i}@example.com")).await;
+        created.push(subscriber["id"].as_str().unwrap().to_owned());
+    }
+
+    // Act - Walk through the pages
+    let mut listed = Vec::new();
+    let mut query = "?limit=2".to_string();
+    let mut n_pages = 0;
+    loop {
+        let response = get_subscribers(&app, &token, &query).await;
+        assert_eq!(response.status().as_u16(), 200);
+        let page: serde_json::Value = response.json().await.unwrap();
+        n_pages += 1;
+        for subscriber in page["subscribers"].as_array().unwrap() {
+            listed.push(subscriber["id"].as_str().unwrap().to_owned());
+        }
+        match page["next_cursor"].as_str() {
+            Some(cursor) => query = format!("?limit=2&cursor={cursor}"),
+            None => break,
+        }
+    }
+
+    // Assert
+    assert_eq!(n_pages, 3);
+    assert_eq!(listed, created);
+}
+
+#[tokio::test]
+async fn invalid_pagination_parameters_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&ALL_SCOPES).await;
+
+    for query in ["?limit=0", "?limit=1000", "?cursor=garbage", "?limit=many"] {
+        // Act
+        let response = get_subscribers(&app, &token, query).await;
+
+        // Assert
+        assert_is_problem(response, 400).await;
+    }
+}
+
+#[tokio::test]
+async fn a_subscriber_can_be_updated() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&ALL_SCOPES).await;
+    let subscriber = create_subscriber(&app, &token, "ursula_le_guin@gmail.com").await;
+    let id = subscriber["id"].as_str().unwrap();
+
+    // Act
+    let response = client_without_session()
+        .patch(format!("{}/api/v1/subscribers/{id}", &app.address))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "name": "Ursula K. Le Guin", "status": "pending_confirmation" }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let response = get_subscribers(&app, &token, &format!("/{id}")).await;
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscriber["name"], "Ursula K. Le Guin");
+    assert_eq!(subscriber["status"], "pending_confirmation");
+    assert_eq!(subscriber["email"], "ursula_le_guin@gmail.com");
+}
+
+#[tokio::test]
+async fn an_update_is_validated() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&ALL_SCOPES).await;
+    let subscriber = create_subscriber(&app, &token, "ursula_le_guin@gmail.com").await;
+    let id = subscriber["id"].as_str().unwrap();
+
+    for body in [
+        serde_json::json!({ "name": "<script>" }),
+        serde_json::json!({ "status": "banned" }),
+    ] {
+        // Act
+        let response = client_without_session()
+            .patch(format!("{}/api/v1/subscribers/{id}", &app.address))
+            .bearer_auth(&token)
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+
+        // Assert
+        assert_is_problem(response, 400).await;
+    }
+}
+
+#[tokio::test]
+async fn the_api_cannot_suppress_a_subscriber_or_lift_a_suppression() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&ALL_SCOPES).await;
+    let subscriber = create_subscriber(&app, &token, "ursula_le_guin@gmail.com").await;
+    let id = subscriber["id"].as_str().unwrap();
+    let patch = |status: &str| {
+        client_without_session()
+            .patch(format!("{}/api/v1/subscribers/{id}", &app.address))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({ "status": status }))
+            .send()
+    };
+
+    // Act - Part 1 - Suppress the subscriber
+    let response = patch("suppressed").await.unwrap();
+    assert_is_problem(response, 422).await;
+
+    // Act - Part 2 - Lift a suppression reported by the email provider
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'suppressed' WHERE id = $1",
+        Uuid::parse_str(id).unwrap()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let response = patch("confirmed").await.unwrap();
+
+    // Assert
+    let problem = assert_is_problem(response, 422).await;
+    assert_eq!(
+        problem["detail"],
+        "The status of a subscriber cannot be changed from suppressed to confirmed."
+    );
+    let response = get_subscribers(&app, &token, &format!("/{id}")).await;
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscriber["status"], "suppressed");
+}
+
+#[tokio::test]
+async fn a_deleted_subscriber_is_gone() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&ALL_SCOPES).await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    // Pending subscribers have a confirmation token referencing them.
+    let response = post_subscriber(
+        &app,
+        &token,
+        &serde_json::json!({ "email": "ursula_le_guin@gmail.com", "name": "le guin" }),
+    )
+    .await;
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    let id = subscriber["id"].as_str().unwrap();
+
+    // Act
+    let response = client_without_session()
+        .delete(format!("{}/api/v1/subscribers/{id}", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 204);
+    let response = get_subscribers(&app, &token, &format!("/{id}")).await;
+    assert_is_problem(response, 404).await;
+}
+
+#[tokio::test]
+async fn a_read_only_token_cannot_change_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["subscribers:read"]).await;
+
+    // Act
+    let response = client_without_session()
+        .delete(format!(
+            "{}/api/v1/subscribers/{}",
+            &app.address,
+            Uuid::new_v4()
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    let problem = assert_is_problem(response, 403).await;
+    assert_eq!(
+        problem["detail"],
+        "The API token is missing the `subscribers:write` scope."
+    );
+}
@@ -0,0 +1,192 @@
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{
+    assert_is_redirect_to, client_without_session, create_confirmed_subscriber, spawn_app, TestApp,
+};
+
+async fn publish_newsletter_with_token(app: &TestApp, token: &str) -> reqwest::Response {
+    client_without_session()
+        .post(format!("{}/admin/newsletters", &app.address))
+        .bearer_auth(token)
+        .form(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_api_tokens() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_api_tokens().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_new_token_is_shown_once_and_stored_hashed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+
+    // Assert
+    assert!(token.starts_with("z2p_"));
+    let stored = sqlx::query!("SELECT name, token_hash, scopes FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(stored.name, "Test token");
+    assert_eq!(stored.scopes, vec!["newsletters:publish".to_string()]);
+    assert_eq!(
+        stored.token_hash,
+        hex::encode(Sha256::digest(token.as_bytes()))
+    );
+
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("Test token"));
+    assert!(!html_page.contains(&token));
+}
+
+#[tokio::test]
+async fn a_token_needs_a_name_and_a_scope() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let test_cases = vec![
+        (
+            vec![("name", ""), ("scope", "newsletters:publish")],
+            "The token must have a name.",
+        ),
+        (
+            vec![("name", "CI")],
+            "The token must have at least one scope.",
+        ),
+        (
+            vec![("name", "CI"), ("scope", "everything")],
+            "Unknown scope: everything.",
+        ),
+        (
+            vec![("name", "CI"), ("scope", "<script>alert(1)</script>")],
+            "Unknown scope: &lt;script&gt;alert(1)&lt;/script&gt;.",
+        ),
+    ];
+
+    for (body, error_message) in test_cases {
+        // Act
+        let response = app.post_create_api_token(&body).await;
+
+        // Assert
+        assert_is_redirect_to(&response, "/admin/api-tokens");
+        let html_page = app.get_api_tokens_html().await;
+        assert!(html_page.contains(&format!("<p><i>{}</i></p>", error_message)));
+    }
+    let count = sqlx::query_scalar!("SELECT COUNT(*) FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(count, Some(0));
+}
+
+#[tokio::test]
+async fn a_scoped_token_can_publish_a_newsletter_without_a_session() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = publish_newsletter_with_token(&app, &token).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+    let last_used_at = sqlx::query_scalar!("SELECT last_used_at FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(last_used_at.is_some());
+}
+
+#[tokio::test]
+async fn an_invalid_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = publish_newsletter_with_token(&app, "z2p_not-a-real-token").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+}
+
+#[tokio::test]
+async fn a_token_cannot_be_used_outside_of_its_scopes() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+
+    // Act
+    let response = client_without_session()
+        .post(format!("{}/admin/password", &app.address))
+        .bearer_auth(&token)
+        .form(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": "a brand new password",
+            "new_password_check": "a brand new password",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn a_revoked_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+    let token_id = sqlx::query_scalar!("SELECT token_id FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act - Part 1 - Revoke the token
+    let response = app
+        .post_revoke_api_token(&serde_json::json!({ "token_id": token_id }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("<p><i>The API token has been revoked.</i></p>"));
+    assert!(!html_page.contains(&token_id.to_string()));
+
+    // Act - Part 2 - Try to use it
+    let response = publish_newsletter_with_token(&app, &token).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,237 @@
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, publish_issue, spawn_app};
+
+#[tokio::test]
+async fn archived_issues_are_listed_and_rendered_for_any_reader() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_issue(&app, "First issue", serde_json::json!({})).await;
+    publish_issue(&app, "Second issue!", serde_json::json!({})).await;
+
+    // Act - Part 1 - List the issues
+    let response = app.get_archive(1).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    let second = html_page
+        .find(r#"<a href="/archive/second-issue">Second issue!</a>"#)
+        .unwrap();
+    let first = html_page
+        .find(r#"<a href="/archive/first-issue">First issue</a>"#)
+        .unwrap();
+    assert!(second < first, "The most recent issue comes first");
+
+    // Act - Part 2 - Read an issue
+    let response = app.get_archived_issue("second-issue").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h1>Second issue!</h1>"));
+    // Subscription links lead to the home page.
+    assert!(html_page.contains(
+        r#"<p>Hi <strong>reader</strong>, <a href="http://127.0.0.1/">unsubscribe</a></p>"#
+    ));
+}
+
+#[tokio::test]
+async fn issues_with_the_same_title_get_distinct_slugs() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    for _ in 0..3 {
+        publish_issue(&app, "Weekly news", serde_json::json!({})).await;
+    }
+
+    // Assert
+    let slugs: Vec<String> =
+        sqlx::query_scalar!("SELECT slug FROM newsletter_issues ORDER BY published_at")
+            .fetch_all(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(slugs, ["weekly-news", "weekly-news-2", "weekly-news-3"]);
+    for slug in slugs {
+        assert_eq!(app.get_archived_issue(&slug).await.status().as_u16(), 200);
+    }
+}
+
+#[tokio::test]
+async fn concurrent_issues_with_the_same_title_get_distinct_slugs() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let body = |key: &str| {
+        serde_json::json!({
+            "title": "Weekly news",
+            "markdown_content": "Hi **{{ name }}**",
+            "idempotency_key": key,
+        })
+    };
+    let (body1, body2) = (body("first-key"), body("second-key"));
+
+    // Act
+    let (response1, response2) = tokio::join!(
+        app.post_publish_newsletter(&body1),
+        app.post_publish_newsletter(&body2)
+    );
+
+    // Assert
+    assert_is_redirect_to(&response1, "/admin/newsletters");
+    assert_is_redirect_to(&response2, "/admin/newsletters");
+    let mut slugs: Vec<String> = sqlx::query_scalar!("SELECT slug FROM newsletter_issues")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    slugs.sort();
+    assert_eq!(slugs, ["weekly-news", "weekly-news-2"]);
+}
+
+#[tokio::test]
+async fn issues_can_be_kept_out_of_the_archive() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = publish_issue(
+        &app,
+        "Members only",
+        serde_json::json!({ "exclude_from_archive": "on" }),
+    )
+    .await;
+
+    // Act - Part 1 - The issue is not in the archive
+    let html_page = app.get_archive(1).await.text().await.unwrap();
+    assert!(!html_page.contains("Members only"));
+    let response = app.get_archived_issue("members-only").await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    // Act - Part 2 - Add it from its report
+    let response = app.post_archive_status(newsletter_issue_id, true).await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{newsletter_issue_id}"),
+    );
+    let report_html = app
+        .get_newsletter_issue_report(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(report_html.contains("<p><i>The issue is now in the public archive.</i></p>"));
+    assert!(report_html.contains(r#"<a href="/archive/members-only">"#));
+    let response = app.get_archived_issue("members-only").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act - Part 3 - Remove it again
+    app.post_archive_status(newsletter_issue_id, false).await;
+    let response = app.get_archived_issue("members-only").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn issues_that_do_not_render_as_templates_are_shown_as_written() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_issue(&app, "Recent issue", serde_json::json!({})).await;
+    // Published before merge tags existed, when braces were plain text.
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content,
+            published_at, in_archive, slug
+        )
+        VALUES ($1, 'Legacy issue', $2, $3, now() - interval '1 year', true, 'legacy-issue')
+        "#,
+        Uuid::new_v4(),
+        "Write {{ name }} or {% if %} in your templates.",
+        "<p>Write {{ name }} or {% if %} in your templates.</p>",
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act - Part 1 - Read the issue
+    let response = app.get_archived_issue("legacy-issue").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<p>Write {{ name }} or {% if %} in your templates.</p>"));
+
+    // Act - Part 2 - The other issues are still listed
+    let response = app.get_archive(1).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Recent issue"));
+    assert!(html_page.contains("Legacy issue"));
+
+    // Act - Part 3 - And in the feed
+    let response = app
+        .api_client
+        .get(format!("{}/feed.rss", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let feed = response.text().await.unwrap();
+    assert!(feed.contains("<title>Recent issue</title>"));
+    assert!(feed.contains("Write {{ name }} or {% if %} in your templates."));
+}
+
+#[tokio::test]
+async fn the_archive_is_paginated() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    for i in 1..=21 {
+        publish_issue(&app, &format!("Issue {i}"), serde_json::json!({})).await;
+    }
+
+    // Act - Part 1 - The most recent issues
+    let html_page = app.get_archive(1).await.text().await.unwrap();
+    assert!(html_page.contains(r#"<a href="/archive/issue-21">"#));
+    assert!(html_page.contains(r#"<a href="/archive/issue-2">"#));
+    assert!(!html_page.contains(r#"<a href="/archive/issue-1">"#));
+    assert!(html_page.contains(r#"<a href="/archive?page=2">Older issues -&gt;</a>"#));
+
+    // Act - Part 2 - The oldest issue
+    let html_page = app.get_archive(2).await.text().await.unwrap();
+    assert!(html_page.contains(r#"<a href="/archive/issue-1">"#));
+    assert!(html_page.contains(r#"<a href="/archive?page=1">&lt;- Newer issues</a>"#));
+    assert!(!html_page.contains("Older issues"));
+
+    // Act - Part 3 - Past the end
+    let response = app.get_archive(3).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn archived_issues_cannot_run_scripts() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = publish_issue(&app, "Issue", serde_json::json!({})).await;
+    // Issues published before their HTML was sanitized.
+    sqlx::query!(
+        r#"UPDATE newsletter_issues SET html_content = '<p onclick="steal()">Hi</p><script>steal()</script>'
+        WHERE newsletter_issue_id = $1"#,
+        newsletter_issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let html_page = app.get_archived_issue("issue").await.text().await.unwrap();
+
+    // Assert
+    assert!(html_page.contains("<p>Hi</p>"));
+    assert!(!html_page.contains("steal()"));
+}
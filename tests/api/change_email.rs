@@ -0,0 +1,98 @@
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_change_your_email() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "email": "new@example.com",
+            "current_password": &app.test_user.password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_current_password_is_required_to_change_the_email() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "email": "new@example.com",
+            "current_password": Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/email");
+    let html_page = app.get_change_email_html().await;
+    assert!(html_page.contains("<p><i>The current password is incorrect.</i></p>"));
+    assert!(html_page.contains(&format!("<b>{}</b>", app.test_user.email)));
+}
+
+#[tokio::test]
+async fn password_reset_links_are_sent_to_the_new_email() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Change the email
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "email": "new@example.com",
+            "current_password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/email");
+    let html_page = app.get_change_email_html().await;
+    assert!(html_page.contains("<p><i>Your email address has been changed.</i></p>"));
+
+    // Act - Part 2 - Ask for a password reset
+    app.post_logout().await;
+    app.post_password_reset_request(&serde_json::json!({ "email": "new@example.com" }))
+        .await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "new@example.com");
+}
+
+#[tokio::test]
+async fn an_invalid_email_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "email": "<script>alert(1)</script>",
+            "current_password": &app.test_user.password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/email");
+    let html_page = app.get_change_email_html().await;
+    assert!(html_page.contains("&lt;script&gt;alert(1)&lt;/script&gt; is not a valid"));
+    assert!(html_page.contains(&format!("<b>{}</b>", app.test_user.email)));
+}
@@ -98,6 +98,21 @@ async fn current_password_must_be_valid() {
     // Act - Part 3 - Follow the redirect
     let html_page = app.get_change_password_html().await;
     assert!(html_page.contains("<p><i>The current password is incorrect.</i></p>"));
+
+    // Act - Part 4 - The policy is not applied without the current password
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &wrong_password,
+            "new_password": &wrong_password,
+            "new_password_check": &wrong_password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+    let html_page = app.get_change_password_html().await;
+
+    // Assert
+    assert!(html_page.contains("<p><i>The current password is incorrect.</i></p>"));
+    assert!(!html_page.contains("must be different from the current one"));
 }
 
 #[tokio::test]
@@ -210,3 +225,53 @@ async fn changing_password_works() {
     let response = app.post_login(&login_body).await;
     assert_is_redirect_to(&response, "/admin/dashboard");
 }
+
+#[tokio::test]
+async fn new_password_must_follow_the_password_policy() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let test_cases = vec![
+        (
+            app.test_user.password.clone(),
+            "The new password must be different from the current one.",
+        ),
+        (
+            format!("{}-{}", app.test_user.username, Uuid::new_v4()),
+            "The new password must not contain your username.",
+        ),
+        (
+            "P@ssw0rd1".to_string(),
+            "The new password has appeared in a data breach and must not be used. \
+            Please choose another one.",
+        ),
+        (
+            "abcdefghijklmnop".to_string(),
+            "The new password is too easy to guess. Use a longer password and avoid \
+            repeated or sequential characters.",
+        ),
+    ];
+
+    for (new_password, error_message) in test_cases {
+        // Act - Part 1 - Try to change the password
+        let response = app
+            .post_change_password(&serde_json::json!({
+                "current_password": &app.test_user.password,
+                "new_password": &new_password,
+                "new_password_check": &new_password
+            }))
+            .await;
+
+        // Assert
+        assert_is_redirect_to(&response, "/admin/password");
+
+        // Act - Part 2 - Follow the redirect
+        let html_page = app.get_change_password_html().await;
+        assert!(
+            html_page.contains(&format!("<p><i>{}</i></p>", error_message)),
+            "The password `{}` was not rejected with `{}`.",
+            new_password,
+            error_message
+        );
+    }
+}
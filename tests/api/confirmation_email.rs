@@ -0,0 +1,84 @@
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+fn confirmation_email_body() -> serde_json::Value {
+    serde_json::json!({
+        "subject": "Please confirm",
+        "html_content": r#"<p>Hi {{ name }}, <a href="{{ confirmation_link }}">confirm</a>.</p>"#,
+        "text_content": "Hi {{ name }}, confirm at {{ confirmation_link }}",
+    })
+}
+
+#[tokio::test]
+async fn new_subscribers_get_the_confirmation_email_written_by_the_admin() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let response = app
+        .post_confirmation_email(&confirmation_email_body())
+        .await;
+    assert_is_redirect_to(&response, "/admin/confirmation-email");
+    let html_page = app.get_confirmation_email_html().await;
+    assert!(html_page.contains("<p><i>The confirmation email has been saved.</i></p>"));
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Subscribe
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(email["Subject"], "Please confirm");
+    assert!(email["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("<p>Hi le guin, "));
+    assert!(email["TextBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("Hi le guin, "));
+
+    // Act - Part 2 - Follow the link
+    let confirmation_links = app.get_confirmation_links(email_request);
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn a_confirmation_email_without_the_confirmation_link_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let mut body = confirmation_email_body();
+    body["text_content"] = "Hi {{ name }}, welcome!".into();
+
+    // Act
+    let response = app.post_confirmation_email(&body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/confirmation-email");
+    let html_page = app.get_confirmation_email_html().await;
+    assert!(html_page.contains(
+        "<p><i>The plain text content does not include {{ confirmation_link }}.</i></p>"
+    ));
+    let saved = sqlx::query!("SELECT subject FROM confirmation_email")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.subject, "Welcome!");
+}
@@ -0,0 +1,173 @@
+use crate::helpers::{assert_is_redirect_to, extract_csrf_token, spawn_app};
+
+#[tokio::test]
+async fn forms_embed_the_csrf_token_of_the_session() {
+    // Arrange
+    let app = spawn_app().await;
+    let anonymous_token = app.csrf_token().await;
+    assert_eq!(anonymous_token.len(), 32);
+    app.test_user.login(&app).await;
+    let session_token = app.csrf_token().await;
+
+    // Act
+    let pages = vec![
+        app.get_admin_dashboard_html().await,
+        app.get_change_password_html().await,
+        app.get_publish_newsletter_html().await,
+        app.get_two_factor_html().await,
+        app.get_sessions_html().await,
+        app.get_password_reset_request_html().await,
+    ];
+
+    // Assert
+    assert_ne!(session_token, anonymous_token);
+    for html_page in pages {
+        assert_eq!(extract_csrf_token(&html_page), session_token);
+    }
+}
+
+#[tokio::test]
+async fn the_csrf_token_picked_up_before_login_is_rejected_after_it() {
+    // Arrange
+    let app = spawn_app().await;
+    let anonymous_token = app.csrf_token().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/logout", &app.address))
+        .form(&serde_json::json!({ "csrf_token": anonymous_token }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn the_second_factor_form_does_not_start_a_session_for_visitors() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/login/two-factor", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    // No session cookie, and therefore no session state stored in Redis.
+    assert!(!response
+        .headers()
+        .get_all("Set-Cookie")
+        .iter()
+        .any(|cookie| cookie.to_str().unwrap().starts_with("id=")));
+}
+
+#[tokio::test]
+async fn a_login_without_a_csrf_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.get_login_html().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_state_changing_admin_request_with_a_wrong_csrf_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/password", &app.address))
+        .form(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+            "csrf_token": "a-token-forged-by-another-site",
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn the_csrf_token_of_another_session_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let other_app_token = spawn_app().await.csrf_token().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/logout", &app.address))
+        .form(&serde_json::json!({ "csrf_token": other_app_token }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn large_admin_forms_reach_the_handler() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    // Over the 256 KB default of raw payloads, under the limit of admin forms.
+    let markdown_content = "All work and no play makes Jack a dull boy.\n\n".repeat(7_000);
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "A long issue",
+            "markdown_content": markdown_content,
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("The newsletter issue has been accepted"));
+}
@@ -0,0 +1,273 @@
+use uuid::Uuid;
+use wiremock::matchers::any;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use zero2prod::configuration::get_configuration;
+use zero2prod::startup::Application;
+
+use crate::helpers::{
+    client_without_session, create_confirmed_subscriber, publish_issue, spawn_app, TestApp,
+};
+
+const EMAIL: &str = "ursula_le_guin@gmail.com";
+
+fn bounce(bounce_type: &str) -> serde_json::Value {
+    serde_json::json!({
+        "RecordType": "Bounce",
+        "ID": 4323372036854775807u64,
+        "Type": bounce_type,
+        "TypeCode": 1,
+        "Name": bounce_type,
+        "MessageID": Uuid::new_v4().to_string(),
+        "Description": "The server was unable to deliver your message.",
+        "Email": EMAIL,
+        "From": "test@email.com",
+        "BouncedAt": "2024-07-29T08:00:00Z",
+        "Inactive": true,
+    })
+}
+
+fn spam_complaint() -> serde_json::Value {
+    serde_json::json!({
+        "RecordType": "SpamComplaint",
+        "ID": 42,
+        "Type": "SpamComplaint",
+        "TypeCode": 512,
+        "Email": EMAIL,
+        "BouncedAt": "2024-07-29T08:00:00Z",
+    })
+}
+
+struct SubscriberState {
+    status: String,
+    n_soft_bounces: i32,
+}
+
+async fn subscriber_state(app: &TestApp) -> SubscriberState {
+    sqlx::query_as!(
+        SubscriberState,
+        "SELECT status, n_soft_bounces FROM subscriptions WHERE email = $1",
+        EMAIL
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn the_application_does_not_start_without_credentials() {
+    // Arrange
+    let mut configuration = get_configuration().expect("Failed to read configuration");
+    configuration.application.port = 0;
+    configuration.email_events = None;
+
+    // Act
+    let application = Application::build(configuration).await;
+
+    // Assert
+    assert!(application.is_err());
+}
+
+#[tokio::test]
+async fn events_without_valid_credentials_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let url = format!("{}/webhooks/email-events", &app.address);
+
+    for (request, description) in [
+        (client_without_session().post(&url), "no credentials"),
+        (
+            client_without_session()
+                .post(&url)
+                .basic_auth(&app.email_events.username, Some("not-the-password")),
+            "a wrong password",
+        ),
+    ] {
+        // Act
+        let response = request.json(&bounce("HardBounce")).send().await.unwrap();
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "The event was not rejected with {}.",
+            description
+        );
+        assert_eq!(
+            response.headers()["WWW-Authenticate"],
+            r#"Basic realm="email-events""#
+        );
+    }
+    assert_eq!(subscriber_state(&app).await.status, "confirmed");
+}
+
+#[tokio::test]
+async fn hard_bounces_and_complaints_suppress_the_subscriber() {
+    for event in [bounce("HardBounce"), spam_complaint()] {
+        // Arrange
+        let app = spawn_app().await;
+        create_confirmed_subscriber(&app).await;
+
+        // Act
+        let response = app.post_email_event(&event).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 200);
+        let state = subscriber_state(&app).await;
+        assert_eq!(state.status, "suppressed", "Not suppressed by {}", event);
+    }
+}
+
+#[tokio::test]
+async fn suppressed_subscribers_do_not_receive_newsletters() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_email_event(&bounce("HardBounce"))
+        .await
+        .error_for_status()
+        .unwrap();
+    app.test_user.login(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    // Mock verifies on Drop that we haven't sent the newsletter email
+}
+
+#[tokio::test]
+async fn suppression_is_reported_to_webhook_endpoints_once() {
+    // Arrange
+    let app = spawn_app().await;
+    let endpoint = MockServer::start().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    app.create_webhook(&endpoint.uri()).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&endpoint)
+        .await;
+
+    // Act
+    app.post_email_event(&bounce("HardBounce")).await;
+    app.post_email_event(&spam_complaint()).await;
+    app.dispatch_all_pending_webhooks().await;
+
+    // Assert
+    let request = &endpoint.received_requests().await.unwrap()[0];
+    let event: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(event["type"], "subscriber.bounced");
+    assert_eq!(event["data"]["email"], EMAIL);
+}
+
+#[tokio::test]
+async fn soft_bounces_are_counted_without_suppressing() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    // Act
+    for _ in 0..2 {
+        let response = app.post_email_event(&bounce("SoftBounce")).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Assert
+    let state = subscriber_state(&app).await;
+    assert_eq!(state.status, "confirmed");
+    assert_eq!(state.n_soft_bounces, 2);
+}
+
+#[tokio::test]
+async fn addresses_are_matched_regardless_of_case() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let mut soft_bounce = bounce("SoftBounce");
+    soft_bounce["Email"] = "Ursula_Le_Guin@Gmail.com".into();
+    let mut hard_bounce = bounce("HardBounce");
+    hard_bounce["Email"] = "URSULA_LE_GUIN@GMAIL.COM".into();
+
+    // Act
+    app.post_email_event(&soft_bounce).await;
+    app.post_email_event(&hard_bounce).await;
+
+    // Assert
+    let state = subscriber_state(&app).await;
+    assert_eq!(state.n_soft_bounces, 1);
+    assert_eq!(state.status, "suppressed");
+}
+
+#[tokio::test]
+async fn events_about_unknown_addresses_or_of_other_types_are_acknowledged() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut unknown_address = bounce("HardBounce");
+    unknown_address["Email"] = "someone_else@gmail.com".into();
+    let delivery = serde_json::json!({
+        "RecordType": "Delivery",
+        "Recipient": EMAIL,
+        "DeliveredAt": "2024-07-29T08:00:00Z",
+    });
+
+    for event in [unknown_address, delivery] {
+        // Act
+        let response = app.post_email_event(&event).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
+#[tokio::test]
+async fn malformed_events_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_email_event(&serde_json::json!({ "Email": EMAIL }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn emails_queued_before_a_hard_bounce_are_not_sent() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    publish_issue(&app, "Weekly news", serde_json::json!({})).await;
+    app.post_email_event(&bounce("HardBounce"))
+        .await
+        .error_for_status()
+        .unwrap();
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(subscriber_state(&app).await.status, "suppressed");
+}
@@ -0,0 +1,177 @@
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app, TestApp};
+
+fn layout_body(name: &str) -> serde_json::Value {
+    serde_json::json!({
+        "name": name,
+        "html_layout": r#"<div class="email">{{ content }}<a href="{{ unsubscribe_url }}">Unsubscribe</a></div>"#,
+        "text_layout": "{{ content }}\n--\nUnsubscribe: {{ unsubscribe_url }}",
+    })
+}
+
+/// Create a template through the admin UI and return its id.
+async fn create_template(app: &TestApp, body: &serde_json::Value) -> Uuid {
+    let response = app.post_create_email_template(body).await;
+    assert_is_redirect_to(&response, "/admin/templates");
+    sqlx::query!(
+        "SELECT template_id FROM email_templates WHERE name = $1",
+        body["name"].as_str().unwrap()
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .template_id
+}
+
+/// The body of the last email sent.
+async fn last_email(app: &TestApp) -> serde_json::Value {
+    let email_request = app.email_server.received_requests().await.unwrap().pop();
+    serde_json::from_slice(&email_request.unwrap().body).unwrap()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_email_templates() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_email_templates().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn issues_are_wrapped_in_the_chosen_template() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let template_id = create_template(&app, &layout_body("Weekly")).await;
+    assert!(app
+        .get_email_templates_html()
+        .await
+        .contains("The template Weekly has been created."));
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Hi {{ name }}",
+        "html_content": "<p>Hi {{ name }}</p>",
+        "template_id": template_id.to_string(),
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+    // Later changes to the template do not affect published issues.
+    sqlx::query!("DELETE FROM email_templates")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email = last_email(&app).await;
+    let unsubscribe_url = format!("{}/subscriptions/unsubscribe?token=", app.address);
+    let text = email["TextBody"].as_str().unwrap();
+    assert!(text.starts_with(&format!("Hi le guin\n--\nUnsubscribe: {unsubscribe_url}")));
+    let html = email["HtmlBody"].as_str().unwrap();
+    assert!(html.starts_with(&format!(
+        r#"<div class="email"><p>Hi le guin</p><a href="{unsubscribe_url}"#
+    )));
+}
+
+#[tokio::test]
+async fn layouts_without_the_content_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let mut body = layout_body("Weekly");
+    body["text_layout"] = "Unsubscribe: {{ unsubscribe_url }}".into();
+
+    // Act - Part 1 - Submit the form
+    let response = app.post_create_email_template(&body).await;
+    assert_is_redirect_to(&response, "/admin/templates");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_email_templates_html().await;
+
+    // Assert
+    assert!(
+        html_page.contains("<p><i>The plain text layout does not include {{ content }}.</i></p>")
+    );
+    let n_templates = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM email_templates"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_templates, 0);
+}
+
+#[tokio::test]
+async fn the_default_template_wraps_confirmation_emails() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let template_id = create_template(&app, &layout_body("Weekly")).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Make it the default
+    let mut body = layout_body("Transactional");
+    body["text_layout"] = "Hello!\n{{ content }}".into();
+    body["is_default"] = "on".into();
+    let response = app.post_update_email_template(template_id, &body).await;
+    assert_is_redirect_to(&response, &format!("/admin/templates/{template_id}"));
+    let html_page = app.get_email_template_html(template_id).await;
+    assert!(html_page.contains("Your changes have been saved."));
+    assert!(html_page.contains("<h1>Transactional</h1>"));
+
+    // Act - Part 2 - Subscribe
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let email = last_email(&app).await;
+    assert!(email["TextBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("Hello!\nWelcome to our newsletter!"));
+}
+
+#[tokio::test]
+async fn templates_can_be_deleted() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let template_id = create_template(&app, &layout_body("Weekly")).await;
+
+    // Act
+    let response = app
+        .post_delete_email_template(&serde_json::json!({ "template_id": template_id }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/templates");
+    let html_page = app.get_email_templates_html().await;
+    assert!(html_page.contains("The template has been deleted."));
+    assert!(!html_page.contains("Weekly"));
+    assert_eq!(
+        app.get_email_template(template_id).await.status().as_u16(),
+        404
+    );
+}
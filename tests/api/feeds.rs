@@ -0,0 +1,135 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::helpers::{publish_issue, spawn_app, TestApp};
+
+async fn get_feed(app: &TestApp, path: &str, headers: &[(&str, &str)]) -> reqwest::Response {
+    let mut request = app.api_client.get(format!("{}{}", &app.address, path));
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    request.send().await.expect("Failed to execute request")
+}
+
+async fn published_at(app: &TestApp, newsletter_issue_id: Uuid) -> DateTime<Utc> {
+    sqlx::query!(
+        "SELECT published_at FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        newsletter_issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .published_at
+}
+
+#[tokio::test]
+async fn the_rss_feed_lists_the_archived_issues() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = publish_issue(&app, "Issue & co", serde_json::json!({})).await;
+    publish_issue(
+        &app,
+        "Members only",
+        serde_json::json!({ "exclude_from_archive": "on" }),
+    )
+    .await;
+
+    // Act
+    let response = get_feed(&app, "/feed.rss", &[]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/rss+xml; charset=utf-8"
+    );
+    let feed = response.text().await.unwrap();
+    assert!(feed.contains("<title>Issue &amp; co</title>"));
+    assert!(feed.contains("<link>http://127.0.0.1/archive/issue-co</link>"));
+    assert!(feed.contains(&format!(
+        r#"<guid isPermaLink="false">urn:uuid:{newsletter_issue_id}</guid>"#
+    )));
+    let published_at = published_at(&app, newsletter_issue_id).await;
+    assert!(feed.contains(&format!("<pubDate>{}</pubDate>", published_at.to_rfc2822())));
+    // The content is escaped HTML.
+    assert!(feed.contains("&lt;p&gt;Hi &lt;strong&gt;reader&lt;/strong&gt;"));
+    assert!(!feed.contains("Members only"));
+}
+
+#[tokio::test]
+async fn the_atom_feed_lists_the_archived_issues() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = publish_issue(&app, "Issue", serde_json::json!({})).await;
+
+    // Act
+    let response = get_feed(&app, "/feed.atom", &[]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/atom+xml; charset=utf-8"
+    );
+    let feed = response.text().await.unwrap();
+    assert!(feed.contains(r#"<link href="http://127.0.0.1/archive/issue"/>"#));
+    assert!(feed.contains(&format!("<id>urn:uuid:{newsletter_issue_id}</id>")));
+    let published_at = published_at(&app, newsletter_issue_id)
+        .await
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    assert!(feed.contains(&format!("<published>{published_at}</published>")));
+    assert!(feed.contains(&format!("<updated>{published_at}</updated>")));
+}
+
+#[tokio::test]
+async fn feeds_support_conditional_requests() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_issue(&app, "First issue", serde_json::json!({})).await;
+    let response = get_feed(&app, "/feed.atom", &[]).await;
+    let etag = response.headers()["ETag"].to_str().unwrap().to_owned();
+    let last_modified = response.headers()["Last-Modified"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    // Act - Part 1 - Nothing has changed
+    let response = get_feed(&app, "/feed.atom", &[("If-None-Match", &etag)]).await;
+    assert_eq!(response.status().as_u16(), 304);
+    assert_eq!(response.headers()["ETag"], etag.as_str());
+    assert!(response.text().await.unwrap().is_empty());
+    let response = get_feed(&app, "/feed.atom", &[("If-Modified-Since", &last_modified)]).await;
+    assert_eq!(response.status().as_u16(), 304);
+
+    // Act - Part 2 - A new issue is published
+    publish_issue(&app, "Second issue", serde_json::json!({})).await;
+    let response = get_feed(&app, "/feed.atom", &[("If-None-Match", &etag)]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_ne!(response.headers()["ETag"], etag.as_str());
+    assert!(response.text().await.unwrap().contains("Second issue"));
+}
+
+#[tokio::test]
+async fn feeds_are_valid_when_nothing_was_published() {
+    // Arrange
+    let app = spawn_app().await;
+
+    for path in ["/feed.rss", "/feed.atom"] {
+        // Act
+        let response = get_feed(&app, path, &[]).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 200);
+        assert!(response.headers().get("Last-Modified").is_none());
+        assert!(response
+            .text()
+            .await
+            .unwrap()
+            .starts_with(r#"<?xml version="1.0" encoding="utf-8"?>"#));
+    }
+}
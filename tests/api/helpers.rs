@@ -1,13 +1,22 @@
+use actix_web::dev::ServerHandle;
 use argon2::{password_hash::SaltString, Argon2};
 use argon2::{Algorithm, Params, PasswordHasher, Version};
 use once_cell::sync::Lazy;
+use rand::Rng;
+use secrecy::{ExposeSecret, Secret};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 use zero2prod::email_client::EmailClient;
 use zero2prod::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
+use zero2prod::tracking::TrackingLinks;
+use zero2prod::webhook_delivery_worker::{self, webhook_http_client};
 use zero2prod::{
-    configuration::{get_configuration, DatabaseSettings, Settings},
+    configuration::{
+        get_configuration, DatabaseSettings, EmailDeliverySettings, EmailEventsSettings, Settings,
+        WebhookSettings,
+    },
     startup::{get_connection_pool, Application},
     telemetry::{get_subscriber, init_subscriber},
 };
@@ -34,6 +43,19 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub email_delivery_settings: EmailDeliverySettings,
+    pub webhook_settings: WebhookSettings,
+    pub email_events: EmailEventsSettings,
+    pub tracking_links: TrackingLinks,
+    server_handle: ServerHandle,
+}
+
+impl Drop for TestApp {
+    /// Stop the server, otherwise its workers outlive the test and keep
+    /// their database connections open.
+    fn drop(&mut self) {
+        tokio::spawn(self.server_handle.stop(false));
+    }
 }
 
 /// Confirmation links embedded in the request to the email API
@@ -45,10 +67,32 @@ pub struct ConfirmationLinks {
 impl TestApp {
     pub async fn dispatch_all_pending_emails(&self) {
         loop {
-            if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
-                    .await
-                    .unwrap()
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.tracking_links,
+                &self.email_delivery_settings,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    /// Deliveries that failed are postponed, so they are not retried here
+    /// unless the retry delay is zero.
+    pub async fn dispatch_all_pending_webhooks(&self) {
+        let http_client = webhook_http_client(&self.webhook_settings);
+        loop {
+            if let ExecutionOutcome::EmptyQueue = webhook_delivery_worker::try_execute_task(
+                &self.db_pool,
+                &http_client,
+                &self.webhook_settings,
+            )
+            .await
+            .unwrap()
             {
                 break;
             }
@@ -57,7 +101,7 @@ impl TestApp {
 
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -70,10 +114,10 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/newsletters", &self.address))
+            .post(format!("{}/admin/newsletters", &self.address))
             // Random credentials!
             .basic_auth(&self.test_user.username, Some(&self.test_user.password))
-            .form(&body)
+            .form(&self.with_csrf_token(&body).await)
             .send()
             .await
             .expect("Failed to execute request")
@@ -81,7 +125,7 @@ impl TestApp {
 
     pub async fn get_publish_newsletter(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/newsletters", &self.address))
+            .get(format!("{}/admin/newsletters", &self.address))
             .basic_auth(&self.test_user.username, Some(&self.test_user.password))
             .send()
             .await
@@ -96,6 +140,83 @@ impl TestApp {
             .expect("Failed to get response text")
     }
 
+    pub async fn post_preview_newsletter<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/newsletters/preview", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// The newsletter form, starting from an earlier issue.
+    pub async fn get_publish_newsletter_from(
+        &self,
+        newsletter_issue_id: Uuid,
+    ) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters?from={}",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_newsletter_issue_report(
+        &self,
+        newsletter_issue_id: Uuid,
+    ) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters/{}",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_archive_status(
+        &self,
+        newsletter_issue_id: Uuid,
+        in_archive: bool,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/{}/archive",
+                &self.address, newsletter_issue_id
+            ))
+            .form(
+                &self
+                    .with_csrf_token(&serde_json::json!({ "in_archive": in_archive }))
+                    .await,
+            )
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_archive(&self, page: u32) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/archive?page={}", &self.address, page))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_archived_issue(&self, slug: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/archive/{}", &self.address, slug))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -104,7 +225,7 @@ impl TestApp {
             .post(format!("{}/login", &self.address))
             // This `reqwest` method makes sure that the body is URL-encoded
             // and the `Content-Type` header is set accordingly.
-            .form(body)
+            .form(&self.with_csrf_token(body).await)
             .send()
             .await
             .expect("Failed to execute request")
@@ -112,7 +233,7 @@ impl TestApp {
 
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", &self.address))
+            .get(format!("{}/login", &self.address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -131,7 +252,7 @@ impl TestApp {
 
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.address))
+            .get(format!("{}/admin/dashboard", &self.address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -139,7 +260,7 @@ impl TestApp {
 
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -161,7 +282,30 @@ impl TestApp {
             .post(format!("{}/admin/password", &self.address))
             // This `reqwest` method makes sure that the body is URL-encoded
             // and the `Content-Type` header is set accordingly.
-            .form(body)
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_change_email_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/email", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_change_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/email", &self.address))
+            .form(&self.with_csrf_token(body).await)
             .send()
             .await
             .expect("Failed to execute request")
@@ -169,12 +313,444 @@ impl TestApp {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .post(format!("{}/admin/logout", &self.address))
+            .form(&self.with_csrf_token(&serde_json::json!({})).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_password_reset_request_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/password-reset", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .expect("Failed to get response text")
+    }
+
+    pub async fn post_password_reset_request<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password-reset", &self.address))
+            .form(&self.with_csrf_token(body).await)
             .send()
             .await
             .expect("Failed to execute request")
     }
 
+    pub async fn get_password_reset(&self, link: &reqwest::Url) -> reqwest::Response {
+        self.api_client
+            .get(link.clone())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_password_reset<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password-reset/confirm", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Follow a link to the unsubscribe or the preferences page.
+    pub async fn get_subscription_page(&self, link: &str) -> reqwest::Response {
+        self.api_client
+            .get(link)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_subscription_page_html(&self, link: &str) -> String {
+        self.get_subscription_page(link).await.text().await.unwrap()
+    }
+
+    pub async fn post_unsubscribe<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/subscriptions/unsubscribe", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_preferences<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/subscriptions/preferences", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_two_factor_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/two-factor", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .expect("Failed to get response text")
+    }
+
+    pub async fn post_enable_two_factor<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/two-factor/enable", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_disable_two_factor<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/two-factor/disable", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_second_factor_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/login/two-factor", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .expect("Failed to get response text")
+    }
+
+    pub async fn post_second_factor<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/login/two-factor", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_sessions_html(&self) -> String {
+        self.get_sessions().await.text().await.unwrap()
+    }
+
+    pub async fn post_revoke_session<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/sessions/revoke", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_revoke_other_sessions(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/sessions/revoke-others", &self.address))
+            .form(&self.with_csrf_token(&serde_json::json!({})).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_api_tokens(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/api-tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_api_tokens_html(&self) -> String {
+        self.get_api_tokens().await.text().await.unwrap()
+    }
+
+    /// The creation form can repeat the `scope` field, so its body is a list of pairs.
+    pub async fn post_create_api_token(&self, body: &[(&str, &str)]) -> reqwest::Response {
+        let csrf_token = self.csrf_token().await;
+        let mut body = body.to_vec();
+        body.push(("csrf_token", &csrf_token));
+        self.api_client
+            .post(format!("{}/admin/api-tokens", &self.address))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Create a token with the given scopes and return it.
+    pub async fn create_api_token(&self, scopes: &[&str]) -> String {
+        let mut body = vec![("name", "Test token")];
+        body.extend(scopes.iter().map(|scope| ("scope", *scope)));
+        let response = self.post_create_api_token(&body).await;
+        assert_eq!(response.status().as_u16(), 200);
+        extract_api_token(&response.text().await.unwrap())
+    }
+
+    pub async fn post_revoke_api_token<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/api-tokens/revoke", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_suppressions(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/suppressions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_suppressions_html(&self) -> String {
+        self.get_suppressions().await.text().await.unwrap()
+    }
+
+    /// Post one of the suppression list forms, e.g. `/delete` or `/import`.
+    pub async fn post_suppressions<Body>(&self, action: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/suppressions{}", &self.address, action))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Post a bounce or complaint as the email provider would.
+    pub async fn post_email_event(&self, body: &serde_json::Value) -> reqwest::Response {
+        client_without_session()
+            .post(format!("{}/webhooks/email-events", &self.address))
+            .basic_auth(
+                &self.email_events.username,
+                Some(self.email_events.password.expose_secret()),
+            )
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_webhooks(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/webhooks", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_webhooks_html(&self) -> String {
+        self.get_webhooks().await.text().await.unwrap()
+    }
+
+    pub async fn post_create_webhook<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/webhooks", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Register an endpoint and return its signing secret.
+    pub async fn create_webhook(&self, url: &str) -> String {
+        let response = self
+            .post_create_webhook(&serde_json::json!({ "url": url }))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+        extract_webhook_secret(&response.text().await.unwrap())
+    }
+
+    pub async fn post_delete_webhook<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/webhooks/delete", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_confirmation_email_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/confirmation-email", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_confirmation_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/confirmation-email", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_welcome_email_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/welcome-email", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_welcome_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/welcome-email", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_email_templates(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/templates", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_email_templates_html(&self) -> String {
+        self.get_email_templates().await.text().await.unwrap()
+    }
+
+    pub async fn post_create_email_template<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/templates", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_email_template(&self, template_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/templates/{}", &self.address, template_id))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_email_template_html(&self, template_id: Uuid) -> String {
+        self.get_email_template(template_id)
+            .await
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_update_email_template<Body>(
+        &self,
+        template_id: Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/templates/{}", &self.address, template_id))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_delete_email_template<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/templates/delete", &self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// The CSRF token of the current session, as embedded in the login form.
+    pub async fn csrf_token(&self) -> String {
+        let html_page = self.get_login_html().await;
+        extract_csrf_token(&html_page)
+    }
+
+    /// Add the CSRF token of the current session to a form body.
+    async fn with_csrf_token<Body>(&self, body: &Body) -> serde_json::Value
+    where
+        Body: serde::Serialize,
+    {
+        let mut body = serde_json::to_value(body).unwrap();
+        body["csrf_token"] = self.csrf_token().await.into();
+        body
+    }
+
     /// Extract the confirmation links embedded in the request ot the email API
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
@@ -206,6 +782,14 @@ impl TestApp {
 // If a fails happen, there is no need to propagate the error
 // Simply panic and crash everything
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Spawn the application after customising its configuration.
+pub async fn spawn_app_with<F>(configure: F) -> TestApp
+where
+    F: FnOnce(&mut Settings),
+{
     // The first time `initialize` is invoked the code in `TRACING` is executed.
     // All other invocations will instead skip execution.
     Lazy::force(&TRACING);
@@ -220,6 +804,19 @@ pub async fn spawn_app() -> TestApp {
         // use a random OS port
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        // Keep throttling tests fast
+        c.login_throttle.max_failures_per_username = 3;
+        c.login_throttle.max_failures_per_ip = 8;
+        c.login_throttle.base_delay_milliseconds = 1;
+        c.login_throttle.max_delay_milliseconds = 10;
+        // Test clients pose as coming from different IPs through
+        // `X-Forwarded-For`, as if we were behind a local proxy.
+        c.application.trusted_proxies = vec!["127.0.0.1".into()];
+        c.email_events = Some(EmailEventsSettings {
+            username: Uuid::new_v4().to_string(),
+            password: Secret::new(Uuid::new_v4().to_string()),
+        });
+        configure(&mut c);
         c
     };
 
@@ -231,33 +828,56 @@ pub async fn spawn_app() -> TestApp {
         .await
         .expect("Failed to build app");
     let application_port = application.port();
-    // let tokio handle the spawning of the server as a background task
-    tokio::spawn(async move {
-        if let Err(e) = application.run_until_stopped().await {
-            eprintln!("Server encountered an error: {:?}", e);
-        }
+    let server_handle = application.handle();
+    // Run the server on its own thread rather than as a task of the test
+    // runtime: the runtime is torn down with the test, before the server
+    // could process the stop command sent when `TestApp` is dropped.
+    std::thread::spawn(move || {
+        actix_web::rt::System::new().block_on(async move {
+            if let Err(e) = application.run_until_stopped().await {
+                eprintln!("Server encountered an error: {:?}", e);
+            }
+        })
     });
 
-    let client = reqwest::Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .cookie_store(true)
-        .build()
-        .unwrap();
+    // Failed logins are tracked per client IP in a Redis instance shared by
+    // all tests: give each test app its own address to keep them isolated.
+    let client = client_from_ip(&random_ip());
 
+    let address = format!("http://127.0.0.1:{}", application_port);
     let test_app = TestApp {
-        address: format!("http://127.0.0.1:{}", application_port),
+        // Unlike the configured base URL, the address includes the random port.
+        tracking_links: TrackingLinks::new(
+            address.clone(),
+            configuration.application.hmac_secret.clone(),
+        ),
+        address,
         port: application_port,
         db_pool: get_connection_pool(&configuration.database),
         email_server,
         test_user: TestUser::generate(),
         api_client: client,
-        email_client: configuration.email_client.client(),
+        email_client: configuration.email_client.clone().client(),
+        email_delivery_settings: configuration.email_delivery.clone(),
+        webhook_settings: configuration.webhooks.clone(),
+        email_events: configuration.email_events.clone().unwrap(),
+        server_handle,
     };
     test_app.test_user.store(&test_app.db_pool).await;
 
     test_app
 }
 
+pub fn random_ip() -> String {
+    let mut rng = rand::thread_rng();
+    format!(
+        "10.{}.{}.{}",
+        rng.gen::<u8>(),
+        rng.gen::<u8>(),
+        rng.gen::<u8>()
+    )
+}
+
 async fn configure_database(config: &DatabaseSettings) -> PgPool {
     // Create Database for testing
     let mut connection = PgConnection::connect_with(&config.without_db())
@@ -281,6 +901,106 @@ async fn configure_database(config: &DatabaseSettings) -> PgPool {
     connection_pool
 }
 
+/// The API token shown, once, right after its creation.
+pub fn extract_api_token(html_page: &str) -> String {
+    html_page
+        .split("<code>")
+        .nth(1)
+        .and_then(|rest| rest.split("</code>").next())
+        .expect("No API token in the page")
+        .to_string()
+}
+
+pub fn extract_webhook_secret(html_page: &str) -> String {
+    html_page
+        .split("<code>")
+        .nth(1)
+        .and_then(|rest| rest.split("</code>").next())
+        .expect("No webhook secret in the page")
+        .to_string()
+}
+
+/// Extract the CSRF token from the hidden field of a form.
+pub fn extract_csrf_token(html_page: &str) -> String {
+    html_page
+        .split(r#"name="csrf_token" value=""#)
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .expect("No CSRF token in the page.")
+        .to_owned()
+}
+
+/// A client with its own session, whose requests are forwarded for `ip`.
+pub fn client_from_ip(ip: &str) -> reqwest::Client {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert("X-Forwarded-For", ip.parse().unwrap());
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .default_headers(headers)
+        .build()
+        .unwrap()
+}
+
+/// A client that carries no session: API tokens are its only credentials.
+pub fn client_without_session() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+/// Subscribe a new subscriber and confirm their subscription.
+pub async fn create_confirmed_subscriber(app: &TestApp) {
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create confirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let ConfirmationLinks { html, .. } = app.get_confirmation_links(email_request);
+    reqwest::get(html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+/// Publish an issue written in Markdown through the admin form, with the
+/// extra fields of `extra`, and return its id.
+pub async fn publish_issue(app: &TestApp, title: &str, extra: serde_json::Value) -> Uuid {
+    let mut body = serde_json::json!({
+        "title": title,
+        "markdown_content": "Hi **{{ name }}**, [unsubscribe]({{unsubscribe_url}})",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    body.as_object_mut()
+        .unwrap()
+        .extend(extra.as_object().unwrap().clone());
+    let response = app.post_publish_newsletter(&body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    sqlx::query!(
+        "SELECT newsletter_issue_id FROM newsletter_issues ORDER BY published_at DESC LIMIT 1"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .newsletter_issue_id
+}
+
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("Location").unwrap(), location);
@@ -290,14 +1010,16 @@ pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
     pub password: String,
+    pub email: String,
 }
 
 impl TestUser {
     pub fn generate() -> Self {
         Self {
             user_id: Uuid::new_v4(),
-            username: "test".into(),
-            password: "test".into(),
+            username: Uuid::new_v4().to_string(),
+            password: Uuid::new_v4().to_string(),
+            email: "test@example.com".into(),
         }
     }
 
@@ -321,11 +1043,12 @@ impl TestUser {
         .to_string();
 
         sqlx::query!(
-            "INSERT INTO users (user_id, username, password_hash)
-            VALUES ($1, $2, $3)",
+            "INSERT INTO users (user_id, username, password_hash, email)
+            VALUES ($1, $2, $3, $4)",
             self.user_id,
             self.username,
             password_hash,
+            self.email,
         )
         .execute(pool)
         .await
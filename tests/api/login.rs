@@ -1,4 +1,36 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
+use secrecy::ExposeSecret;
+use uuid::Uuid;
+use zero2prod::configuration::get_configuration;
+
+use crate::helpers::{
+    assert_is_redirect_to, client_from_ip, extract_csrf_token, random_ip, spawn_app,
+    spawn_app_with, TestApp,
+};
+
+/// Try to log in from `client`, which has a session of its own.
+async fn post_login_with(
+    app: &TestApp,
+    client: &reqwest::Client,
+    body: &serde_json::Value,
+) -> reqwest::Response {
+    let login_form = client
+        .get(format!("{}/login", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let mut body = body.clone();
+    body["csrf_token"] = extract_csrf_token(&login_form).into();
+    client
+        .post(format!("{}/login", &app.address))
+        .form(&body)
+        .send()
+        .await
+        .unwrap()
+}
 
 #[tokio::test]
 async fn an_error_flash_message_is_set_on_failure() {
@@ -7,7 +39,7 @@ async fn an_error_flash_message_is_set_on_failure() {
 
     // Act - Part 1 - Try to login
     let login_body = serde_json::json!({
-        "username": "random-username",
+        "username": Uuid::new_v4().to_string(),
         "password": "random-password"
     });
     let response = app.post_login(&login_body).await;
@@ -43,3 +75,223 @@ async fn redirect_to_admin_dashboard_after_login_success() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
 }
+
+#[tokio::test]
+async fn an_account_is_locked_after_too_many_failures() {
+    // Arrange
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        app.post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": "wrong-password"
+        }))
+        .await;
+    }
+
+    // Act - Part 1 - Login with the right password
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_login_html().await;
+    assert!(
+        html_page.contains("<p><i>Too many failed login attempts. Please try again later.</i></p>")
+    );
+}
+
+#[tokio::test]
+async fn an_account_is_not_locked_for_other_clients() {
+    // Arrange
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        app.post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": "wrong-password"
+        }))
+        .await;
+    }
+
+    // Act
+    let response = post_login_with(
+        &app,
+        &client_from_ip(&random_ip()),
+        &serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }),
+    )
+    .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn forwarded_headers_from_untrusted_peers_do_not_reset_the_ip_limit() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.trusted_proxies = vec![]).await;
+    // Every test app shares the same Redis instance, and this one is seen
+    // as 127.0.0.1 whatever the header says: start from a clean slate.
+    let redis_uri = get_configuration().unwrap().redis_uri;
+    let redis = redis::Client::open(redis_uri.expose_secret().as_str()).unwrap();
+    let mut connection = redis.get_multiplexed_async_connection().await.unwrap();
+    let clear_failures = redis::cmd("DEL").arg("login_failures:ip:127.0.0.1").clone();
+    clear_failures
+        .query_async::<_, ()>(&mut connection)
+        .await
+        .unwrap();
+
+    // Act - Fail from a different spoofed address every time
+    for _ in 0..8 {
+        post_login_with(
+            &app,
+            &client_from_ip(&random_ip()),
+            &serde_json::json!({
+                "username": Uuid::new_v4().to_string(),
+                "password": "wrong-password"
+            }),
+        )
+        .await;
+    }
+    let client = client_from_ip(&random_ip());
+    let response = post_login_with(
+        &app,
+        &client,
+        &serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }),
+    )
+    .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    // Other tests connect from 127.0.0.1 too.
+    clear_failures
+        .query_async::<_, ()>(&mut connection)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn a_client_is_locked_after_too_many_failures_across_accounts() {
+    // Arrange
+    let app = spawn_app().await;
+    for _ in 0..8 {
+        app.post_login(&serde_json::json!({
+            "username": Uuid::new_v4().to_string(),
+            "password": "wrong-password"
+        }))
+        .await;
+    }
+
+    // Act - Part 1 - Login with the right credentials
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_login_html().await;
+    assert!(
+        html_page.contains("<p><i>Too many failed login attempts. Please try again later.</i></p>")
+    );
+}
+
+#[tokio::test]
+async fn a_successful_login_resets_the_failures_of_the_account() {
+    // Arrange
+    let app = spawn_app().await;
+    let wrong_login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password"
+    });
+    let login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    });
+
+    for _ in 0..2 {
+        // Act
+        app.post_login(&wrong_login_body).await;
+        app.post_login(&wrong_login_body).await;
+        let response = app.post_login(&login_body).await;
+
+        // Assert
+        assert_is_redirect_to(&response, "/admin/dashboard");
+    }
+}
+
+#[tokio::test]
+async fn a_password_hash_with_weak_parameters_is_upgraded_on_login() {
+    // Arrange
+    let app = spawn_app().await;
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let weak_hash = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(4096, 1, 1, None).unwrap(),
+    )
+    .hash_password(app.test_user.password.as_bytes(), &salt)
+    .unwrap()
+    .to_string();
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE user_id = $2",
+        weak_hash,
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // Assert - the upgrade happens in the background
+    let mut upgraded_hash = None;
+    for _ in 0..50 {
+        let hash = sqlx::query!(
+            "SELECT password_hash FROM users WHERE user_id = $1",
+            app.test_user.user_id
+        )
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .password_hash;
+        if hash != weak_hash {
+            upgraded_hash = Some(hash);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let upgraded_hash = upgraded_hash.expect("The password hash was not upgraded.");
+    assert!(upgraded_hash.contains("m=15000,t=2,p=1"));
+
+    // The upgraded hash still verifies the same password
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
@@ -1,8 +1,30 @@
 mod admin_dashboard;
+mod api_newsletters;
+mod api_subscribers;
+mod api_tokens;
+mod archive;
+mod change_email;
 mod change_password;
+mod confirmation_email;
+mod csrf;
+mod email_events;
+mod email_templates;
+mod feeds;
 mod health_check;
 mod helpers;
 mod login;
+mod merge_tags;
 mod newsletter;
+mod newsletter_html;
+mod newsletter_markdown;
+mod newsletter_tracking;
+mod openapi;
+mod password_reset;
+mod sessions;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_manage;
+mod suppressions;
+mod two_factor;
+mod webhooks;
+mod welcome_email;
@@ -0,0 +1,83 @@
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app};
+
+#[tokio::test]
+async fn merge_tags_are_filled_in_for_every_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Hi {{ name }} <{{ email }}>!\nUnsubscribe: {{ unsubscribe_url }}",
+        "html_content": r#"<p>Hi {{ name }}!</p><a href="{{ preferences_url }}">Preferences</a>"#,
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    // The confirmation email came first.
+    let email_request = app.email_server.received_requests().await.unwrap().pop();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.unwrap().body).unwrap();
+    let text = email["TextBody"].as_str().unwrap();
+    assert!(text.starts_with(&format!(
+        "Hi le guin <ursula_le_guin@gmail.com>!\nUnsubscribe: {}/subscriptions/unsubscribe?token=",
+        app.address
+    )));
+    let html = email["HtmlBody"].as_str().unwrap();
+    assert!(html.starts_with(&format!(
+        r#"<p>Hi le guin!</p><a href="{}/subscriptions/preferences?token="#,
+        app.address
+    )));
+}
+
+#[tokio::test]
+async fn issues_with_broken_placeholders_are_rejected_when_published() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Submit the form
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Hi {{ nmae }}",
+            "html_content": "<p>Hi {{ name }}</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains(
+        "<p><i>The plain text content is not a valid template: undefined value (line 1).</i></p>"
+    ));
+
+    // Assert
+    app.dispatch_all_pending_emails().await;
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 0);
+}
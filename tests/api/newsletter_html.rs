@@ -0,0 +1,142 @@
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{
+    assert_is_redirect_to, client_without_session, create_confirmed_subscriber, spawn_app,
+};
+
+#[tokio::test]
+async fn html_content_is_sanitized_and_its_css_inlined() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Publish
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<style>p { color: navy } a:hover { color: red }</style>\
+                <p onmouseover=\"steal()\">Hi {{ name }}</p><script>steal()</script>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains(
+        "<p><i>The newsletter issue has been accepted - emails will go out shortly.</i></p>"
+    ));
+    assert!(html_page
+        .contains("<p><i>These CSS rules cannot be inlined and were removed: a:hover.</i></p>"));
+
+    // Assert
+    app.dispatch_all_pending_emails().await;
+    let email_request = app.email_server.received_requests().await.unwrap().pop();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.unwrap().body).unwrap();
+    assert!(email["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .starts_with(r#"<p style="color: navy">Hi le guin</p><img src="#));
+}
+
+#[tokio::test]
+async fn template_statements_survive_the_sanitizer() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": r#"{% if name|length > 1 %}<p title='{{ name | replace("le", "Le") }}'>Hi {{ name | replace("le", "Le") }}</p>{% endif %}"#,
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app.email_server.received_requests().await.unwrap().pop();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.unwrap().body).unwrap();
+    assert!(email["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .starts_with(r#"<p title="Le guin">Hi Le guin</p>"#));
+}
+
+#[tokio::test]
+async fn authors_are_warned_about_emails_gmail_would_clip() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": format!("<p>{}</p>", "a".repeat(110 * 1024)),
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Assert
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains(
+        "<p><i>The HTML version is 111 KB: Gmail clips emails over 102 KB, hiding their end.</i></p>"
+    ));
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 1);
+}
+
+#[tokio::test]
+async fn issues_published_through_the_api_are_sanitized_too() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:publish"]).await;
+
+    // Act
+    let response = client_without_session()
+        .post(format!("{}/api/v1/newsletters", &app.address))
+        .bearer_auth(token)
+        .json(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p><script>steal()</script>",
+            }
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(issue["content"]["html"], "<p>Newsletter body as HTML</p>");
+}
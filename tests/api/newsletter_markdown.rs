@@ -0,0 +1,168 @@
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app};
+
+const MARKDOWN: &str = "Hi **{{ name }}**!\n\n[Read more](https://example.org/post)";
+
+#[tokio::test]
+async fn issues_written_in_markdown_are_sent_as_html_and_plain_text() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "markdown_content": MARKDOWN,
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app.email_server.received_requests().await.unwrap().pop();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.unwrap().body).unwrap();
+    assert_eq!(
+        email["TextBody"].as_str().unwrap(),
+        "Hi le guin!\n\nRead more (https://example.org/post)"
+    );
+    assert!(email["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("<p>Hi <strong>le guin</strong>!</p>\n<p><a href="));
+    let issue = sqlx::query!("SELECT markdown_content FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.markdown_content.as_deref(), Some(MARKDOWN));
+}
+
+#[tokio::test]
+async fn previews_show_both_versions_next_to_the_draft() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    // Act
+    let response = app
+        .post_preview_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "markdown_content": MARKDOWN,
+            "idempotency_key": idempotency_key,
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(
+        html_page.contains("<pre>Hi Ursula Le Guin!\n\nRead more (https://example.org/post)</pre>")
+    );
+    assert!(html_page.contains("<iframe sandbox"));
+    // The draft goes back into the form, to be published as is.
+    assert!(html_page.contains(&htmlescape::encode_attribute("Newsletter title")));
+    assert!(html_page.contains("Hi **{{ name }}**!"));
+    assert!(html_page.contains(&htmlescape::encode_attribute(&idempotency_key)));
+    app.dispatch_all_pending_emails().await;
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 0);
+}
+
+#[tokio::test]
+async fn previews_report_broken_placeholders() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .post_preview_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "markdown_content": "Hi {{ nmae }}",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    // Assert
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(
+        "<p><i>The HTML content is not a valid template: undefined value (line 1).</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn new_issues_can_start_from_the_markdown_of_an_earlier_one() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "markdown_content": MARKDOWN,
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+    let newsletter_issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    // Act - Part 1 - Follow the link of the report
+    let report_html = app
+        .get_newsletter_issue_report(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(report_html.contains(&format!(
+        r#"<a href="/admin/newsletters?from={newsletter_issue_id}">"#
+    )));
+    let response = app.get_publish_newsletter_from(newsletter_issue_id).await;
+
+    // Assert
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(&htmlescape::encode_attribute("Newsletter title")));
+    assert!(html_page.contains("Hi **{{ name }}**!"));
+}
+
+#[tokio::test]
+async fn issues_without_any_content_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "markdown_content": " ",
+            "html_content": "<p>Only HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,300 @@
+use fake::{faker::internet::en::SafeEmail, Fake};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, client_without_session, spawn_app, TestApp};
+
+/// Subscribe and confirm a new subscriber through the API.
+async fn create_confirmed_subscriber(app: &TestApp, token: &str) {
+    let email: String = SafeEmail().fake();
+    client_without_session()
+        .post(format!("{}/api/v1/subscribers", &app.address))
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "email": email, "name": "le guin", "confirmed": true }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+/// Publish an issue and deliver it to every confirmed subscriber.
+/// Returns the id of the issue and the emails that were sent.
+async fn publish_and_deliver(app: &TestApp) -> (Uuid, Vec<serde_json::Value>) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": r#"<html><body><p>Newsletter body as HTML.
+            <a href="https://example.org/post?id=1&amp;ref=mail">Read more</a>
+            <a href="mailto:editor@example.com">Reply</a></p></body></html>"#,
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let newsletter_issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    let emails = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect();
+    (newsletter_issue_id, emails)
+}
+
+/// The URL of the tracking pixel embedded in an email.
+fn pixel_url(email: &serde_json::Value) -> String {
+    let html = email["HtmlBody"].as_str().unwrap();
+    html.split(r#"<img src=""#)
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .expect("No tracking pixel in the email")
+        .to_owned()
+}
+
+/// The URL of the first link of an email.
+fn link_url(email: &serde_json::Value) -> String {
+    let html = email["HtmlBody"].as_str().unwrap();
+    html.split(r#"<a href=""#)
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .expect("No link in the email")
+        .to_owned()
+}
+
+/// Follow a link of an email without following the redirect.
+async fn click(url: &str) -> reqwest::Response {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(url)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn every_recipient_gets_their_own_tracking_pixel() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["subscribers:write"]).await;
+    create_confirmed_subscriber(&app, &token).await;
+    create_confirmed_subscriber(&app, &token).await;
+
+    // Act
+    let (_, emails) = publish_and_deliver(&app).await;
+
+    // Assert
+    assert_eq!(emails.len(), 2);
+    let pixel_urls: Vec<_> = emails.iter().map(pixel_url).collect();
+    assert_ne!(pixel_urls[0], pixel_urls[1]);
+    for (email, pixel_url) in emails.iter().zip(&pixel_urls) {
+        assert!(pixel_url.starts_with(&format!("{}/o/", app.address)));
+        // The pixel is part of the body, and the plain text is left alone.
+        assert!(email["HtmlBody"]
+            .as_str()
+            .unwrap()
+            .ends_with("</body></html>"));
+        assert_eq!(email["TextBody"], "Newsletter body as plain text");
+    }
+}
+
+#[tokio::test]
+async fn opening_an_issue_is_reported_on_its_admin_page() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["subscribers:write"]).await;
+    create_confirmed_subscriber(&app, &token).await;
+    create_confirmed_subscriber(&app, &token).await;
+    let (newsletter_issue_id, emails) = publish_and_deliver(&app).await;
+
+    // Act - Part 1 - The first recipient opens the issue twice
+    for _ in 0..2 {
+        let response = reqwest::get(pixel_url(&emails[0])).await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers()["Content-Type"], "image/gif");
+        assert_eq!(response.headers()["Cache-Control"], "no-store");
+        assert!(response.bytes().await.unwrap().starts_with(b"GIF89a"));
+    }
+
+    // Act - Part 2 - Look at the report
+    let html_page = app
+        .get_newsletter_issue_report(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(html_page.contains("<tr><th>Recipients</th><td>2</td></tr>"));
+    assert!(html_page.contains("<tr><th>Unique opens</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Open rate</th><td>50.0%</td></tr>"));
+    assert!(html_page.contains("<tr><th>Total opens</th><td>2</td></tr>"));
+    let opens = sqlx::query!("SELECT n_opens FROM newsletter_issue_opens")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(opens.len(), 1);
+    assert_eq!(opens[0].n_opens, 2);
+}
+
+#[tokio::test]
+async fn forged_tracking_pixels_are_not_found() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["subscribers:write"]).await;
+    create_confirmed_subscriber(&app, &token).await;
+    let (_, emails) = publish_and_deliver(&app).await;
+    let genuine_url = pixel_url(&emails[0]);
+    let (payload, _) = genuine_url.rsplit_once('.').unwrap();
+
+    // Act
+    let response = reqwest::get(format!("{payload}.AAAAAAAAAAAAAAAAAAAAAA"))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+    let n_opens = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issue_opens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_opens, 0);
+}
+
+#[tokio::test]
+async fn published_issues_are_listed_with_a_link_to_their_report() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (newsletter_issue_id, _) = publish_and_deliver(&app).await;
+
+    // Act
+    let html_page = app.get_publish_newsletter_html().await;
+
+    // Assert
+    assert!(html_page.contains(&format!(
+        r#"<a href="/admin/newsletters/{newsletter_issue_id}">Newsletter title</a>"#
+    )));
+}
+
+#[tokio::test]
+async fn reports_require_a_login_and_an_existing_issue() {
+    // Arrange
+    let app = spawn_app().await;
+    let newsletter_issue_id = Uuid::new_v4();
+
+    // Act - Part 1 - Anonymous
+    let response = app.get_newsletter_issue_report(newsletter_issue_id).await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 2 - Logged in
+    app.test_user.login(&app).await;
+    let response = app.get_newsletter_issue_report(newsletter_issue_id).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn links_to_web_pages_go_through_a_redirect_per_recipient() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["subscribers:write"]).await;
+    create_confirmed_subscriber(&app, &token).await;
+    create_confirmed_subscriber(&app, &token).await;
+
+    // Act
+    let (_, emails) = publish_and_deliver(&app).await;
+
+    // Assert
+    let link_urls: Vec<_> = emails.iter().map(link_url).collect();
+    assert_ne!(link_urls[0], link_urls[1]);
+    for (email, link_url) in emails.iter().zip(&link_urls) {
+        assert!(link_url.starts_with(&format!("{}/r/", app.address)));
+        let html = email["HtmlBody"].as_str().unwrap();
+        assert!(!html.contains("https://example.org/post"));
+        assert!(html.contains(r#"<a href="mailto:editor@example.com">Reply</a>"#));
+    }
+}
+
+#[tokio::test]
+async fn clicking_a_link_redirects_to_it_and_is_reported() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["subscribers:write"]).await;
+    create_confirmed_subscriber(&app, &token).await;
+    create_confirmed_subscriber(&app, &token).await;
+    let (newsletter_issue_id, emails) = publish_and_deliver(&app).await;
+
+    // Act - Part 1 - The first recipient clicks twice, the second once
+    for email in [&emails[0], &emails[0], &emails[1]] {
+        let response = click(&link_url(email)).await;
+        assert_eq!(response.status().as_u16(), 302);
+        assert_eq!(
+            response.headers()["Location"],
+            "https://example.org/post?id=1&ref=mail"
+        );
+    }
+
+    // Act - Part 2 - Look at the report
+    let html_page = app
+        .get_newsletter_issue_report(newsletter_issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(html_page.contains(
+        "<tr><td>https://example.org/post?id=1&amp;ref=mail</td><td>2</td><td>3</td></tr>"
+    ));
+}
+
+#[tokio::test]
+async fn forged_links_are_not_followed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["subscribers:write"]).await;
+    create_confirmed_subscriber(&app, &token).await;
+    let (_, emails) = publish_and_deliver(&app).await;
+    let genuine_url = link_url(&emails[0]);
+    let (_, signature) = genuine_url.rsplit_once('.').unwrap();
+    // Point the payload of a genuine link somewhere else.
+    let payload = base64::Engine::encode(
+        &base64::engine::general_purpose::URL_SAFE_NO_PAD,
+        [[0u8; 32].as_slice(), b"https://evil.example.com"].concat(),
+    );
+
+    // Act
+    let response = click(&format!("{}/r/{payload}.{signature}", app.address)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+    let n_clicks = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issue_clicks"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_clicks, 0);
+}
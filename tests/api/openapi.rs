@@ -0,0 +1,21 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn the_openapi_document_is_served() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!("{}/openapi.json", &app.address))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let document: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(document["openapi"], "3.1.0");
+    let subscribe = &document["paths"]["/subscriptions"]["post"];
+    assert!(subscribe["requestBody"]["content"]["application/x-www-form-urlencoded"].is_object());
+    assert!(subscribe["responses"]["400"].is_object());
+    assert!(document["paths"]["/subscriptions/confirm"]["get"]["responses"]["401"].is_object());
+}
@@ -0,0 +1,262 @@
+use uuid::Uuid;
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, extract_csrf_token, spawn_app, TestApp};
+
+/// Request a password reset for the test user and return the link
+/// that was emailed to them.
+async fn request_reset_link(app: &TestApp) -> reqwest::Url {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_password_reset_request(&serde_json::json!({
+        "email": &app.test_user.email
+    }))
+    .await;
+
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_confirmation_links(email_request).html
+}
+
+fn token_from_link(link: &reqwest::Url) -> String {
+    link.query_pairs()
+        .find(|(k, _)| k == "token")
+        .map(|(_, v)| v.into_owned())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn requesting_a_reset_sends_an_email_with_a_link() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let link = request_reset_link(&app).await;
+
+    // Assert
+    assert_eq!(link.path(), "/password-reset/confirm");
+    let response = app.get_password_reset(&link).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn the_email_address_is_matched_regardless_of_case() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_password_reset_request(&serde_json::json!({
+        "email": "Test@Example.COM"
+    }))
+    .await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    // The link goes to the address of the account, as it was stored.
+    assert_eq!(body["To"], app.test_user.email);
+}
+
+#[tokio::test]
+async fn requesting_a_reset_for_an_unknown_email_does_not_send_an_email() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Request a reset
+    let response = app
+        .post_password_reset_request(&serde_json::json!({
+            "email": "unknown@example.com"
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password-reset");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_password_reset_request_html().await;
+    assert!(html_page.contains(
+        "<p><i>If an account is associated with that email address, \
+        you will receive a password reset link shortly.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn a_reset_link_changes_the_password() {
+    // Arrange
+    let app = spawn_app().await;
+    let new_password = Uuid::new_v4().to_string();
+    let link = request_reset_link(&app).await;
+
+    // Act - Part 1 - Reset the password
+    let response = app
+        .post_password_reset(&serde_json::json!({
+            "token": token_from_link(&link),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Your password has been reset. You can now log in.</i></p>"));
+
+    // Act - Part 3 - Login with the new password
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_reset_link_can_only_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    let new_password = Uuid::new_v4().to_string();
+    let link = request_reset_link(&app).await;
+    let body = serde_json::json!({
+        "token": token_from_link(&link),
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    });
+    app.post_password_reset(&body).await;
+
+    // Act
+    let response = app.post_password_reset(&body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password-reset");
+    let response = app.get_password_reset(&link).await;
+    assert_is_redirect_to(&response, "/password-reset");
+}
+
+#[tokio::test]
+async fn an_expired_reset_link_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let new_password = Uuid::new_v4().to_string();
+    let link = request_reset_link(&app).await;
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app
+        .post_password_reset(&serde_json::json!({
+            "token": token_from_link(&link),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password-reset");
+    let html_page = app.get_password_reset_request_html().await;
+    assert!(html_page.contains("<p><i>The password reset link is invalid or has expired.</i></p>"));
+}
+
+#[tokio::test]
+async fn resetting_the_password_logs_out_existing_sessions() {
+    // Arrange
+    let app = spawn_app().await;
+    let new_password = Uuid::new_v4().to_string();
+    app.test_user.login(&app).await;
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let link = request_reset_link(&app).await;
+
+    // Act - Reset the password from another client
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let reset_page = client
+        .get(link.clone())
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let response = client
+        .post(format!("{}/password-reset/confirm", &app.address))
+        .form(&serde_json::json!({
+            "token": token_from_link(&link),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+            "csrf_token": extract_csrf_token(&reset_page),
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+
+    // Assert
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_reset_link_is_kept_when_the_new_password_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let link = request_reset_link(&app).await;
+    let token = token_from_link(&link);
+
+    // Act - Part 1 - Try a breached password
+    let response = app
+        .post_password_reset(&serde_json::json!({
+            "token": &token,
+            "new_password": "P@ssw0rd1",
+            "new_password_check": "P@ssw0rd1",
+        }))
+        .await;
+    assert_eq!(
+        response.headers().get("Location").unwrap(),
+        &format!("/password-reset/confirm?token={}", token)
+    );
+    let html_page = app.get_password_reset(&link).await.text().await.unwrap();
+    assert!(html_page.contains("has appeared in a data breach"));
+
+    // Act - Part 2 - The link can still be used with an acceptable password
+    let new_password = Uuid::new_v4().to_string();
+    let response = app
+        .post_password_reset(&serde_json::json!({
+            "token": &token,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
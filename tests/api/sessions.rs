@@ -0,0 +1,192 @@
+use std::time::Duration;
+
+use crate::helpers::{
+    assert_is_redirect_to, extract_csrf_token, random_ip, spawn_app, spawn_app_with, TestApp,
+};
+
+/// Log the test user in from another device, with its own cookie jar.
+async fn login_from_another_device(app: &TestApp, user_agent: &str) -> reqwest::Client {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert("X-Forwarded-For", random_ip().parse().unwrap());
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .user_agent(user_agent)
+        .default_headers(headers)
+        .build()
+        .unwrap();
+    let login_page = client
+        .get(format!("{}/login", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let response = client
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+            "csrf_token": extract_csrf_token(&login_page),
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    client
+}
+
+async fn get_dashboard(app: &TestApp, client: &reqwest::Client) -> reqwest::Response {
+    client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap()
+}
+
+/// The ids of the sessions that can be revoked from the listing page.
+fn revocable_session_ids(html_page: &str) -> Vec<String> {
+    html_page
+        .split(r#"name="session_id" value=""#)
+        .skip(1)
+        .map(|rest| rest.split('"').next().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_your_sessions() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_sessions().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn active_sessions_are_listed_with_their_browser() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    login_from_another_device(&app, "Another Browser/1.0").await;
+
+    // Act
+    let html_page = app.get_sessions_html().await;
+
+    // Assert
+    assert!(html_page.contains("<b>This session</b>"));
+    assert!(html_page.contains("Another Browser/1.0"));
+    assert_eq!(revocable_session_ids(&html_page).len(), 1);
+}
+
+#[tokio::test]
+async fn a_revoked_session_is_logged_out() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let other_device = login_from_another_device(&app, "Stolen Laptop/1.0").await;
+    let session_id = revocable_session_ids(&app.get_sessions_html().await)
+        .pop()
+        .unwrap();
+
+    // Act - Part 1 - Revoke the other session
+    let response = app
+        .post_revoke_session(&serde_json::json!({ "session_id": session_id }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/sessions");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("<p><i>The session has been revoked.</i></p>"));
+    assert!(!html_page.contains("Stolen Laptop/1.0"));
+
+    // Assert
+    let response = get_dashboard(&app, &other_device).await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn all_other_sessions_can_be_revoked_at_once() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let laptop = login_from_another_device(&app, "Laptop/1.0").await;
+    let phone = login_from_another_device(&app, "Phone/1.0").await;
+
+    // Act
+    let response = app.post_revoke_other_sessions().await;
+    assert_is_redirect_to(&response, "/admin/sessions");
+
+    // Assert
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("<p><i>All other sessions have been revoked.</i></p>"));
+    assert!(revocable_session_ids(&html_page).is_empty());
+    for client in [laptop, phone] {
+        let response = get_dashboard(&app, &client).await;
+        assert_is_redirect_to(&response, "/login");
+    }
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn an_idle_session_expires() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.session_idle_timeout_seconds = 1).await;
+    app.test_user.login(&app).await;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    // Act - Part 1 - Come back after the idle timeout
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Your session has expired. Please log in again.</i></p>"));
+
+    // Assert - The session has been purged
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(!html_page.contains("Your session has expired"));
+}
+
+#[tokio::test]
+async fn an_active_session_does_not_expire_while_in_use() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.session_idle_timeout_seconds = 2).await;
+    app.test_user.login(&app).await;
+
+    for _ in 0..4 {
+        // Act
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        let response = app.get_admin_dashboard().await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
+#[tokio::test]
+async fn a_session_expires_after_its_absolute_lifetime_even_if_in_use() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.session_absolute_timeout_seconds = 2).await;
+    app.test_user.login(&app).await;
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+
+    // Act
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Your session has expired. Please log in again.</i></p>"));
+}
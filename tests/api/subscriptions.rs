@@ -3,7 +3,7 @@ use wiremock::{
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::spawn_app;
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with};
 
 #[tokio::test]
 async fn subscribe_returns_a_200_for_valid_form_data() {
@@ -161,3 +161,123 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     // Assert
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[tokio::test]
+async fn subscribe_shows_a_page_asking_to_check_the_inbox() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/html; charset=utf-8"
+    );
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h1>Check your inbox</h1>"));
+}
+
+#[tokio::test]
+async fn subscribe_explains_what_is_wrong_with_the_form() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = [
+        (
+            "name=Ursula&email=%3Cb%3Enot-an-email",
+            "<p>&lt;b&gt;not-an-email is not a valid subscriber email.</p>",
+        ),
+        (
+            "name=Ursula",
+            "<p>Both your name and your email address are required.</p>",
+        ),
+    ];
+
+    for (body, error_html) in test_cases {
+        // Act
+        let response = app.post_subscriptions(body.into()).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 400);
+        let html_page = response.text().await.unwrap();
+        assert!(html_page.contains("<h1>We could not sign you up</h1>"));
+        assert!(html_page.contains(error_html), "{html_page}");
+    }
+}
+
+#[tokio::test]
+async fn subscribe_redirects_to_pages_of_our_own_sites() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.redirect_allow_list = vec!["https://www.example.com/blog".into()];
+    })
+    .await;
+    let test_cases = [
+        ("%2Farchive", "http://127.0.0.1/archive"),
+        (
+            "https%3A%2F%2Fwww.example.com%2Fblog%2Fthanks",
+            "https://www.example.com/blog/thanks",
+        ),
+    ];
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    for (i, (redirect_to, location)) in test_cases.into_iter().enumerate() {
+        // Act
+        let body = format!("name=le%20guin&email=ursula{i}%40gmail.com&redirect_to={redirect_to}");
+        let response = app.post_subscriptions(body).await;
+
+        // Assert
+        assert_is_redirect_to(&response, location);
+    }
+}
+
+#[tokio::test]
+async fn subscribe_rejects_redirects_to_other_sites() {
+    // Arrange
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    for redirect_to in [
+        "https%3A%2F%2Fevil.example.com%2F",
+        "%2F%2Fevil.example.com",
+    ] {
+        // Act
+        let body =
+            format!("name=le%20guin&email=ursula_le_guin%40gmail.com&redirect_to={redirect_to}");
+        let response = app.post_subscriptions(body).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 400);
+        assert!(response
+            .text()
+            .await
+            .unwrap()
+            .contains("is not a page of our site."));
+    }
+    let n_subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_subscribers, 0);
+}
@@ -3,7 +3,22 @@ use wiremock::{
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::spawn_app;
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+/// Subscribe, and return the confirmation link of the email.
+async fn subscribe(app: &TestApp) -> reqwest::Url {
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    app.get_confirmation_links(email_request).html
+}
 
 #[tokio::test]
 async fn confirmations_without_token_are_rejected_with_a_400() {
@@ -75,3 +90,76 @@ async fn clickling_on_the_confirmation_link_confirms_a_subscriber() {
     assert_eq!(saved.name, "le guin");
     assert_eq!(saved.status, "confirmed");
 }
+
+#[tokio::test]
+async fn confirming_shows_a_page_also_when_clicking_the_link_again() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_link = subscribe(&app).await;
+
+    // Act - Part 1 - Confirm
+    let response = reqwest::get(confirmation_link.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h1>Your subscription is confirmed</h1>"));
+
+    // Act - Part 2 - Click the link again
+    let response = reqwest::get(confirmation_link).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h1>You are already subscribed</h1>"));
+}
+
+#[tokio::test]
+async fn unknown_tokens_are_rejected_with_a_page() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(&format!(
+        "{}/subscriptions/confirm?subscription_token=unknown",
+        app.address
+    ))
+    .await
+    .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/html; charset=utf-8"
+    );
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h1>This link is invalid or has expired</h1>"));
+}
+
+#[tokio::test]
+async fn confirming_redirects_to_allowed_pages_only() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_link = subscribe(&app).await;
+
+    // Act - Part 1 - Another site: the subscriber is not confirmed
+    let mut link = confirmation_link.clone();
+    link.query_pairs_mut()
+        .append_pair("redirect_to", "https://evil.example.com/");
+    let response = app.api_client.get(link).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+    let status = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "pending_confirmation");
+
+    // Act - Part 2 - A page of our site
+    let mut link = confirmation_link;
+    link.query_pairs_mut()
+        .append_pair("redirect_to", "/archive");
+    let response = app.api_client.get(link).send().await.unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "http://127.0.0.1/archive");
+}
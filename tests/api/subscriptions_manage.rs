@@ -0,0 +1,180 @@
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app, TestApp};
+
+struct SubscriptionLinks {
+    unsubscribe: String,
+    preferences: String,
+}
+
+impl SubscriptionLinks {
+    fn token(&self) -> &str {
+        self.unsubscribe.split_once("?token=").unwrap().1
+    }
+}
+
+/// Deliver an issue to a new confirmed subscriber, returning the links
+/// it carries to manage their subscription.
+async fn subscription_links(app: &TestApp) -> SubscriptionLinks {
+    create_confirmed_subscriber(app).await;
+    app.test_user.login(app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "{{ unsubscribe_url }}\n{{ preferences_url }}",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app.email_server.received_requests().await.unwrap().pop();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.unwrap().body).unwrap();
+    let (unsubscribe, preferences) = email["TextBody"]
+        .as_str()
+        .unwrap()
+        .split_once('\n')
+        .unwrap();
+    SubscriptionLinks {
+        unsubscribe: unsubscribe.to_owned(),
+        preferences: preferences.to_owned(),
+    }
+}
+
+async fn subscriber_names(app: &TestApp) -> Vec<String> {
+    sqlx::query!("SELECT name FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.name)
+        .collect()
+}
+
+#[tokio::test]
+async fn subscribers_can_unsubscribe_from_the_link_in_an_issue() {
+    // Arrange
+    let app = spawn_app().await;
+    let links = subscription_links(&app).await;
+
+    // Act - Part 1 - Follow the link
+    let html_page = app.get_subscription_page_html(&links.unsubscribe).await;
+    assert!(html_page
+        .contains("Do you want to stop receiving our newsletter at ursula_le_guin@gmail.com?"));
+    // Opening the link is not enough, e.g. for link scanners.
+    assert_eq!(subscriber_names(&app).await.len(), 1);
+
+    // Act - Part 2 - Confirm
+    let response = app
+        .post_unsubscribe(&serde_json::json!({ "token": links.token() }))
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/subscriptions/unsubscribe?token={}", links.token()),
+    );
+
+    // Act - Part 3 - Follow the redirect
+    let html_page = app.get_subscription_page_html(&links.unsubscribe).await;
+
+    // Assert
+    assert!(html_page.contains("<p><i>ursula_le_guin@gmail.com has been unsubscribed.</i></p>"));
+    assert!(html_page.contains("You are not subscribed to our newsletter."));
+    assert!(subscriber_names(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn subscribers_can_change_their_name() {
+    // Arrange
+    let app = spawn_app().await;
+    let links = subscription_links(&app).await;
+
+    // Act - Part 1 - Follow the link
+    let html_page = app.get_subscription_page_html(&links.preferences).await;
+    assert!(html_page.contains("You receive our newsletter at ursula_le_guin@gmail.com."));
+
+    // Act - Part 2 - Submit an invalid name
+    let response = app
+        .post_preferences(&serde_json::json!({ "token": links.token(), "name": "<ursula>" }))
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/subscriptions/preferences?token={}", links.token()),
+    );
+    let html_page = app.get_subscription_page_html(&links.preferences).await;
+    assert!(html_page.contains("&lt;ursula&gt; is not a valid subscriber name."));
+    assert_eq!(subscriber_names(&app).await, ["le guin"]);
+
+    // Act - Part 3 - Submit a valid name
+    app.post_preferences(&serde_json::json!({ "token": links.token(), "name": "Ursula" }))
+        .await;
+    let html_page = app.get_subscription_page_html(&links.preferences).await;
+
+    // Assert
+    assert!(html_page.contains("Your preferences have been saved."));
+    assert_eq!(subscriber_names(&app).await, ["Ursula"]);
+}
+
+#[tokio::test]
+async fn forged_subscription_links_are_not_found() {
+    // Arrange
+    let app = spawn_app().await;
+    let links = subscription_links(&app).await;
+    let (payload, _) = links.token().split_once('.').unwrap();
+    let forged_token = format!("{payload}.AAAAAAAAAAAAAAAAAAAAAA");
+
+    // Act
+    let get_response = app
+        .get_subscription_page(&format!(
+            "{}/subscriptions/unsubscribe?token={forged_token}",
+            app.address
+        ))
+        .await;
+    let post_response = app
+        .post_unsubscribe(&serde_json::json!({ "token": forged_token }))
+        .await;
+
+    // Assert
+    assert_eq!(get_response.status().as_u16(), 404);
+    assert_eq!(post_response.status().as_u16(), 404);
+    assert_eq!(subscriber_names(&app).await.len(), 1);
+}
+
+#[tokio::test]
+async fn subscribers_who_left_after_publication_are_not_sent_the_issue() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    sqlx::query!("DELETE FROM subscription_tokens")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    sqlx::query!("DELETE FROM subscriptions")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    // Mock verifies on Drop that we haven't sent the newsletter email
+}
@@ -0,0 +1,170 @@
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app, TestApp};
+
+async fn suppress(app: &TestApp, email: &str) {
+    let response = app
+        .post_suppressions("", &serde_json::json!({ "email": email, "reason": "" }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_the_suppression_list() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_suppressions().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn suppressed_addresses_are_not_sent_confirmation_emails() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    // The list is case-insensitive.
+    suppress(&app, "Ursula_Le_Guin@gmail.com").await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // Assert
+    // Whoever subscribed is not told the address is suppressed.
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn suppressed_addresses_are_not_sent_newsletters_even_if_confirmed() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    suppress(&app, "ursula_le_guin@gmail.com").await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    // Mock verifies on Drop that we haven't sent the newsletter email
+}
+
+#[tokio::test]
+async fn removed_addresses_can_be_sent_emails_again() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    suppress(&app, "ursula_le_guin@gmail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Remove the address
+    let response = app
+        .post_suppressions(
+            "/delete",
+            &serde_json::json!({ "email": "ursula_le_guin@gmail.com" }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page.contains("ursula_le_guin@gmail.com can be sent emails again."));
+
+    // Act - Part 3 - Subscribe
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    // Mock verifies on Drop that the confirmation email was sent
+}
+
+#[tokio::test]
+async fn addresses_can_be_imported_in_bulk() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    suppress(&app, "postmaster@example.com").await;
+
+    // Act - Part 1 - Import
+    let response = app
+        .post_suppressions(
+            "/import",
+            &serde_json::json!({
+                "emails": "abuse@example.com\r\nPostmaster@example.com, <b>oops</b>\n\nsales@example.com",
+                "reason": "Role addresses",
+            }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_suppressions_html().await;
+
+    // Assert
+    assert!(html_page.contains("2 new addresses suppressed."));
+    assert!(
+        html_page.contains("These entries are not valid email addresses: &lt;b&gt;oops&lt;/b&gt;.")
+    );
+    assert!(html_page.contains("abuse@example.com"));
+    assert!(html_page.contains("sales@example.com"));
+    assert!(html_page.contains("Role addresses"));
+    let n_suppressed = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM suppressed_emails"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_suppressed, 3);
+}
+
+#[tokio::test]
+async fn invalid_addresses_cannot_be_suppressed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Submit the form
+    let response = app
+        .post_suppressions(
+            "",
+            &serde_json::json!({ "email": "not-an-email", "reason": "" }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_suppressions_html().await;
+
+    // Assert
+    assert!(html_page.contains("not-an-email is not a valid email address."));
+}
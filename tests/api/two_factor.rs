@@ -0,0 +1,320 @@
+use secrecy::Secret;
+use zero2prod::authentication::{time_step, totp_code};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+/// The code an authenticator app would show `offset` time steps from now.
+fn code_for(secret: &Secret<String>, offset: i64) -> String {
+    let now = time_step(chrono::Utc::now().timestamp() as u64);
+    totp_code(secret, now + offset).unwrap()
+}
+
+/// Enrol the logged-in test user and return their secret and recovery codes.
+async fn enable_two_factor(app: &TestApp) -> (Secret<String>, Vec<String>) {
+    let html_page = app.get_two_factor_html().await;
+    let secret = html_page
+        .split("enter the secret manually: <code>")
+        .nth(1)
+        .and_then(|s| s.split("</code>").next())
+        .unwrap()
+        .to_string();
+    let secret = Secret::new(secret);
+
+    let response = app
+        .post_enable_two_factor(&serde_json::json!({ "code": code_for(&secret, 0) }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    let recovery_codes = html_page
+        .split("<li><code>")
+        .skip(1)
+        .map(|s| s.split("</code>").next().unwrap().to_string())
+        .collect();
+
+    (secret, recovery_codes)
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_two_factor_authentication() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_enable_two_factor(&serde_json::json!({ "code": "123456" }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn enrolment_shows_a_provisioning_uri() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let html_page = app.get_two_factor_html().await;
+
+    // Assert
+    assert!(html_page.contains(&format!(
+        "otpauth://totp/zero2prod:{}?secret=",
+        app.test_user.username
+    )));
+    assert!(html_page.contains("<svg"));
+}
+
+#[tokio::test]
+async fn enrolment_requires_a_valid_code() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.get_two_factor_html().await;
+
+    // Act - Part 1 - Submit a wrong code
+    let response = app
+        .post_enable_two_factor(&serde_json::json!({ "code": "000000x" }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_two_factor_html().await;
+    assert!(html_page.contains("<p><i>Invalid authentication code.</i></p>"));
+    assert!(html_page.contains("is <b>disabled</b>"));
+}
+
+#[tokio::test]
+async fn enabling_two_factor_returns_recovery_codes() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let (_, recovery_codes) = enable_two_factor(&app).await;
+
+    // Assert
+    assert_eq!(recovery_codes.len(), 10);
+    let html_page = app.get_two_factor_html().await;
+    assert!(html_page.contains("is <b>enabled</b>"));
+}
+
+#[tokio::test]
+async fn login_asks_for_a_second_factor_when_enabled() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (secret, _) = enable_two_factor(&app).await;
+    app.post_logout().await;
+
+    // Act - Part 1 - Login with the password
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+
+    // Act - Part 2 - The admin area is still off-limits
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 3 - Submit the code
+    let response = app
+        .post_second_factor(&serde_json::json!({ "code": code_for(&secret, 1) }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // Assert
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn an_invalid_second_factor_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    enable_two_factor(&app).await;
+    app.post_logout().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Submit a wrong code
+    let response = app
+        .post_second_factor(&serde_json::json!({ "code": "not-a-code" }))
+        .await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_second_factor_html().await;
+    assert!(html_page.contains("<p><i>Invalid authentication code.</i></p>"));
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn too_many_invalid_second_factors_require_the_password_again() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (secret, _) = enable_two_factor(&app).await;
+    app.post_logout().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Guess until the pending login is dropped
+    for _ in 0..4 {
+        let response = app
+            .post_second_factor(&serde_json::json!({ "code": "000000" }))
+            .await;
+        assert_is_redirect_to(&response, "/login/two-factor");
+    }
+    let response = app
+        .post_second_factor(&serde_json::json!({ "code": "000000" }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts."));
+
+    // Act - Part 2 - Even a valid code is now refused
+    let response = app
+        .post_second_factor(&serde_json::json!({ "code": code_for(&secret, 1) }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn entering_the_password_again_does_not_grant_new_guesses() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (secret, _) = enable_two_factor(&app).await;
+    app.post_logout().await;
+    app.test_user.login(&app).await;
+    for _ in 0..5 {
+        app.post_second_factor(&serde_json::json!({ "code": "000000" }))
+            .await;
+    }
+
+    // Act - Part 1 - Log in again
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts."));
+
+    // Act - Part 2 - A valid code cannot be submitted either
+    let response = app
+        .post_second_factor(&serde_json::json!({ "code": code_for(&secret, 1) }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_code_cannot_be_used_twice() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (secret, _) = enable_two_factor(&app).await;
+    app.post_logout().await;
+
+    // Read back the step of the enrolment code: a new step may have started since.
+    let enrolment_step = sqlx::query_scalar!(
+        "SELECT totp_last_used_step FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .unwrap();
+
+    // Act - The code used for the enrolment is replayed
+    app.test_user.login(&app).await;
+    let code = totp_code(&secret, enrolment_step).unwrap();
+    let response = app
+        .post_second_factor(&serde_json::json!({ "code": code }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn a_recovery_code_can_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (_, recovery_codes) = enable_two_factor(&app).await;
+    app.post_logout().await;
+
+    // Act - Part 1 - Use a recovery code
+    app.test_user.login(&app).await;
+    let response = app
+        .post_second_factor(&serde_json::json!({ "code": &recovery_codes[0] }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.post_logout().await;
+
+    // Act - Part 2 - Reuse it
+    app.test_user.login(&app).await;
+    let response = app
+        .post_second_factor(&serde_json::json!({ "code": &recovery_codes[0] }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn the_second_factor_step_requires_a_password_first() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_second_factor(&serde_json::json!({ "code": "123456" }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn two_factor_can_be_disabled_with_a_valid_code() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (secret, _) = enable_two_factor(&app).await;
+
+    // Act - Part 1 - Disable two-factor authentication
+    let response = app
+        .post_disable_two_factor(&serde_json::json!({ "code": code_for(&secret, 1) }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_two_factor_html().await;
+    assert!(html_page.contains("<p><i>Two-factor authentication has been disabled.</i></p>"));
+
+    // Act - Part 3 - Login only requires the password again
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
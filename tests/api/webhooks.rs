@@ -0,0 +1,370 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, spawn_app, spawn_app_with, TestApp,
+};
+
+/// Subscribe someone, answering the confirmation email.
+async fn subscribe(app: &TestApp) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+}
+
+/// The events received by the endpoint, in order.
+async fn received_events(endpoint: &MockServer) -> Vec<serde_json::Value> {
+    endpoint
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect()
+}
+
+async fn n_pending_deliveries(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM webhook_outbox"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+async fn n_stored_events(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM webhook_events"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_webhooks() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_webhooks().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn new_subscribers_are_posted_to_the_endpoints_with_a_signature() {
+    // Arrange
+    let app = spawn_app().await;
+    let endpoint = MockServer::start().await;
+    app.test_user.login(&app).await;
+    let secret = app
+        .create_webhook(&format!("{}/hooks", endpoint.uri()))
+        .await;
+    Mock::given(path("/hooks"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&endpoint)
+        .await;
+
+    // Act
+    subscribe(&app).await;
+    app.dispatch_all_pending_webhooks().await;
+
+    // Assert
+    let request = &endpoint.received_requests().await.unwrap()[0];
+    let event: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(event["type"], "subscriber.created");
+    assert_eq!(event["data"]["email"], "ursula_le_guin@gmail.com");
+    assert_eq!(event["data"]["name"], "le guin");
+    assert_eq!(
+        request.headers.get("Webhook-Id").unwrap().to_str().unwrap(),
+        event["id"].as_str().unwrap()
+    );
+
+    // Receivers recompute the signature from the raw body.
+    let signature = request
+        .headers
+        .get("Webhook-Signature")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    let (timestamp, digest) = signature
+        .strip_prefix("t=")
+        .and_then(|rest| rest.split_once(",v1="))
+        .unwrap();
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{timestamp}.").as_bytes());
+    mac.update(&request.body);
+    mac.verify_slice(&hex::decode(digest).unwrap())
+        .expect("The signature does not match the body");
+    assert_eq!(n_pending_deliveries(&app).await, 0);
+    assert_eq!(n_stored_events(&app).await, 0);
+}
+
+#[tokio::test]
+async fn events_are_not_stored_without_endpoints() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    subscribe(&app).await;
+
+    // Assert
+    assert_eq!(n_stored_events(&app).await, 0);
+}
+
+#[tokio::test]
+async fn confirming_a_subscription_sends_a_confirmed_event() {
+    // Arrange
+    let app = spawn_app().await;
+    let endpoint = MockServer::start().await;
+    app.test_user.login(&app).await;
+    app.create_webhook(&endpoint.uri()).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&endpoint)
+        .await;
+
+    // Act
+    create_confirmed_subscriber(&app).await;
+    app.dispatch_all_pending_webhooks().await;
+
+    // Assert
+    let types: Vec<_> = received_events(&endpoint)
+        .await
+        .iter()
+        .map(|event| event["type"].as_str().unwrap().to_owned())
+        .collect();
+    assert_eq!(types, ["subscriber.created", "subscriber.confirmed"]);
+}
+
+#[tokio::test]
+async fn an_issue_is_reported_once_it_has_been_sent_to_every_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let endpoint = MockServer::start().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    app.create_webhook(&endpoint.uri()).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&endpoint)
+        .await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Publish
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_webhooks().await;
+    assert!(received_events(&endpoint).await.is_empty());
+
+    // Act - Part 2 - Deliver
+    app.dispatch_all_pending_emails().await;
+    app.dispatch_all_pending_webhooks().await;
+
+    // Assert
+    let events = received_events(&endpoint).await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["type"], "newsletter_issue.delivered");
+    assert_eq!(events[0]["data"]["title"], "Newsletter title");
+    assert_eq!(events[0]["data"]["recipients"], 1);
+}
+
+#[tokio::test]
+async fn an_issue_without_recipients_is_reported_right_away() {
+    // Arrange
+    let app = spawn_app().await;
+    let endpoint = MockServer::start().await;
+    app.test_user.login(&app).await;
+    app.create_webhook(&endpoint.uri()).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&endpoint)
+        .await;
+
+    // Act
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+    app.dispatch_all_pending_webhooks().await;
+
+    // Assert
+    let events = received_events(&endpoint).await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["type"], "newsletter_issue.delivered");
+    assert_eq!(events[0]["data"]["recipients"], 0);
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_later() {
+    // Arrange
+    let app = spawn_app().await;
+    let endpoint = MockServer::start().await;
+    app.test_user.login(&app).await;
+    app.create_webhook(&endpoint.uri()).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&endpoint)
+        .await;
+
+    // Act
+    subscribe(&app).await;
+    app.dispatch_all_pending_webhooks().await;
+
+    // Assert
+    let delivery = sqlx::query!(
+        r#"SELECT n_attempts, execute_after > now() AS "postponed!" FROM webhook_outbox"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(delivery.n_attempts, 1);
+    assert!(delivery.postponed);
+}
+
+#[tokio::test]
+async fn deliveries_are_dropped_after_too_many_failures() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.webhooks.max_attempts = 3;
+        c.webhooks.base_retry_delay_seconds = 0;
+    })
+    .await;
+    let endpoint = MockServer::start().await;
+    app.test_user.login(&app).await;
+    app.create_webhook(&endpoint.uri()).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500))
+        .expect(3)
+        .mount(&endpoint)
+        .await;
+
+    // Act
+    subscribe(&app).await;
+    app.dispatch_all_pending_webhooks().await;
+
+    // Assert
+    assert_eq!(n_pending_deliveries(&app).await, 0);
+    assert_eq!(n_stored_events(&app).await, 0);
+}
+
+#[tokio::test]
+async fn redirects_are_not_followed() {
+    // Arrange
+    let app = spawn_app().await;
+    let endpoint = MockServer::start().await;
+    let elsewhere = MockServer::start().await;
+    app.test_user.login(&app).await;
+    app.create_webhook(&endpoint.uri()).await;
+    Mock::given(any())
+        .respond_with(
+            ResponseTemplate::new(307).insert_header("Location", elsewhere.uri().as_str()),
+        )
+        .expect(1)
+        .mount(&endpoint)
+        .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&elsewhere)
+        .await;
+
+    // Act
+    subscribe(&app).await;
+    app.dispatch_all_pending_webhooks().await;
+
+    // Assert
+    let delivery = sqlx::query!(r#"SELECT n_attempts FROM webhook_outbox"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.n_attempts, 1);
+}
+
+#[tokio::test]
+async fn deleted_endpoints_receive_no_more_events() {
+    // Arrange
+    let app = spawn_app().await;
+    let endpoint = MockServer::start().await;
+    app.test_user.login(&app).await;
+    app.create_webhook(&endpoint.uri()).await;
+    subscribe(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&endpoint)
+        .await;
+    let endpoint_id = sqlx::query!("SELECT endpoint_id FROM webhook_endpoints")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .endpoint_id;
+
+    // Act - Part 1 - Delete the endpoint
+    let response = app
+        .post_delete_webhook(&serde_json::json!({ "endpoint_id": endpoint_id }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/webhooks");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_webhooks_html().await;
+    assert!(html_page.contains("The webhook endpoint has been deleted."));
+
+    // Act - Part 3 - Deliver
+    app.dispatch_all_pending_webhooks().await;
+
+    // Assert
+    assert_eq!(n_pending_deliveries(&app).await, 0);
+    assert_eq!(n_stored_events(&app).await, 0);
+}
+
+#[tokio::test]
+async fn only_http_urls_can_be_registered() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Submit the form
+    let response = app
+        .post_create_webhook(&serde_json::json!({ "url": "ftp://example.com/hooks" }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/webhooks");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_webhooks_html().await;
+    assert!(html_page.contains("ftp://example.com/hooks is not an HTTP URL."));
+    let n_endpoints = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM webhook_endpoints"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_endpoints, 0);
+}
@@ -0,0 +1,257 @@
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{
+    assert_is_redirect_to, client_without_session, create_confirmed_subscriber, spawn_app,
+    spawn_app_with, TestApp,
+};
+
+fn welcome_email_body() -> serde_json::Value {
+    serde_json::json!({
+        "enabled": "on",
+        "subject": "Welcome aboard",
+        "html_content": "<p>Welcome {{ name }}!</p><script>steal()</script>",
+        "text_content": "Welcome {{ name }}!",
+    })
+}
+
+async fn n_queued_emails(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+#[tokio::test]
+async fn new_subscribers_get_the_welcome_email_once_they_confirm() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let response = app.post_welcome_email(&welcome_email_body()).await;
+    assert_is_redirect_to(&response, "/admin/welcome-email");
+    let html_page = app.get_welcome_email_html().await;
+    assert!(html_page.contains("<p><i>The welcome email has been saved.</i></p>"));
+
+    // Act - Part 1 - Confirm: the email is queued, not sent
+    create_confirmed_subscriber(&app).await;
+    assert_eq!(n_queued_emails(&app).await, 1);
+
+    // Act - Part 2 - Deliver it
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app.email_server.received_requests().await.unwrap().pop();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.unwrap().body).unwrap();
+    assert_eq!(email["Subject"], "Welcome aboard");
+    assert_eq!(email["HtmlBody"], "<p>Welcome le guin!</p>");
+    assert_eq!(email["TextBody"], "Welcome le guin!");
+    assert_eq!(n_queued_emails(&app).await, 0);
+}
+
+#[tokio::test]
+async fn subscribers_confirmed_through_the_api_get_the_welcome_email() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_welcome_email(&welcome_email_body()).await;
+    let token = app
+        .create_api_token(&["subscribers:read", "subscribers:write"])
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let post_subscriber = |email: &str, confirmed: bool| {
+        client_without_session()
+            .post(format!("{}/api/v1/subscribers", &app.address))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({ "email": email, "name": "le guin", "confirmed": confirmed }))
+            .send()
+    };
+
+    // Act - Part 1 - Create a confirmed subscriber
+    let response = post_subscriber("ursula_le_guin@gmail.com", true)
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    assert_eq!(n_queued_emails(&app).await, 1);
+
+    // Act - Part 2 - Confirm a pending subscriber
+    let response = post_subscriber("octavia_butler@gmail.com", false)
+        .await
+        .unwrap();
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(n_queued_emails(&app).await, 1);
+    let response = client_without_session()
+        .patch(format!(
+            "{}/api/v1/subscribers/{}",
+            &app.address,
+            subscriber["id"].as_str().unwrap()
+        ))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "status": "confirmed" }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(n_queued_emails(&app).await, 2);
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_welcome_email(&welcome_email_body()).await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - The email is postponed
+    app.dispatch_all_pending_emails().await;
+    let task = sqlx::query!(
+        r#"SELECT n_attempts, execute_after > now() AS "postponed!" FROM issue_delivery_queue"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(task.n_attempts, 1);
+    assert!(task.postponed);
+
+    // Act - Part 2 - And sent once it is due
+    sqlx::query!("UPDATE issue_delivery_queue SET execute_after = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(n_queued_emails(&app).await, 0);
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_dropped_after_the_last_attempt() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.email_delivery.max_attempts = 3;
+        c.email_delivery.base_retry_delay_seconds = 0;
+    })
+    .await;
+    app.test_user.login(&app).await;
+    app.post_welcome_email(&welcome_email_body()).await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(n_queued_emails(&app).await, 0);
+}
+
+#[tokio::test]
+async fn nothing_is_sent_while_the_welcome_email_is_disabled() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let mut body = welcome_email_body();
+    body.as_object_mut().unwrap().remove("enabled");
+    app.post_welcome_email(&body).await;
+
+    // Act
+    create_confirmed_subscriber(&app).await;
+
+    // Assert
+    assert_eq!(n_queued_emails(&app).await, 0);
+    let html_page = app.get_welcome_email_html().await;
+    assert!(html_page.contains(r#"<input type="checkbox" name="enabled" value="on">"#));
+    assert!(html_page.contains(&htmlescape::encode_attribute("Welcome aboard")));
+}
+
+#[tokio::test]
+async fn emails_queued_before_the_welcome_email_was_disabled_are_not_sent() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_welcome_email(&welcome_email_body()).await;
+    create_confirmed_subscriber(&app).await;
+    let mut body = welcome_email_body();
+    body.as_object_mut().unwrap().remove("enabled");
+    app.post_welcome_email(&body).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(n_queued_emails(&app).await, 0);
+}
+
+#[tokio::test]
+async fn invalid_welcome_emails_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let test_cases = [
+        (
+            serde_json::json!({ "subject": " " }),
+            "The welcome email needs a subject, HTML and plain text content.",
+        ),
+        (
+            serde_json::json!({ "text_content": "Welcome {{ nmae }}!" }),
+            "The plain text content is not a valid template: undefined value (line 1).",
+        ),
+    ];
+
+    for (invalid_fields, error_message) in test_cases {
+        let mut body = welcome_email_body();
+        body.as_object_mut()
+            .unwrap()
+            .extend(invalid_fields.as_object().unwrap().clone());
+
+        // Act
+        let response = app.post_welcome_email(&body).await;
+
+        // Assert
+        assert_is_redirect_to(&response, "/admin/welcome-email");
+        let html_page = app.get_welcome_email_html().await;
+        assert!(html_page.contains(&format!("<p><i>{error_message}</i></p>")));
+    }
+    let n_saved = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM welcome_email"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_saved, 0);
+}